@@ -0,0 +1,68 @@
+// Build metadata for `--version`: the git commit, the build date, and the
+// libinput and libudev versions the program is built against, so a bug
+// report has them without anyone having to ask. Anything that can't be
+// found (like the commit, when building from a release tarball) comes out
+// as "unknown" rather than failing the build.
+
+use std::{
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+
+fn main() {
+    println!("cargo:rustc-env=L3FD_GIT_COMMIT={}", git_commit().unwrap_or_else(|| "unknown".to_string()));
+    println!("cargo:rustc-env=L3FD_BUILD_DATE={}", build_date());
+    println!("cargo:rustc-env=L3FD_LIBINPUT_VERSION={}", pkg_config_version("libinput"));
+    println!("cargo:rustc-env=L3FD_LIBUDEV_VERSION={}", pkg_config_version("libudev"));
+
+    // HEAD for switching branches, and the ref it points at for new commits
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    if let Some(head_ref) = std::fs::read_to_string(".git/HEAD").ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|head_ref| head_ref.trim().to_string()))
+    {
+        println!("cargo:rerun-if-changed=.git/{}", head_ref);
+    }
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
+
+
+fn output_of(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    let text = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !text.trim().is_empty()).then(|| text.trim().to_string())
+}
+
+
+fn git_commit() -> Option<String> {
+    let commit = output_of("git", &["rev-parse", "--short=12", "HEAD"])?;
+    let dirty = output_of("git", &["status", "--porcelain", "--untracked-files=no"]).is_some();
+    Some(if dirty { format!("{commit}-dirty") } else { commit })
+}
+
+
+fn pkg_config_version(library: &str) -> String {
+    output_of("pkg-config", &["--modversion", library]).unwrap_or_else(|| "unknown".to_string())
+}
+
+
+/// The date of the build, as YYYY-MM-DD in UTC. `SOURCE_DATE_EPOCH` is
+/// used over the clock, when it's set, so reproducible builds stay that way.
+fn build_date() -> String {
+    let secs = std::env::var("SOURCE_DATE_EPOCH").ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0));
+
+    // days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
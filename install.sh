@@ -6,6 +6,14 @@
 # this flag pops up in several places, so if I change it, 
 # I want the changes to be consistent
 LIBINPUT_INSTALLED_FLAG="--libinput-installed"
+SETGID_HELPER_FLAG="--setgid-helper"
+HELPER_NAME="linux-3-finger-drag-open-helper"
+
+# flags can come in any order, so check all args for them
+has-flag() {
+    [[ " $ALL_ARGS " == *" $1 "* ]]
+}
+ALL_ARGS="$*"
 
 # echo multi-line string (standard echo doesn't work well with tabs)
 # this also makes sure the printed lines wrap on spaces, not in the
@@ -102,7 +110,7 @@ if [[ $(whoami) != "root" ]]; then
 fi
 
 # 1. Check if libinput dev library is installed
-if [[ $1 != "$LIBINPUT_INSTALLED_FLAG" ]]; then
+if ! has-flag "$LIBINPUT_INSTALLED_FLAG"; then
     echo -ne "Verifying prerequisites...                      "
     ensure-libinput
 else
@@ -130,8 +138,11 @@ echo -n "Updating permissions...                         "
 mkdir -p /etc/udev/rules.d   # make if not already extant
 cp ./60-uinput.rules /etc/udev/rules.d/
 
-## Add user to "input" group to read libinput debug events
-gpasswd --add $SUDO_USER input > /dev/null
+## Add user to "input" group to read libinput debug events,
+## unless the setgid helper will be opening devices instead
+if ! has-flag "$SETGID_HELPER_FLAG"; then
+    gpasswd --add $SUDO_USER input > /dev/null
+fi
 
 ## Automatically load uinput kernel module
 ## Not necessary on Ubuntu-based distros,
@@ -190,6 +201,18 @@ else
     echo -e "[\e[0;32m DONE \e[0m]"
 fi
 
+# The helper is the one piece that runs with 'input' group permissions
+# (setgid), so the user doesn't need to be in the group themselves.
+if has-flag "$SETGID_HELPER_FLAG"; then
+    echo -n "Installing setgid helper to /usr/bin...         "
+    install -o root -g input -m 2755 ./target/release/$HELPER_NAME /usr/bin/
+    if [[ $? -ne 0 ]]; then
+        echo -e "[\e[0;31m FAIL \e[0m]"
+        exit 1
+    fi
+    echo -e "[\e[0;32m DONE \e[0m]"
+fi
+
 
 # Set up config file
 # Has to be done as non-root user, so the file is accessible to the user
@@ -0,0 +1,10 @@
+// The setgid `input` helper; see `init::open_helper` for what it's for.
+// This is kept as small as possible, since it runs with elevated privileges.
+
+use linux_3_finger_drag::init::open_helper;
+
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(open_helper::run_helper(&args));
+}
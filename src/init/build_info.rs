@@ -0,0 +1,49 @@
+// `version` (and `--version`): the version, plus what a bug report needs
+// to go with it. The commit, build date, and the libinput and libudev
+// versions built against come from build.rs; which libinput and libudev
+// actually got loaded (which can differ, after a distro upgrade) and the
+// kernel are looked up as the program runs.
+
+use std::{fs, path::PathBuf};
+
+
+/// Everything `version` prints, a line each.
+pub fn version_text() -> String {
+    format!(
+        "linux-3-finger-drag {}\n\
+        commit:   {}\n\
+        built:    {}\n\
+        libinput: {}\n\
+        libudev:  {}\n\
+        kernel:   {}",
+        env!("CARGO_PKG_VERSION"),
+        env!("L3FD_GIT_COMMIT"),
+        env!("L3FD_BUILD_DATE"),
+        library_text(env!("L3FD_LIBINPUT_VERSION"), "libinput.so"),
+        library_text(env!("L3FD_LIBUDEV_VERSION"), "libudev.so"),
+        fs::read_to_string("/proc/sys/kernel/osrelease")
+            .map(|release| release.trim().to_string())
+            .unwrap_or_else(|_| "unknown".to_string())
+    )
+}
+
+
+fn library_text(built_against: &str, file_prefix: &str) -> String {
+    match loaded_library(file_prefix) {
+        Some(path) => format!("{} (built against), loaded from {}", built_against, path.display()),
+        None => format!("{} (built against)", built_against)
+    }
+}
+
+
+/// Where the shared library whose file name starts with `file_prefix` was
+/// loaded from, going by this process's memory map, with symlinks
+/// resolved so the file name has the library's full version in it.
+fn loaded_library(file_prefix: &str) -> Option<PathBuf> {
+    let maps = fs::read_to_string("/proc/self/maps").ok()?;
+    let path = maps.lines()
+        .filter_map(|line| line.split_whitespace().nth(5))
+        .map(PathBuf::from)
+        .find(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with(file_prefix)))?;
+    Some(fs::canonicalize(&path).unwrap_or(path))
+}
@@ -0,0 +1,227 @@
+// The command line. With no command (or `run`), the program is the daemon
+// itself; the rest of the commands either talk to a running daemon over
+// the control socket (`stop`, `config`, ...), or do a job of their own and
+// exit (`check-config`, `calibrate`, ...). The daemon's flags are taken
+// both with and without `run`, so service files from before there were
+// commands keep working, as do the old `--self-test`, `--check-config` and
+// `--print-config-schema` spellings.
+
+use std::{path::PathBuf, time::Duration};
+
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
+
+use super::{
+    config::{self, ConfigFormat, Preset},
+    generate_config,
+    import::Tool
+};
+
+
+/// The whole command line, for `get_matches()`.
+pub fn command() -> Command {
+    Command::new("linux-3-finger-drag")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Three-finger dragging for Linux trackpads")
+        // clap's own only has the version number; this goes to `version`
+        .disable_version_flag(true)
+        .arg(Arg::new("version").short('V').long("version").action(ArgAction::SetTrue)
+            .help("Print the version, and what it was built with"))
+        .args(daemon_args())
+        .args_conflicts_with_subcommands(true)
+        .arg(Arg::new("config").long("config").value_name("PATH").global(true)
+            .value_parser(value_parser!(PathBuf))
+            .help("Use this config file, instead of looking in the usual places"))
+        // from before these were commands
+        .arg(Arg::new("self-test").long("self-test").action(ArgAction::SetTrue).hide(true))
+        .arg(Arg::new("check-config").long("check-config").action(ArgAction::SetTrue).hide(true))
+        .arg(Arg::new("print-config-schema").long("print-config-schema").action(ArgAction::SetTrue).hide(true))
+        .subcommand(Command::new("run")
+            .about("Start the daemon (the same as giving no command)")
+            .args(daemon_args()))
+        .subcommand(Command::new("check-config")
+            .about("Load the config file as the daemon would, and print what it comes out as"))
+        .subcommand(Command::new("version")
+            .about("Print the version, and what it was built with"))
+        .subcommand(Command::new("list-devices")
+            .about("List the input devices libinput can see, and which are trackpads"))
+        .subcommand(Command::new("status")
+            .about("Print whether an instance is running, and what it's up to"))
+        .subcommand(Command::new("stats")
+            .about("Print counts of the running instance's gestures, drags, and errors since it started")
+            .arg(Arg::new("json").long("json").action(ArgAction::SetTrue)
+                .help("Print them as JSON, for monitoring")))
+        .subcommand(Command::new("stop")
+            .about("Stop the running instance"))
+        .subcommand(Command::new("reexec")
+            .about("Restart the running instance in place, keeping its virtual trackpad"))
+        .subcommand(Command::new("capabilities")
+            .about("Print what the running instance can do on this desktop"))
+        .subcommand(Command::new("reload")
+            .about("Have the running instance read its config file again"))
+        .subcommand(Command::new("reopen-log")
+            .about("Have the running instance reopen its log file, after it's been rotated"))
+        .subcommand(Command::new("pause")
+            .about("Have the running instance stop dragging (letting go of any drag) until `resume`"))
+        .subcommand(Command::new("resume")
+            .about("Have the running instance start dragging again after `pause`"))
+        .subcommand(Command::new("dump-state")
+            .about("Print (and have the running instance log) what it's in the middle of, for a stuck button"))
+        .subcommand(Command::new("pointer-lock")
+            .about("Tell the running instance whether the pointer is locked")
+            .arg(Arg::new("state").required(true).value_parser(["on", "off", "auto"])))
+        .subcommand(Command::new("profile")
+            .about("Print the active profile, or switch to another (or none)")
+            .arg(Arg::new("name")))
+        .subcommand(Command::new("config")
+            .about("Read or change the running instance's config")
+            .subcommand_required(true)
+            .subcommand(Command::new("get")
+                .about("Print an option (or all of them)")
+                .arg(Arg::new("option")))
+            .subcommand(Command::new("set")
+                .about("Change an option")
+                .arg(Arg::new("option").required(true))
+                .arg(Arg::new("value").required(true))
+                .arg(Arg::new("persist").long("persist").action(ArgAction::SetTrue)
+                    .help("Also write it into the config file"))))
+        .subcommand(Command::new("simulate-drag")
+            .about("Perform a made-up drag through a fresh virtual trackpad")
+            .arg(Arg::new("dx").long("dx").value_name("UNITS").allow_negative_numbers(true)
+                .value_parser(value_parser!(f64)).default_value("400"))
+            .arg(Arg::new("dy").long("dy").value_name("UNITS").allow_negative_numbers(true)
+                .value_parser(value_parser!(f64)).default_value("0"))
+            .arg(Arg::new("duration").long("duration").value_name("TIME")
+                .value_parser(parse_duration).default_value("500ms")))
+        .subcommand(Command::new("test-gesture")
+            .about("Drag a square straight through a fresh virtual trackpad, to check it works without a trackpad")
+            .arg(Arg::new("size").long("size").value_name("PIXELS")
+                .value_parser(value_parser!(f64)).default_value("200")
+                .help("How far across the square is")))
+        .subcommand(Command::new("record")
+            .about("Record the trackpad's gestures to a file, for a bug report, without dragging")
+            .arg(Arg::new("path").required(true).value_parser(value_parser!(PathBuf))
+                .help("Where to write the trace (like trace.jsonl)")))
+        .subcommand(Command::new("replay")
+            .about("Play a recorded trace back through a fresh virtual trackpad, at its original timing")
+            .arg(Arg::new("path").required(true).value_parser(value_parser!(PathBuf))
+                .help("A trace written by record"))
+            .arg(Arg::new("dry-run").long("dry-run").action(ArgAction::SetTrue)
+                .help("Log the drags the trace makes, without creating a virtual trackpad"))
+            .arg(Arg::new("my-config").long("my-config").action(ArgAction::SetTrue)
+                .help("Use your config, instead of the one the trace was recorded with")))
+        .subcommand(Command::new("bench")
+            .about("Time drags from a made-up touchpad all the way through to the pointer, with your config")
+            .arg(Arg::new("updates").long("updates").value_name("COUNT")
+                .value_parser(value_parser!(u32).range(10..=100_000)).default_value("300")
+                .help("How many motion updates to time"))
+            .arg(Arg::new("response-time").long("response-time").value_name("TIME").value_parser(parse_duration)
+                .help("In place of responseTime in the config file")))
+        .subcommand(Command::new("bench-backends")
+            .about("Time the same made-up drag through each backend"))
+        .subcommand(Command::new("calibrate")
+            .about("Work out acceleration and dragEndDelay from a few gestures")
+            .arg(Arg::new("swipes").long("swipes").value_name("COUNT")
+                .value_parser(value_parser!(u8).range(1..=20)).default_value("3")
+                .help("How many full-width swipes to measure acceleration from")))
+        .subcommand(Command::new("install-udev-rule")
+            .about("Install the udev rule for /dev/uinput (run with sudo), and offer to add you to the input group"))
+        .subcommand(Command::new("doctor")
+            .about("Check what the program needs from the system, without changing anything"))
+        .subcommand(Command::new("setup")
+            .about("Check (and fix what it can of) what the program needs to run"))
+        .subcommand(Command::new("import")
+            .about("Convert settings from libinput-gestures, fusuma, or touchegg")
+            .arg(Arg::new("tool").value_parser(parse_tool))
+            .arg(Arg::new("path").requires("tool").help("The tool's config, if not where it usually is"))
+            .arg(Arg::new("save").long("save").action(ArgAction::SetTrue)
+                .help("Write the converted settings into the config file")))
+        .subcommand(Command::new("generate-config")
+            .about("Write a config file with every option at its default")
+            .arg(Arg::new("format").value_parser(parse_format).default_value("json"))
+            .arg(Arg::new("force").long("force").action(ArgAction::SetTrue)
+                .help("Replace an existing file")))
+        .subcommand(Command::new("self-test")
+            .about("Check that the virtual trackpad's events get through"))
+        .subcommand(Command::new("print-config-schema")
+            .about("Print a JSON Schema for the config file"))
+}
+
+
+/// The flags for starting the daemon, which also go before any command.
+fn daemon_args() -> [Arg; 13] {
+    [
+        Arg::new("preset").long("preset").value_name("NAME").value_parser(parse_preset)
+            .help("mac-like, precise, fast, or large-display, in place of the one in the config file"),
+        Arg::new("system").long("system").action(ArgAction::SetTrue)
+            .help("Run one instance for the whole system, following the active session"),
+        Arg::new("acceleration").long("acceleration").value_name("NUMBER").value_parser(value_parser!(f64))
+            .help("In place of acceleration in the config file"),
+        Arg::new("drag-end-delay").long("drag-end-delay").value_name("TIME").value_parser(parse_duration)
+            .help("In place of dragEndDelay in the config file"),
+        Arg::new("log-level").long("log-level").value_name("LEVEL")
+            .help("In place of logLevel in the config file"),
+        Arg::new("log-file").long("log-file").value_name("PATH")
+            .help("In place of logFile in the config file"),
+        Arg::new("strict").long("strict").action(ArgAction::SetTrue)
+            .help("Treat unknown config options as errors"),
+        Arg::new("dry-run").long("dry-run").action(ArgAction::SetTrue)
+            .help("Log the drags that would be made, without creating a virtual trackpad"),
+        Arg::new("device").long("device").value_name("PATH").action(ArgAction::Append)
+            .value_parser(value_parser!(PathBuf))
+            .help("Read gestures from this device (like /dev/input/event5) instead of searching; can be given more than once"),
+        Arg::new("daemon").long("daemon").action(ArgAction::SetTrue)
+            .help("Go into the background, writing the pid to a pidfile"),
+        Arg::new("pidfile").long("pidfile").value_name("PATH").requires("daemon")
+            .value_parser(value_parser!(PathBuf))
+            .help("Where --daemon writes the pid (by default, $XDG_RUNTIME_DIR/l3fd.pid)"),
+        Arg::new("foreground").long("foreground").action(ArgAction::SetTrue).conflicts_with("service")
+            .help("Print for a person at a terminal, in color (the default, unless the output goes to journald)"),
+        Arg::new("service").long("service").action(ArgAction::SetTrue)
+            .help("Print for journald: no pre-log lines, color, or timestamps, and startup reported to systemd"),
+    ]
+}
+
+
+/// The daemon's flags (from `command()`'s matches, or `run`'s) that stand
+/// in for config fields, as the fields they'd be in the config file.
+pub fn config_overrides(matches: &ArgMatches) -> serde_json::Map<String, serde_json::Value> {
+
+    let mut overrides = serde_json::Map::new();
+    if let Some(acceleration) = matches.get_one::<f64>("acceleration") {
+        overrides.insert("acceleration".to_string(), serde_json::json!(acceleration));
+    }
+    if let Some(delay) = matches.get_one::<Duration>("drag-end-delay") {
+        overrides.insert("dragEndDelay".to_string(), serde_json::json!(delay.as_millis() as u64));
+    }
+    if let Some(level) = matches.get_one::<String>("log-level") {
+        overrides.insert("logLevel".to_string(), serde_json::json!(level.to_lowercase()));
+    }
+    if let Some(path) = matches.get_one::<String>("log-file") {
+        overrides.insert("logFile".to_string(), serde_json::json!(path));
+    }
+    if matches.get_flag("strict") {
+        overrides.insert("strict".to_string(), serde_json::json!(true));
+    }
+    overrides
+}
+
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    config::parse_duration(value)
+        .ok_or_else(|| "expected a duration, like 300 or 300ms or 1.5s".to_string())
+}
+
+fn parse_preset(name: &str) -> Result<Preset, String> {
+    Preset::from_name(name)
+        .ok_or_else(|| "the presets are mac-like, precise, fast, and large-display".to_string())
+}
+
+fn parse_tool(name: &str) -> Result<Tool, String> {
+    Tool::from_name(name)
+        .ok_or_else(|| "the tools are libinput-gestures, fusuma, and touchegg".to_string())
+}
+
+fn parse_format(name: &str) -> Result<ConfigFormat, String> {
+    generate_config::format_from_name(name)
+        .ok_or_else(|| "the formats are json, toml, and yaml".to_string())
+}
@@ -1,23 +1,26 @@
-use serde::Deserialize;
-use serde_json::from_str;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{de::{self, Visitor}, Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::{schemars_1::JsonSchemaAs, DeserializeAs, SerializeAs};
+use serde_json::{from_str, json, Value};
 use std::{
-    fs::{File, read_to_string, OpenOptions}, 
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fs::{read_to_string, rename, write}, 
     io::ErrorKind, 
-    path::PathBuf, time::Duration
+    path::{Path, PathBuf}, 
+    sync::{Mutex, OnceLock, PoisonError},
+    time::Duration
 };
 
 use tracing_subscriber::{
-    filter::LevelFilter, 
-    fmt::{
-        format::{Format, Full, DefaultFields},
-        SubscriberBuilder,
-        time::ChronoLocal
-    }
+    filter::LevelFilter
 };
+
+use super::{log_file::ReopenableLog, run_mode};
 // This is simply a wrapper to allow deserialization of the
 // logLevel field into a simplelog::LevelFilter, albeit in
 // a roundabout way.
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum LogLevel { OFF, ERROR, WARN, INFO, DEBUG, TRACE }
 
@@ -37,52 +40,932 @@ impl From<LogLevel> for LevelFilter {
 }
 
 
+/// What a three-finger tap (fingers down and up again, without moving) clicks.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreeFingerTap { None, Left, Middle, Right }
+
+
+/// How a drag's speed depends on how fast the fingers move (see
+/// `runtime::accel_curve`).
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccelerationProfile { Adaptive, Flat, Linear }
+
+
+/// What `acceleration` (and anything else measured on the trackpad)
+/// is in: the trackpad's own units, or millimeters of finger travel.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SensitivityUnits { Raw, Mm }
+
+
+/// What a drag comes out as: a held mouse button, or a finger on a
+/// virtual touchscreen.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode { Pointer, Touch }
+
+
+/// Which mouse button a drag holds down.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DragButton { Left, Middle, Right }
+
+
+/// Settings that override the usual ones while a particular app is 
+/// focused (see `appProfiles`). Anything left out isn't overridden.
+#[serde_with::serde_as]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acceleration: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drag_button: Option<DragButton>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<Milliseconds>")]
+    pub drag_end_delay: Option<Duration>,   // in milliseconds
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointer_lock_passthrough: Option<bool>, // drags only move, without the button, while the pointer is locked
+}
+
+
+/// Settings for gestures from a particular trackpad (see `devices`).
+/// Anything left out isn't overridden.
+#[serde_with::serde_as]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acceleration: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<Milliseconds>")]
+    pub drag_end_delay: Option<Duration>,   // in milliseconds
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,              // false ignores the trackpad's gestures altogether
+}
+
+impl DeviceProfile {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled != Some(false)
+    }
+}
+
+
+/// One step in a gesture macro: a three-finger tap, or a whole
+/// three-finger swipe, by the direction it mostly went in.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MacroStep { Tap, SwipeLeft, SwipeRight, SwipeUp, SwipeDown }
+
+
+/// A sequence of gestures, performed within `within` of each other
+/// (first to last), and the command to run when it's recognized.
+#[serde_with::serde_as]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GestureMacro {
+    pub sequence: Vec<MacroStep>,
+
+    pub command: String,
+
+    #[serde(default = "default_800ms")]
+    #[serde_as(as = "Milliseconds")]
+    pub within: Duration,               // in milliseconds
+}
+
+
+/// A named starting point for the settings below. A preset only fills in
+/// the fields the config file leaves out, so any of them can still be
+/// tuned one by one on top of it.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset { MacLike, Precise, Fast, LargeDisplay }
+
+impl Preset {
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        serde_json::from_value(Value::String(name.to_string())).ok()
+    }
+
+    /// The fields this preset sets, as they'd be written in the config file.
+    fn values(self) -> Value {
+        match self {
+            // long enough to lift and re-place your fingers mid-drag, like on a Mac
+            Preset::MacLike => json!({
+                "acceleration": 1.0,
+                "dragEndDelay": 600,
+                "responseTime": 5
+            }),
+            // slower, for text selection and fine positioning
+            Preset::Precise => json!({
+                "acceleration": 0.6,
+                "dragEndDelay": 300,
+                "responseTime": 5
+            }),
+            // quicker, and the button comes up as soon as the fingers do
+            Preset::Fast => json!({
+                "acceleration": 1.8,
+                "dragEndDelay": 0,
+                "responseTime": 2
+            }),
+            // a lot of ground to cover, so faster and with time to reposition
+            Preset::LargeDisplay => json!({
+                "acceleration": 2.2,
+                "dragEndDelay": 800,
+                "responseTime": 5
+            })
+        }
+    }
+
+    /// Fills in whatever `options` (a config file's JSON) doesn't set
+    /// with this preset's values.
+    fn fill_in(self, options: &mut Value) {
+        let (Some(options), Value::Object(preset_values)) = (options.as_object_mut(), self.values()) else {
+            return;
+        };
+        for (key, value) in preset_values {
+            options.entry(key).or_insert(value);
+        }
+    }
+}
+
+
 #[serde_with::serde_as]  // this has to be before the #[derive]
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Configuration {
     #[serde(default = "default_1")]
     pub acceleration: f64,
 
+    #[serde(default = "default_adaptive")]
+    pub acceleration_profile: AccelerationProfile,
+
+    #[serde(default)]
+    pub acceleration_x: Option<f64>,    // in place of `acceleration`, for horizontal motion
+
+    #[serde(default)]
+    pub acceleration_y: Option<f64>,    // and for vertical motion
+
+    #[serde(default)]
+    pub active_profile: Option<String>, // which of `profiles` is in use
+
+    #[serde(default)]
+    pub app_profiles: BTreeMap<String, AppProfile>, // app ID/window class -> profile, in order for profile_for()
+
     #[serde(default = "default_0ms")]
-    #[serde_as(as = "serde_with::DurationMilliSeconds<u64>")]
+    #[serde_as(as = "Milliseconds")]
+    pub click_suppress_time: Duration,  // in milliseconds; 0 turns it off
+
+    #[serde(default)]
+    pub dbus_drag_events: bool,
+
+    #[serde(default)]
+    pub dead_zone: f64,                 // in trackpad units; 0 turns it off
+
+    #[serde(default)]
+    pub devices: HashMap<String, DeviceProfile>,    // device name or vendor:product -> profile
+
+    #[serde(default)]
+    pub disabled_devices: Vec<String>,  // trackpad names or sysnames, with * and ? globs
+
+    #[serde(default = "default_left_button")]
+    pub drag_button: DragButton,
+
+    #[serde(default)]
+    #[serde_as(as = "Option<Milliseconds>")]
+    pub drag_cancel_delay: Option<Duration>,    // in place of `drag_end_delay` when libinput cancels a swipe
+
+    #[serde(default = "default_0ms")]
+    #[serde_as(as = "Milliseconds")]
     pub drag_end_delay: Duration,       // in milliseconds
 
+    #[serde(default)]
+    pub gesture_macros: Vec<GestureMacro>,
+
+    #[serde(default)]
+    pub hold_to_right_click: bool,
+
+    #[serde(default)]
+    pub hyprland_disable_conflicts: bool,
+
+    #[serde(default)]
+    pub hyprland_drag_events: bool,
+
+    #[serde(default)]
+    pub idle_exit_hours: f64,           // 0 never exits
+
+    #[serde(default)]
+    pub invert_x: bool,
+
+    #[serde(default)]
+    pub invert_y: bool,
+
+    #[serde(default = "default_1px")]
+    pub jiggle_amplitude: u32,          // in pixels (well, relative motion units)
+
+    #[serde(default = "default_0ms")]
+    #[serde_as(as = "Milliseconds")]
+    pub jiggle_interval: Duration,      // in milliseconds; 0 turns jiggling off
+
     #[serde(default = "default_stdout")]
     pub log_file: String,
 
     #[serde(default = "default_info")]
     pub log_level: LogLevel,
 
+    #[serde(default)]
+    pub max_event_rate: u32,            // motion events per second; 0 means no limit
+
+    #[serde(default)]
+    pub max_speed: f64,                 // pixels per update, after acceleration; 0 means no limit
+
+    #[serde(default)]
+    pub one_finger_continue: bool,
+
+    #[serde(default = "default_pointer")]
+    pub output_mode: OutputMode,
+
+    #[serde(default)]
+    pub output_scales: HashMap<String, f64>,    // output name -> multiplier
+
+    #[serde(default)]
+    pub pause_for_apps: Vec<String>,    // app IDs/window classes
+
+    #[serde(default)]
+    pub pause_when_fullscreen: bool,
+
+    #[serde(default)]
+    pub preset: Option<Preset>,
+
+    #[serde(default)]
+    pub profiles: HashMap<String, serde_json::Map<String, Value>>,  // name -> the fields it changes
+
     #[serde(default = "default_5ms")]
-    #[serde_as(as = "serde_with::DurationMilliSeconds<u64>")]
-    pub response_time: Duration,        // in milliseconds
+    #[serde_as(as = "MillisecondsOrAuto")]
+    pub response_time: Option<Duration>,    // in milliseconds; none is "auto", following the trackpad
+
+    #[serde(default)]
+    pub resting_thumb_zone: f64,        // fraction of the trackpad's height, from the bottom; 0 turns it off
+
+    #[serde(default = "default_500ms")]
+    #[serde_as(as = "Milliseconds")]
+    pub right_click_hold_time: Duration,    // in milliseconds
+
+    #[serde(default)]
+    pub scale_by_output: bool,
+
+    #[serde(default = "default_raw")]
+    pub sensitivity_units: SensitivityUnits,
+
+    #[serde(default)]
+    pub start_threshold: f64,           // in trackpad units; 0 starts drags right away
+
+    #[serde(default)]
+    pub strict: bool,                   // unknown options are an error, not ignored
+
+    #[serde(default)]
+    pub suppress_tap_while_dragging: bool,
+
+    #[serde(default = "default_true")]
+    pub sync_desktop_speed: bool,
+
+    #[serde(default = "default_left")]
+    pub three_finger_tap: ThreeFingerTap,
+
+    #[serde(default)]
+    pub wayland: serde_json::Map<String, Value>,    // fields that change under Wayland
+
+    #[serde(default)]
+    pub x11: serde_json::Map<String, Value>,        // and under X11
+
+    #[serde(skip)]
+    pub source: Option<ConfigSource>,   // what this was loaded from, for switching profiles
 }
 
 impl Default for Configuration {
     fn default() -> Self {
         Configuration {
             acceleration: 1.0,
+            acceleration_profile: AccelerationProfile::Adaptive,
+            acceleration_x: None,
+            acceleration_y: None,
+            active_profile: None,
+            app_profiles: BTreeMap::new(),
+            click_suppress_time: Duration::from_millis(0),
+            dbus_drag_events: false,
+            dead_zone: 0.0,
+            devices: HashMap::new(),
+            disabled_devices: Vec::new(),
+            drag_button: DragButton::Left,
+            drag_cancel_delay: None,
             drag_end_delay: Duration::from_millis(0),
+            gesture_macros: Vec::new(),
+            hold_to_right_click: false,
+            hyprland_disable_conflicts: false,
+            hyprland_drag_events: false,
+            idle_exit_hours: 0.0,
+            invert_x: false,
+            invert_y: false,
+            jiggle_amplitude: 1,
+            jiggle_interval: Duration::from_millis(0),
             log_file: "stdout".to_string(),
             log_level: LogLevel::INFO,
-            response_time: Duration::from_millis(5)
+            max_event_rate: 0,
+            max_speed: 0.0,
+            one_finger_continue: false,
+            output_mode: OutputMode::Pointer,
+            output_scales: HashMap::new(),
+            pause_for_apps: Vec::new(),
+            pause_when_fullscreen: false,
+            preset: None,
+            profiles: HashMap::new(),
+            response_time: Some(Duration::from_millis(5)),
+            resting_thumb_zone: 0.0,
+            right_click_hold_time: Duration::from_millis(500),
+            scale_by_output: false,
+            sensitivity_units: SensitivityUnits::Raw,
+            start_threshold: 0.0,
+            strict: false,
+            suppress_tap_while_dragging: false,
+            sync_desktop_speed: true,
+            three_finger_tap: ThreeFingerTap::Left,
+            wayland: serde_json::Map::new(),
+            x11: serde_json::Map::new(),
+            source: None
+        }
+    }
+}
+
+// Anything past these is far more likely to be a typo (or a value in
+// the wrong units) than something anyone actually wants
+const MAX_DRAG_END_DELAY: Duration = Duration::from_secs(60 * 60);
+const MAX_RESPONSE_TIME: Duration  = Duration::from_secs(1);
+const MAX_RIGHT_CLICK_HOLD_TIME: Duration = Duration::from_secs(10);
+const MAX_JIGGLE_AMPLITUDE: u32 = 10;
+const MAX_MACRO_WINDOW: Duration = Duration::from_secs(10);
+const MAX_EVENT_RATE: u32 = 1000;
+const MAX_RESTING_THUMB_ZONE: f64 = 0.5;
+const MAX_CLICK_SUPPRESS_TIME: Duration = Duration::from_secs(2);
+const MAX_START_THRESHOLD: f64 = 1000.0;
+const MAX_DEAD_ZONE: f64 = 200.0;
+const MAX_IDLE_EXIT_HOURS: f64 = 24.0 * 365.0;
+
+// Delays past this are allowed, but they tend to make it seem like
+// the mouse button is stuck, so the user gets a heads-up about it
+const LONG_DRAG_END_DELAY: Duration = Duration::from_secs(5);
+
+impl Configuration {
+
+    /// Checks for values that would deserialize fine, but make the program
+    /// behave bizarrely, like a NaN acceleration or an hour-long response time.
+    pub fn validate(&self) -> Result<(), std::io::Error> {
+
+        let invalid = |msg: String| Err(std::io::Error::new(ErrorKind::InvalidData, msg));
+
+        if !self.acceleration.is_finite() || self.acceleration < 0.0 {
+            return invalid(format!(
+                "acceleration must be a non-negative number, but it's {}", 
+                self.acceleration
+            ));
+        }
+        for (name, accel) in [("accelerationX", self.acceleration_x), ("accelerationY", self.acceleration_y)] {
+            if accel.is_some_and(|accel| !accel.is_finite() || accel < 0.0) {
+                return invalid(format!(
+                    "{} must be a non-negative number, but it's {}",
+                    name, accel.unwrap_or_default()
+                ));
+            }
+        }
+
+        for (app, profile) in &self.app_profiles {
+            if profile.acceleration.is_some_and(|accel| !accel.is_finite() || accel < 0.0) {
+                return invalid(format!(
+                    "acceleration must be a non-negative number, but it's {} in the profile for '{}'",
+                    profile.acceleration.unwrap_or_default(), app
+                ));
+            }
+            if profile.drag_end_delay.is_some_and(|delay| delay > MAX_DRAG_END_DELAY) {
+                return invalid(format!(
+                    "dragEndDelay in the profile for '{}' is over the maximum of {}ms",
+                    app, MAX_DRAG_END_DELAY.as_millis()
+                ));
+            }
+        }
+
+        for (device, profile) in &self.devices {
+            if profile.acceleration.is_some_and(|accel| !accel.is_finite() || accel < 0.0) {
+                return invalid(format!(
+                    "acceleration must be a non-negative number, but it's {} for the device '{}'",
+                    profile.acceleration.unwrap_or_default(), device
+                ));
+            }
+            if profile.drag_end_delay.is_some_and(|delay| delay > MAX_DRAG_END_DELAY) {
+                return invalid(format!(
+                    "dragEndDelay for the device '{}' is over the maximum of {}ms",
+                    device, MAX_DRAG_END_DELAY.as_millis()
+                ));
+            }
+        }
+
+        if !(0.0..=MAX_IDLE_EXIT_HOURS).contains(&self.idle_exit_hours) {
+            return invalid(format!(
+                "idleExitHours must be between 0 and {}, but it's {}",
+                MAX_IDLE_EXIT_HOURS, self.idle_exit_hours
+            ));
+        }
+
+        for (output, scale) in &self.output_scales {
+            if !scale.is_finite() || *scale < 0.0 {
+                return invalid(format!(
+                    "outputScales values must be non-negative numbers, but the one for '{}' is {}",
+                    output, scale
+                ));
+            }
+        }
+
+        for gesture_macro in &self.gesture_macros {
+            if gesture_macro.sequence.is_empty() || gesture_macro.command.trim().is_empty() {
+                return invalid("every gestureMacros entry needs a sequence and a command".to_string());
+            }
+            if gesture_macro.within > MAX_MACRO_WINDOW {
+                return invalid(format!(
+                    "`within` for the gesture macro running `{}` is over the maximum of {}ms",
+                    gesture_macro.command, MAX_MACRO_WINDOW.as_millis()
+                ));
+            }
+        }
+
+        if self.click_suppress_time > MAX_CLICK_SUPPRESS_TIME {
+            return invalid(format!(
+                "clickSuppressTime is {}ms, which is over the maximum of {}ms",
+                self.click_suppress_time.as_millis(), MAX_CLICK_SUPPRESS_TIME.as_millis()
+            ));
+        }
+
+        if let Some(delay) = self.drag_cancel_delay.filter(|&delay| delay > MAX_DRAG_END_DELAY) {
+            return invalid(format!(
+                "dragCancelDelay is {}ms, which is over the maximum of {}ms",
+                delay.as_millis(), MAX_DRAG_END_DELAY.as_millis()
+            ));
+        }
+
+        if self.drag_end_delay > MAX_DRAG_END_DELAY {
+            return invalid(format!(
+                "dragEndDelay is {}ms, which is over the maximum of {}ms",
+                self.drag_end_delay.as_millis(), MAX_DRAG_END_DELAY.as_millis()
+            ));
+        }
+
+        if let Some(response_time) = self.response_time.filter(|&time| time > MAX_RESPONSE_TIME) {
+            return invalid(format!(
+                "responseTime is {}ms, which is over the maximum of {}ms",
+                response_time.as_millis(), MAX_RESPONSE_TIME.as_millis()
+            ));
+        }
+
+        if self.jiggle_amplitude > MAX_JIGGLE_AMPLITUDE {
+            return invalid(format!(
+                "jiggleAmplitude is {}, which is over the maximum of {}",
+                self.jiggle_amplitude, MAX_JIGGLE_AMPLITUDE
+            ));
+        }
+
+        if self.max_event_rate > MAX_EVENT_RATE {
+            return invalid(format!(
+                "maxEventRate is {}, which is over the maximum of {} (use 0 for no limit)",
+                self.max_event_rate, MAX_EVENT_RATE
+            ));
+        }
+
+        if !(0.0..=MAX_RESTING_THUMB_ZONE).contains(&self.resting_thumb_zone) {
+            return invalid(format!(
+                "restingThumbZone must be between 0 and {}, but it's {}",
+                MAX_RESTING_THUMB_ZONE, self.resting_thumb_zone
+            ));
+        }
+
+        if !(0.0..=MAX_DEAD_ZONE).contains(&self.dead_zone) {
+            return invalid(format!(
+                "deadZone must be between 0 and {}, but it's {}",
+                MAX_DEAD_ZONE, self.dead_zone
+            ));
+        }
+
+        if !self.max_speed.is_finite() || self.max_speed < 0.0 {
+            return invalid(format!("maxSpeed must be a non-negative number, but it's {}", self.max_speed));
+        }
+
+        if !(0.0..=MAX_START_THRESHOLD).contains(&self.start_threshold) {
+            return invalid(format!(
+                "startThreshold must be between 0 and {}, but it's {}",
+                MAX_START_THRESHOLD, self.start_threshold
+            ));
+        }
+
+        if self.right_click_hold_time > MAX_RIGHT_CLICK_HOLD_TIME {
+            return invalid(format!(
+                "rightClickHoldTime is {}ms, which is over the maximum of {}ms",
+                self.right_click_hold_time.as_millis(), MAX_RIGHT_CLICK_HOLD_TIME.as_millis()
+            ));
         }
+
+        Ok(())
+    }
+
+
+    /// How long the trackpad can go unused before the program exits, if
+    /// it's set to exit at all.
+    pub fn idle_exit_after(&self) -> Option<Duration> {
+        // validate() keeps it in range, but a config that skipped that
+        // shouldn't panic over it
+        (self.idle_exit_hours > 0.0)
+            .then(|| Duration::try_from_secs_f64(self.idle_exit_hours * 60.0 * 60.0).ok())
+            .flatten()
     }
+
+
+    /// The profile for the app with `app_id` (or window class), if there
+    /// is one. Case doesn't matter, like for `pauseForApps`, but one keyed
+    /// by exactly the app ID wins, and otherwise it's the first by key
+    /// (so "GIMP" over "gimp"), so the same app always gets the same one.
+    pub fn profile_for(&self, app_id: &str) -> Option<&AppProfile> {
+        self.app_profiles.get(app_id).or_else(||
+            self.app_profiles.iter()
+                .find(|(app, _)| app.eq_ignore_ascii_case(app_id))
+                .map(|(_, profile)| profile)
+        )
+    }
+
+
+    /// The settings for the trackpad with the given name and IDs, keyed
+    /// either by its name (as libinput has it) or by its IDs, as
+    /// `vendor:product` in hex (like `04f3:3140`).
+    pub fn device_profile_for(&self, name: &str, vendor: u32, product: u32) -> Option<&DeviceProfile> {
+        let ids = format!("{vendor:04x}:{product:04x}");
+        self.devices.iter()
+            .find(|(device, _)| device.as_str() == name || device.eq_ignore_ascii_case(&ids))
+            .map(|(_, profile)| profile)
+    }
+
+
+    /// Looks up a single value by its name in the config file (e.g. `dragEndDelay`).
+    pub fn get_value(&self, key: &str) -> Option<Value> {
+        serde_json::to_value(self).ok()?
+            .get(key)
+            .cloned()
+    }
+
+
+    /// Returns a copy of this configuration with a single value changed, 
+    /// by its name in the config file. The change goes through the same
+    /// deserialization and validation as the config file, so if the
+    /// result is `Ok`, it's a configuration that could have been loaded.
+    pub fn with_value(&self, key: &str, value: Value) -> Result<Configuration, std::io::Error> {
+
+        // picking a profile means loading the config over again
+        if key == "activeProfile" {
+            return match value {
+                Value::Null => self.with_profile(None),
+                Value::String(name) => self.with_profile(Some(&name)),
+                _ => Err(std::io::Error::new(ErrorKind::InvalidInput, "activeProfile must be a profile's name, or null"))
+            };
+        }
+
+        let mut as_json = serde_json::to_value(self)?;
+        match as_json.get_mut(key) {
+            Some(field) => *field = value,
+            None => return Err(
+                std::io::Error::new(ErrorKind::InvalidInput, format!("no config option named '{key}'"))
+            )
+        }
+
+        let mut new_cfg = serde_json::from_value::<Configuration>(as_json)?;
+        new_cfg.validate()?;
+        new_cfg.source = self.source.clone();
+        Ok(new_cfg)
+    }
+
+
+    /// Returns this configuration as it would be with the profile `name`
+    /// in use (or none), and keeps using it from here on, even when the
+    /// config file is reloaded. This goes back to the config as it was
+    /// loaded, so anything changed since with `config set` (and not saved)
+    /// is lost.
+    pub fn with_profile(&self, name: Option<&str>) -> Result<Configuration, std::io::Error> {
+
+        if let Some(name) = name.filter(|name| !self.profiles.contains_key(*name)) {
+            return Err(std::io::Error::new(ErrorKind::InvalidInput, format!("there's no profile named '{name}'")));
+        }
+        let Some(source) = &self.source else {
+            return Err(std::io::Error::new(ErrorKind::Unsupported, "this config wasn't loaded from a file"));
+        };
+
+        let mut choice = PROFILE_CHOICE.lock().unwrap_or_else(PoisonError::into_inner);
+        let previous = choice.replace(name.map(str::to_string));
+        drop(choice);
+
+        let new_cfg = from_json_with_preset(source.as_json.clone(), source.preset_override);
+        if new_cfg.is_err() {
+            *PROFILE_CHOICE.lock().unwrap_or_else(PoisonError::into_inner) = previous;
+        }
+        new_cfg
+    }
+}
+
+
+/// A config as it was loaded, before its profile (see `profiles`), any
+/// overrides, and its preset were applied, so it can be worked out again
+/// with another profile.
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    as_json: Value,
+    preset_override: Option<Preset>
 }
 
+
+// The fields that hold settings for one session type, named after what
+// `$XDG_SESSION_TYPE` says for it
+const SESSION_SECTIONS: [&str; 2] = ["wayland", "x11"];
+
+
+/// The session type this is running in (`x11`, `wayland`, or whatever
+/// else `$XDG_SESSION_TYPE` says), looked up once, when the config is
+/// first loaded.
+fn session_type() -> Option<&'static str> {
+    static SESSION_TYPE: OnceLock<Option<String>> = OnceLock::new();
+    SESSION_TYPE
+        .get_or_init(|| std::env::var("XDG_SESSION_TYPE").ok().map(|session| session.trim().to_lowercase()))
+        .as_deref()
+}
+
+
+/// Merges the fields of the section for this session type (`wayland` or
+/// `x11`), if there is one, over `as_json`.
+fn apply_session_section(as_json: &mut Value) {
+
+    let Some(session) = session_type().filter(|session| SESSION_SECTIONS.contains(session)) else { return };
+    let Some(Value::Object(mut section)) = as_json.get(session).cloned() else { return };
+
+    // a section can pick a profile, but not hold any, or more sections
+    section.remove("profiles");
+    for session in SESSION_SECTIONS {
+        section.remove(session);
+    }
+
+    merge_layer(as_json, Value::Object(section));
+}
+
+
+// The profile picked while running (with the `profile` command), which
+// wins over `activeProfile`, including when the config file is reloaded.
+// `Some(None)` is picking no profile at all.
+static PROFILE_CHOICE: Mutex<Option<Option<String>>> = Mutex::new(None);
+
+
+/// Merges the fields of the profile in use (the one picked while running,
+/// or else the one `activeProfile` names) over `as_json`, returning its
+/// name.
+fn apply_profile(as_json: &mut Value) -> Result<Option<String>, std::io::Error> {
+
+    let chosen = PROFILE_CHOICE.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    let name = match chosen {
+        Some(name) => name,
+        None => overrides().get("activeProfile")
+            .or_else(|| as_json.get("activeProfile"))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let Some(name) = name else { return Ok(None) };
+
+    let Some(Value::Object(mut profile)) = as_json.get("profiles").and_then(|profiles| profiles.get(&name)).cloned() else {
+        return Err(std::io::Error::new(ErrorKind::InvalidData, format!("activeProfile is '{name}', but there's no profile by that name in profiles")));
+    };
+    // a profile can't pick another profile
+    profile.remove("activeProfile");
+    profile.remove("profiles");
+
+    merge_layer(as_json, Value::Object(profile));
+    Ok(Some(name))
+}
+
+
+/// Options that take effect as soon as they're changed while running. 
+/// The rest are only read at startup.
+pub const LIVE_OPTIONS: &[&str] = &[
+    "acceleration", 
+    "accelerationProfile",
+    "accelerationX",
+    "accelerationY",
+    "activeProfile",
+    "clickSuppressTime",
+    "deadZone",
+    "devices",
+    "dragButton",
+    "dragCancelDelay",
+    "dragEndDelay", 
+    "gestureMacros",
+    "holdToRightClick", 
+    "idleExitHours",
+    "invertX",
+    "invertY",
+    "jiggleAmplitude",
+    "jiggleInterval",
+    "logLevel",
+    "maxEventRate",
+    "maxSpeed",
+    "oneFingerContinue",
+    "responseTime",
+    "rightClickHoldTime",
+    "sensitivityUnits",
+    "startThreshold",
+    "threeFingerTap",
+    "wayland",
+    "x11"
+];
+
+
 // for some reason, default literals don't seem to be okay
 // with the serde crate, despite several issues and PRs on the 
 // subject. Using functions to yield the values is the only 
 // accepted way.
 fn default_1()      -> f64      { 1.0 }
+fn default_1px()    -> u32      { 1 }
 fn default_0ms()    -> Duration { Duration::from_millis(0) }
-fn default_5ms()    -> Duration { Duration::from_millis(5) }
+fn default_5ms()    -> Option<Duration> { Some(Duration::from_millis(5)) }
+fn default_500ms()  -> Duration { Duration::from_millis(500) }
+fn default_800ms()  -> Duration { Duration::from_millis(800) }
 fn default_stdout() -> String   { "stdout".to_string() }
 fn default_info()   -> LogLevel { LogLevel::INFO }
+fn default_true()   -> bool     { true }
+fn default_left()   -> ThreeFingerTap { ThreeFingerTap::Left }
+fn default_left_button() -> DragButton { DragButton::Left }
+fn default_raw()    -> SensitivityUnits { SensitivityUnits::Raw }
+fn default_pointer() -> OutputMode { OutputMode::Pointer }
+fn default_adaptive() -> AccelerationProfile { AccelerationProfile::Adaptive }
+
+
+/// Parses a duration like `500ms` or `1.5s`. A bare number is taken
+/// as milliseconds, like everywhere else in the config.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+
+    let text = text.trim();
+    let (number, millis_per_unit) = if let Some(number) = text.strip_suffix("ms") {
+        (number, 1.0)
+    } else if let Some(number) = text.strip_suffix('s') {
+        (number, 1000.0)
+    } else {
+        (text, 1.0)
+    };
+
+    // which turns down negative, infinite and NaN ones, and ones too long
+    // for a Duration, instead of panicking over them like from_secs_f64
+    let millis = number.trim().parse::<f64>().ok()? * millis_per_unit;
+    Duration::try_from_secs_f64(millis / 1000.0).ok()
+}
+
+
+/// For `#[serde_as]`: a duration in the config, written as milliseconds,
+/// but read from either that or a string for `parse_duration()`, so
+/// `"250ms"` and `"1.5s"` work as well as `250`.
+pub struct Milliseconds;
+
+impl SerializeAs<Duration> for Milliseconds {
+    fn serialize_as<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis() as u64)
+    }
+}
+
+impl<'de> DeserializeAs<'de, Duration> for Milliseconds {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        deserializer.deserialize_any(MillisecondsVisitor)
+    }
+}
+
+struct MillisecondsVisitor;
+
+impl Visitor<'_> for MillisecondsVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("milliseconds, or a duration like \"250ms\" or \"1.5s\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, millis: u64) -> Result<Duration, E> {
+        Ok(Duration::from_millis(millis))
+    }
+
+    fn visit_i64<E: de::Error>(self, millis: i64) -> Result<Duration, E> {
+        u64::try_from(millis)
+            .map(Duration::from_millis)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(millis), &self))
+    }
+
+    fn visit_f64<E: de::Error>(self, millis: f64) -> Result<Duration, E> {
+        Duration::try_from_secs_f64(millis / 1000.0).map_err(|_| E::invalid_value(de::Unexpected::Float(millis), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<Duration, E> {
+        parse_duration(text).ok_or_else(|| E::invalid_value(de::Unexpected::Str(text), &self))
+    }
+}
+
+/// For `#[serde_as]`: like `Milliseconds`, but also allowing `"auto"`,
+/// which is `None`.
+pub struct MillisecondsOrAuto;
+
+impl SerializeAs<Option<Duration>> for MillisecondsOrAuto {
+    fn serialize_as<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => Milliseconds::serialize_as(duration, serializer),
+            None => serializer.serialize_str("auto")
+        }
+    }
+}
+
+impl<'de> DeserializeAs<'de, Option<Duration>> for MillisecondsOrAuto {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        deserializer.deserialize_any(MillisecondsOrAutoVisitor)
+    }
+}
+
+struct MillisecondsOrAutoVisitor;
+
+impl Visitor<'_> for MillisecondsOrAutoVisitor {
+    type Value = Option<Duration>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        MillisecondsVisitor.expecting(f)?;
+        f.write_str(", or \"auto\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, millis: u64) -> Result<Option<Duration>, E> {
+        MillisecondsVisitor.visit_u64(millis).map(Some)
+    }
+
+    fn visit_i64<E: de::Error>(self, millis: i64) -> Result<Option<Duration>, E> {
+        MillisecondsVisitor.visit_i64(millis).map(Some)
+    }
+
+    fn visit_f64<E: de::Error>(self, millis: f64) -> Result<Option<Duration>, E> {
+        MillisecondsVisitor.visit_f64(millis).map(Some)
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<Option<Duration>, E> {
+        if text.trim() == "auto" {
+            return Ok(None);
+        }
+        MillisecondsVisitor.visit_str(text).map(Some)
+    }
+}
+
+impl JsonSchemaAs<Option<Duration>> for MillisecondsOrAuto {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        "MillisecondsOrAuto".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let milliseconds = <Milliseconds as JsonSchemaAs<Duration>>::json_schema(generator);
+        json_schema!({
+            "anyOf": [milliseconds, { "const": "auto" }]
+        })
+    }
+}
+
+impl JsonSchemaAs<Duration> for Milliseconds {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        "Milliseconds".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "anyOf": [
+                { "type": "integer", "minimum": 0 },
+                { "type": "string", "pattern": "^\\s*[0-9]*\\.?[0-9]+\\s*(ms|s)?\\s*$" }
+            ]
+        })
+    }
+}
 
 
 pub fn get_config_file_path() -> Result<PathBuf, std::io::Error> {
+    if let Some(path) = CONFIG_FILE_OVERRIDE.get() {
+        return Ok(path.clone());
+    }
     let config_folder = match std::env::var_os("XDG_CONFIG_HOME") {
         Some(config_dir) => PathBuf::from(config_dir),
         None => {
@@ -99,56 +982,512 @@ pub fn get_config_file_path() -> Result<PathBuf, std::io::Error> {
             }
         }
     };
-    let filepath = config_folder.join("linux-3-finger-drag/3fd-config.json");
-    Ok(filepath)
+    Ok(config_path_in(config_folder))
+}
+
+
+// The config file given with `--config`, in place of the usual ones
+static CONFIG_FILE_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Uses `path` as the config file from here on, for loading and saving
+/// alike, instead of looking in the usual places (the user's config
+/// folder, then the system-wide one), for `--config`. A system-wide
+/// instance uses it in place of the system-wide config. Only the first
+/// call counts.
+pub fn use_config_file(path: &Path) {
+    // relative to where the program was started, not wherever it is when reloading
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let _ = CONFIG_FILE_OVERRIDE.set(path);
+}
+
+
+/// Where the config file goes in a given config folder (like ~/.config).
+pub fn config_path_in(config_folder: PathBuf) -> PathBuf {
+    config_path_in_dir(&config_folder.join("linux-3-finger-drag"))
+}
+
+
+// The config files looked for in the program's config folder, other than
+// `3fd-config.json`, in order of preference
+const OTHER_CONFIG_FILE_NAMES: [&str; 3] = ["3fd-config.toml", "3fd-config.yaml", "3fd-config.yml"];
+
+
+/// The config file in the program's own config folder: the first of
+/// `OTHER_CONFIG_FILE_NAMES` there is, and `3fd-config.json` otherwise
+/// (even if there isn't one, since that's where a new one goes).
+fn config_path_in_dir(dir: &Path) -> PathBuf {
+    OTHER_CONFIG_FILE_NAMES.iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| dir.join("3fd-config.json"))
+}
+
+
+/// Where a config file is looked for, in order: the user's own (see
+/// `get_config_file_path()`), then the system-wide one, so admins can ship
+/// a machine-wide default. Only the first one there is gets loaded.
+pub fn config_search_paths() -> Vec<PathBuf> {
+    if let Some(path) = CONFIG_FILE_OVERRIDE.get() {
+        return vec![path.clone()];
+    }
+    get_config_file_path().into_iter()
+        .chain([system_config_path()])
+        .collect()
+}
+
+
+/// The config file to load, which is the first in `config_search_paths()`
+/// there is. Settings are still saved into the user's own (see
+/// `get_config_file_path()`), never into the system-wide one.
+pub fn find_config_file() -> Result<PathBuf, std::io::Error> {
+    let paths = config_search_paths();
+    paths.iter()
+        .find(|path| path.exists() || !drop_in_files(path).is_empty())
+        .cloned()
+        .ok_or_else(||
+            std::io::Error::new(
+                ErrorKind::NotFound,
+                format!("Unable to locate a config file at any of {:?}", paths)
+            )
+        )
+}
+
+
+/// Whether `name` is one a config file in the program's config folder
+/// could have (or the name of the one given with `--config`).
+pub fn is_config_file_name(name: &str) -> bool {
+    if let Some(path) = CONFIG_FILE_OVERRIDE.get() {
+        return path.file_name().is_some_and(|file_name| file_name == name);
+    }
+    name == "3fd-config.json" || OTHER_CONFIG_FILE_NAMES.contains(&name)
+}
+
+
+/// The folder of drop-in files merged over the config file next to it.
+pub const DROP_IN_DIR_NAME: &str = "3fd-config.d";
+
+
+/// The config file for a system-wide instance (see `--system`), as JSON.
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/linux-3-finger-drag/3fd-config.json";
+
+
+/// The config file a system-wide instance actually uses, which is
+/// `SYSTEM_CONFIG_PATH`, unless there's a TOML or YAML one next to it
+/// (or one was given with `--config`).
+pub fn system_config_path() -> PathBuf {
+    if let Some(path) = CONFIG_FILE_OVERRIDE.get() {
+        return path.clone();
+    }
+    config_path_in_dir(Path::new(SYSTEM_CONFIG_PATH).parent().unwrap_or(Path::new("/")))
+}
+
+
+/// The formats a config file can be in, going by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat { Json, Toml, Yaml }
+
+impl ConfigFormat {
+
+    pub fn of(filepath: &Path) -> ConfigFormat {
+        match filepath.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json
+        }
+    }
+
+
+    /// Parses a config file's contents into JSON, whichever format it's
+    /// in, so the rest of the loading (presets, defaults, validation) is
+    /// the same for all of them.
+    fn parse(self, contents: &str) -> Result<Value, std::io::Error> {
+        let invalid = |e| std::io::Error::new(ErrorKind::InvalidData, e);
+        match self {
+            // use serde's error as is
+            ConfigFormat::Json => Ok(from_str::<Value>(contents)?),
+            ConfigFormat::Toml => {
+                let parsed = toml::from_str::<toml::Value>(contents).map_err(|e| invalid(e.to_string()))?;
+                if let Some((key, float)) = non_finite_float(&parsed, "") {
+                    return Err(invalid(format!("{key} must be a number, but it's {float}")));
+                }
+                serde_json::to_value(parsed).map_err(|e| invalid(e.to_string()))
+            },
+            ConfigFormat::Yaml => serde_yaml::from_str::<Value>(contents).map_err(|e| invalid(e.to_string()))
+        }
+    }
+
+
+    pub fn serialize(self, as_json: &Value) -> Result<String, std::io::Error> {
+        let invalid = |e| std::io::Error::new(ErrorKind::InvalidData, e);
+        match self {
+            ConfigFormat::Json => Ok(serde_json::to_string_pretty(as_json)?),
+            ConfigFormat::Toml => toml::to_string_pretty(as_json).map_err(|e| invalid(e.to_string())),
+            ConfigFormat::Yaml => serde_yaml::to_string(as_json).map_err(|e| invalid(e.to_string()))
+        }
+    }
+}
+
+
+/// The first float in `value` that JSON can't hold (TOML's `nan` and
+/// `inf`), and where it is, since it'd otherwise come through as a null,
+/// and the error would be about that instead.
+fn non_finite_float(value: &toml::Value, path: &str) -> Option<(String, f64)> {
+    match value {
+        toml::Value::Float(float) if !float.is_finite() => Some((path.to_string(), *float)),
+        toml::Value::Table(table) => table.iter().find_map(|(key, value)| {
+            let path = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+            non_finite_float(value, &path)
+        }),
+        toml::Value::Array(items) => items.iter()
+            .enumerate()
+            .find_map(|(i, value)| non_finite_float(value, &format!("{path}[{i}]"))),
+        _ => None
+    }
+}
+
+
+/// Reads a config file into JSON, whichever format it's in.
+fn read_config_value(filepath: &Path) -> Result<Value, std::io::Error> {
+    ConfigFormat::of(filepath).parse(&read_to_string(filepath)?)
+}
+
+
+/// The drop-in files for the config file at `filepath`: every config file
+/// (in any of the formats) in the `3fd-config.d` folder next to it, in
+/// name order.
+pub fn drop_in_files(filepath: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = filepath.with_file_name(DROP_IN_DIR_NAME).read_dir() else { return Vec::new() };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| matches!(path.extension().and_then(|ext| ext.to_str()), Some("json" | "toml" | "yaml" | "yml")))
+        .collect();
+    paths.sort();
+    paths
+}
+
+
+/// Reads the config file at `filepath` into JSON, with its drop-in files
+/// merged over it in order, so later ones win. A drop-in only needs the
+/// fields it changes, and there needn't be a config file under them.
+fn read_layered_config(filepath: &Path) -> Result<Value, std::io::Error> {
+
+    let drop_ins = drop_in_files(filepath);
+    let mut as_json = match read_config_value(filepath) {
+        Err(e) if e.kind() == ErrorKind::NotFound && !drop_ins.is_empty() => Value::Object(Default::default()),
+        read => read?
+    };
+
+    for path in drop_ins {
+        let layer = read_config_value(&path)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("In the drop-in {:?}: {}", path, e)))?;
+        merge_layer(&mut as_json, layer);
+    }
+    Ok(as_json)
+}
+
+
+/// The top-level keys in the config file at `filepath` (and its drop-ins)
+/// that aren't config options, which are ignored when loading it, so are
+/// likely typos. Fields inside `appProfiles` and the like aren't checked.
+pub fn unknown_keys(filepath: &Path) -> Result<Vec<String>, std::io::Error> {
+    unknown_keys_in(&read_layered_config(filepath)?)
+}
+
+
+/// Like `unknown_keys()`, for a config that's already been read. The
+/// fields of each of `profiles` are checked too, as `profiles.<name>.<key>`,
+/// and so are those of `wayland` and `x11`.
+fn unknown_keys_in(as_json: &Value) -> Result<Vec<String>, std::io::Error> {
+
+    let known = serde_json::to_value(Configuration::default())?;
+    let unknown_in = |options: &serde_json::Map<String, Value>| -> Vec<String> {
+        options.keys()
+            // `$schema` is for editors (see `config_schema`)
+            .filter(|key| *key != "$schema" && known.get(key.as_str()).is_none())
+            .cloned()
+            .collect()
+    };
+
+    let Some(options) = as_json.as_object() else { return Ok(Vec::new()) };
+    let mut unknown = unknown_in(options);
+
+    if let Some(profiles) = options.get("profiles").and_then(Value::as_object) {
+        for (name, profile) in profiles {
+            let Some(profile) = profile.as_object() else { continue };
+            unknown.extend(unknown_in(profile).into_iter().map(|key| format!("profiles.{name}.{key}")));
+        }
+    }
+    for session in SESSION_SECTIONS {
+        let Some(section) = options.get(session).and_then(Value::as_object) else { continue };
+        unknown.extend(unknown_in(section).into_iter().map(|key| format!("{session}.{key}")));
+    }
+    Ok(unknown)
+}
+
+
+/// The error for a config with unknown options in it, when `strict` is on.
+#[derive(Debug)]
+pub struct UnknownKeys(pub Vec<String>);
+
+impl std::fmt::Display for UnknownKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "strict is on, and these aren't config options: {}", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownKeys {}
+
+
+/// Merges `layer` over `base`: objects (like `appProfiles`) field by
+/// field, and anything else by replacing it outright.
+fn merge_layer(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                merge_layer(base.entry(key).or_insert(Value::Null), value);
+            }
+        },
+        (base, layer) => *base = layer
+    }
 }
 
 
 // Configs are so optional that their absence should not crash the program,
-// So if there is any issue with the JSON config file,
-// the following default values will be returned:
+// So if there is any issue with the JSON config file (including values 
+// that parse, but are out of range), the following default values will 
+// be returned:
 //
 // {
 //     acceleration: 1.0,
 //     dragEndDelay: 0,
 //     logFile: "stdout",
 //     logLevel: "info",
-//     responseTime: 5
+//     outputScales: {},
+//     responseTime: 5,
+//     scaleByOutput: false,
+//     syncDesktopSpeed: true
 // }
 //
 // The user is also warned about this, so they can address the issues
 // if they want to configure the way the program runs.
 pub fn parse_config_file() -> Result<Configuration, std::io::Error> {
-    let filepath = get_config_file_path()?;
-    let jsonfile = read_to_string(&filepath)
-        .map_err(|_| 
-            // more descriptive error
-            std::io::Error::new(
-                ErrorKind::NotFound, 
-                format!("Unable to locate JSON file at {:?} ", filepath)
-            )
+    parse_config_file_with_preset(None)
+}
+
+
+/// Like `parse_config_file()`, but with `preset_override` (if given) used
+/// instead of any preset named in the file.
+pub fn parse_config_file_with_preset(preset_override: Option<Preset>) -> Result<Configuration, std::io::Error> {
+    parse_config_file_at(&find_config_file()?, preset_override)
+}
+
+
+/// Like `parse_config_file_with_preset()`, but for a config file in a 
+/// place of the caller's choosing.
+pub fn parse_config_file_at(filepath: &Path, preset_override: Option<Preset>) -> Result<Configuration, std::io::Error> {
+    let as_json = read_layered_config(filepath)
+        .map_err(|e| 
+            if e.kind() == ErrorKind::NotFound {
+                // more descriptive error
+                std::io::Error::new(
+                    ErrorKind::NotFound, 
+                    format!("Unable to locate config file at {:?} ", filepath)
+                )
+            } else {
+                e
+            }
         )?;
 
-    // use serde's error as is
-    let config = from_str::<Configuration>(&jsonfile)?;
+    from_json_with_preset(as_json, preset_override)
+}
+
+
+// Values from the environment (like `L3FD_ACCELERATION`) and then the
+// command line (like `--acceleration`), which win over the config file's,
+// every time it's loaded
+static OVERRIDES: OnceLock<Value> = OnceLock::new();
+
+const ENV_PREFIX: &str = "L3FD_";
+
+
+/// Sets values (as a JSON object of config fields) that win over the config
+/// file's and the environment's, for every config loaded from here on,
+/// including reloads. They're checked first (along with the environment's),
+/// so a bad one is an error here, rather than the whole config falling back
+/// on the defaults. Only the first call counts.
+pub fn set_overrides(overrides: Value) -> Result<(), std::io::Error> {
+    let mut layered = env_overrides();
+    merge_layer(&mut layered, overrides);
+
+    serde_json::from_value::<Configuration>(layered.clone())?.validate()?;
+    let _ = OVERRIDES.set(layered);
+    Ok(())
+}
+
+
+fn overrides() -> &'static Value {
+    // without `set_overrides()` (like for `calibrate`), there's only the environment's
+    OVERRIDES.get_or_init(env_overrides)
+}
+
+
+/// Config fields set in the environment, like `L3FD_DRAG_END_DELAY=300`
+/// for `dragEndDelay`. A value is read as JSON if it is JSON (so numbers,
+/// `true`, and lists work), and as a string otherwise.
+fn env_overrides() -> Value {
+
+    let fields = serde_json::to_value(Configuration::default()).unwrap_or_default();
+    let mut overrides = serde_json::Map::new();
+
+    for (name, value) in std::env::vars() {
+        let Some(field) = name.strip_prefix(ENV_PREFIX) else { continue };
+        let key = camel_case(field);
+        if fields.get(&key).is_none() {
+            // the ones for handing off to a re-executed instance aren't config
+            if !field.starts_with("HANDOFF_") {
+                run_mode::pre_warn(format!("Ignoring {name}, since there's no config option named '{key}'"));
+            }
+            continue;
+        }
+        let value = from_str::<Value>(&value).unwrap_or(Value::String(value));
+        overrides.insert(key, value);
+    }
+
+    Value::Object(overrides)
+}
+
+
+/// `DRAG_END_DELAY` -> `dragEndDelay`
+fn camel_case(screaming_snake: &str) -> String {
+    let mut words = screaming_snake.split('_').filter(|word| !word.is_empty()).map(str::to_lowercase);
+    let first = words.next().unwrap_or_default();
+    words.fold(first, |mut key, word| {
+        let mut chars = word.chars();
+        key.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+        key.push_str(chars.as_str());
+        key
+    })
+}
+
+
+/// Deserializes a config from its JSON, after filling in the values of
+/// its preset (`preset_override`, or else the one it names) for anything
+/// it leaves out. Any overrides (see `set_overrides()`) win over both.
+fn from_json_with_preset(source: Value, preset_override: Option<Preset>) -> Result<Configuration, std::io::Error> {
+
+    let mut as_json = source.clone();
+    apply_session_section(&mut as_json);
+    let profile = apply_profile(&mut as_json)?;
+    merge_layer(&mut as_json, overrides().clone());
+    if as_json.get("strict") == Some(&Value::Bool(true)) {
+        let unknown = unknown_keys_in(&as_json)?;
+        if !unknown.is_empty() {
+            return Err(std::io::Error::new(ErrorKind::InvalidData, UnknownKeys(unknown)));
+        }
+    }
+    if let Some(options) = as_json.as_object_mut() {
+        options.insert("activeProfile".to_string(), json!(profile));
+    }
+
+    if let (Some(preset), Some(options)) = (preset_override, as_json.as_object_mut()) {
+        options.insert("preset".to_string(), serde_json::to_value(preset)?);
+    }
+
+    let preset = as_json.get("preset")
+        .cloned()
+        .map(serde_json::from_value::<Option<Preset>>)
+        .transpose()?
+        .flatten();
+    if let Some(preset) = preset {
+        preset.fill_in(&mut as_json);
+    }
+
+    let mut config = serde_json::from_value::<Configuration>(as_json)?;
+    config.validate()?;
+    config.source = Some(ConfigSource { as_json: source, preset_override });
 
     Ok(config)
 }
 
 
-pub fn init_cfg() -> Configuration {
-    
-    println!("[PRE-LOG: INFO]: Loading configuration...");
-    let configs = match parse_config_file() {
+/// Writes a single value into the config file, leaving the rest of it as
+/// is (or creating the file, if there isn't one yet). The file is
+/// replaced in one step, so it's never left half-written.
+pub fn persist_value(key: &str, value: Value) -> Result<(), std::io::Error> {
+
+    let filepath = get_config_file_path()?;
+
+    let mut as_json = match read_config_value(&filepath) {
+        Ok(as_json) => as_json,
+        Err(e) if e.kind() == ErrorKind::NotFound => Value::Object(Default::default()),
+        Err(e) => return Err(e)
+    };
+
+    match as_json.as_object_mut() {
+        Some(options) => { options.insert(key.to_string(), value); },
+        None => return Err(
+            std::io::Error::new(ErrorKind::InvalidData, format!("{:?} is not a JSON object", filepath))
+        )
+    }
+
+    if let Some(config_folder) = filepath.parent() {
+        std::fs::create_dir_all(config_folder)?;
+    }
+
+    // the file stays in the format it was in (though its formatting and
+    // any comments don't survive the trip)
+    let contents = ConfigFormat::of(&filepath).serialize(&as_json)?;
+    let mut tmp_path = filepath.clone().into_os_string();
+    tmp_path.push(".tmp");
+    write(&tmp_path, contents)?;
+    rename(&tmp_path, &filepath)
+}
+
+
+/// Loads the config file, falling back on the defaults if it can't be.
+/// `preset_override` (from `--preset`) applies either way.
+pub fn init_cfg(preset_override: Option<Preset>) -> Configuration {
+    run_mode::pre_info("Loading configuration...");
+    if let Ok(path) = find_config_file() {
+        if get_config_file_path().is_ok_and(|own| own != path) {
+            run_mode::pre_info(format!("You don't have a config file of your own, so using the system-wide one at {}", path.display()));
+        }
+    }
+    init_cfg_from(parse_config_file_with_preset(preset_override), preset_override)
+}
+
+
+/// Like `init_cfg()`, but for a system-wide instance, which has its
+/// config in /etc instead.
+pub fn init_system_cfg(preset_override: Option<Preset>) -> Configuration {
+    let path = system_config_path();
+    run_mode::pre_info(format!("Loading system-wide configuration from {}...", path.display()));
+    init_cfg_from(parse_config_file_at(&path, preset_override), preset_override)
+}
+
+
+fn init_cfg_from(parsed: Result<Configuration, std::io::Error>, preset_override: Option<Preset>) -> Configuration {
+
+    let configs = match parsed {
         Ok(cfg) => {
-            println!("[PRE-LOG: INFO]: Successfully loaded your configuration (with defaults for unspecified values): \n{:#?}", &cfg);
+            run_mode::pre_info(format!("Successfully loaded your configuration (with defaults for unspecified values): \n{:#?}", &cfg));
+            warn_on_long_delay(&cfg);
             cfg
         },
         Err(err) => {
-            let cfg = Default::default();
-            println!("\n[PRE-LOG: WARNING]: {err}\n\nThe configuration file could not be \
-                loaded, so the program will continue with defaults of:\n{cfg:#?}",
-            );
+            let cfg = from_json_with_preset(json!({}), preset_override).unwrap_or_default();
+            // strict mode is for finding out about mistakes, so there's
+            // no carrying on without them being fixed
+            let rejected = err.get_ref().is_some_and(|e| e.is::<UnknownKeys>());
+            if cfg.strict || rejected {
+                run_mode::pre_error(format!("{err}\n\nThe configuration file could not be \
+                    loaded, and strict is on, so the program won't start until that's fixed."));
+                std::process::exit(1);
+            }
+            run_mode::pre_warn(format!("{err}\n\nThe configuration file could not be \
+                loaded, so the program will continue with defaults of:\n{cfg:#?}"
+            ));
             cfg
         }
     };
@@ -157,7 +1496,23 @@ pub fn init_cfg() -> Configuration {
 }
 
 
-pub fn init_file_logger(cfg: Configuration) -> Option<SubscriberBuilder<DefaultFields, Format<Full, ChronoLocal>, LevelFilter, File>>{
+fn warn_on_long_delay(cfg: &Configuration) {
+    if cfg.drag_end_delay > LONG_DRAG_END_DELAY {
+        run_mode::pre_warn(format!("dragEndDelay is set to {}ms. With a delay this long, \
+            the mouse button will stay held for {:.1} seconds after every drag, which looks \
+            a lot like it's stuck: clicks won't register, and anything you hover over is \
+            still being dragged. The value will be used as-is, but if what you're after is \
+            a drag that stays held until you choose to end it, try turning on \"drag lock\" \
+            (alongside tap-and-drag) in your desktop's touchpad settings instead.",
+            cfg.drag_end_delay.as_millis(),
+            cfg.drag_end_delay.as_secs_f64()
+        ));
+    }
+}
+
+
+/// Opens the log file to log to, or returns `None` to log to stdout.
+pub fn init_file_logger(cfg: Configuration) -> Option<ReopenableLog> {
 
     let log_level: LevelFilter = cfg.log_level.into();
     
@@ -166,32 +1521,161 @@ pub fn init_file_logger(cfg: Configuration) -> Option<SubscriberBuilder<DefaultF
     // SimpleLogger (for console logging)
     if cfg.log_file == "stdout" { return None }
 
-    match OpenOptions::new().append(true).open(&cfg.log_file) {
+    match ReopenableLog::open(Path::new(&cfg.log_file)) {
 
         Ok(log_file) => {
-
-            let file_logger= tracing_subscriber::fmt()
-                .with_writer(log_file)
-                .with_max_level(log_level)
-                .with_timer(ChronoLocal::rfc_3339());
-            println!(
-                "[PRE-LOG: INFO]: Logging to '{}' at {}-level verbosity.", 
+            run_mode::pre_info(format!(
+                "Logging to '{}' at {}-level verbosity.", 
                 cfg.log_file, 
                 log_level
-            );
-            Some(file_logger)
+            ));
+            Some(log_file)
         },
 
         Err(open_err) => {
-            println!(
-                "[PRE-LOG: WARN]: Failed to open logfile '{}' \
+            run_mode::pre_warn(format!(
+                "Failed to open logfile '{}' \
                 due to the the following error: {}, {}.", 
                 cfg.log_file,
                 open_err.kind(),
                 open_err
-            );
-            println!("[PRE-LOG: WARN]: Logging to stdout at {log_level}-level verbosity.");
+            ));
+            run_mode::pre_warn(format!("Logging to stdout at {log_level}-level verbosity."));
             None
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads `contents` as a TOML config file, returning what loading it
+    /// said (the error is what gets warned about), and the config the
+    /// program would go on with.
+    fn load_toml(name: &str, contents: &str) -> (Result<Configuration, std::io::Error>, Configuration) {
+        let folder = std::env::temp_dir().join(format!("l3fd-config-test-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("3fd-config.toml");
+        std::fs::write(&path, contents).unwrap();
+
+        let parsed = parse_config_file_at(&path, None);
+        let summary = match &parsed {
+            Ok(cfg) => Ok(cfg.clone()),
+            Err(e) => Err(std::io::Error::new(e.kind(), e.to_string()))
+        };
+        let loaded = init_cfg_from(parsed, None);
+
+        std::fs::remove_dir_all(&folder).unwrap();
+        (summary, loaded)
+    }
+
+    fn assert_falls_back(name: &str, contents: &str, mentioning: &str) {
+        let (parsed, loaded) = load_toml(name, contents);
+
+        let err = parsed.expect_err(contents);
+        assert_eq!(err.kind(), ErrorKind::InvalidData, "{contents}");
+        assert!(err.to_string().contains(mentioning), "{contents}: {err}");
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(Configuration::default()).unwrap(),
+            "{contents}"
+        );
+    }
+
+    #[test]
+    fn non_finite_acceleration_falls_back() {
+        assert_falls_back("nan", "acceleration = nan", "acceleration");
+        assert_falls_back("inf", "acceleration = inf", "acceleration");
+        assert_falls_back("negative-inf", "acceleration = -inf", "acceleration");
+        assert_falls_back("axis-nan", "accelerationX = nan", "accelerationX");
+        assert_falls_back("profile-inf", "[appProfiles.firefox]\nacceleration = inf", "appProfiles.firefox.acceleration");
+    }
+
+    #[test]
+    fn negative_acceleration_falls_back() {
+        assert_falls_back("negative", "acceleration = -1.5", "acceleration");
+        assert_falls_back("axis-negative", "accelerationY = -0.1", "accelerationY");
+    }
+
+    #[test]
+    fn negative_durations_fall_back() {
+        assert_falls_back("negative-delay", "dragEndDelay = -5", "-5");
+        assert_falls_back("negative-response", "responseTime = -1", "-1");
+    }
+
+    #[test]
+    fn absurd_durations_fall_back() {
+        let over = |max: Duration| max.as_millis() + 1;
+        assert_falls_back("long-delay", &format!("dragEndDelay = {}", over(MAX_DRAG_END_DELAY)), "dragEndDelay");
+        assert_falls_back("long-cancel", &format!("dragCancelDelay = {}", over(MAX_DRAG_END_DELAY)), "dragCancelDelay");
+        assert_falls_back("long-response", &format!("responseTime = {}", over(MAX_RESPONSE_TIME)), "responseTime");
+        assert_falls_back("long-hold", &format!("rightClickHoldTime = {}", over(MAX_RIGHT_CLICK_HOLD_TIME)), "rightClickHoldTime");
+        assert_falls_back("long-suppress", &format!("clickSuppressTime = {}", over(MAX_CLICK_SUPPRESS_TIME)), "clickSuppressTime");
+        assert_falls_back("long-delay-string", "dragEndDelay = \"3601s\"", "dragEndDelay");
+    }
+
+    #[test]
+    fn durations_too_long_to_hold_are_turned_down() {
+        assert_eq!(parse_duration("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("1e300s"), None);
+        assert_eq!(parse_duration("1e300"), None);
+        assert_eq!(parse_duration("-5ms"), None);
+        assert_eq!(parse_duration("nan"), None);
+
+        // through `config set`, which would otherwise panic the daemon
+        let err = Configuration::default().with_value("dragEndDelay", Value::from("1e300s")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn durations_too_long_to_hold_fall_back() {
+        assert_falls_back("huge-delay", "dragEndDelay = 1e300", "1e300");
+        assert_falls_back("huge-delay-string", "dragEndDelay = \"1e300\"", "1e300");
+
+        let err = Configuration::default().with_value("dragEndDelay", Value::from(1e300)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn absurd_idle_exit_hours_fall_back() {
+        assert_falls_back("huge-idle", "idleExitHours = 1e300", "idleExitHours");
+        assert_falls_back("inf-idle", "idleExitHours = inf", "idleExitHours");
+        assert_falls_back("negative-idle", "idleExitHours = -1.0", "idleExitHours");
+
+        let err = Configuration::default().with_value("idleExitHours", Value::from(1e300)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let unvalidated = Configuration { idle_exit_hours: 1e300, ..Default::default() };
+        assert_eq!(unvalidated.idle_exit_after(), None);
+    }
+
+    #[test]
+    fn the_same_app_always_gets_the_same_profile() {
+        let profile = |acceleration| AppProfile { acceleration: Some(acceleration), ..Default::default() };
+        let cfg = Configuration {
+            app_profiles: BTreeMap::from([
+                ("gimp".to_string(), profile(1.0)),
+                ("Gimp".to_string(), profile(2.0)),
+                ("GIMP".to_string(), profile(3.0))
+            ]),
+            ..Default::default()
+        };
+
+        // written exactly as the app ID wins
+        assert_eq!(cfg.profile_for("gimp").unwrap().acceleration, Some(1.0));
+        assert_eq!(cfg.profile_for("Gimp").unwrap().acceleration, Some(2.0));
+        // and otherwise, the first by key
+        assert_eq!(cfg.profile_for("gImP").unwrap().acceleration, Some(3.0));
+        assert!(cfg.profile_for("inkscape").is_none());
+    }
+
+    #[test]
+    fn values_in_range_load() {
+        let (parsed, loaded) = load_toml("in-range", "acceleration = 1.5\ndragEndDelay = 300");
+        assert!(parsed.is_ok());
+        assert_eq!(loaded.acceleration, 1.5);
+        assert_eq!(loaded.drag_end_delay, Duration::from_millis(300));
+    }
+}
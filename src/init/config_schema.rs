@@ -0,0 +1,29 @@
+// `--print-config-schema`: a JSON Schema for the config file, generated
+// from `Configuration` itself so it can't drift from what's actually
+// loaded. Pointing an editor at it (with a `"$schema"` key in the config
+// file, or the editor's own settings) gets completion and checking of
+// field names and values as you type.
+
+use schemars::schema_for;
+use serde_json::{json, Value};
+
+use super::{config::Configuration, generate_config::FIELD_NOTES};
+
+
+/// The schema for `3fd-config.json`, with each field described by the
+/// same line `generate-config` comments it with.
+pub fn config_schema() -> Value {
+
+    let mut schema = schema_for!(Configuration).to_value();
+    if let Some(options) = schema.get_mut("properties").and_then(Value::as_object_mut) {
+        for (field, note) in FIELD_NOTES {
+            if let Some(property) = options.get_mut(*field).and_then(Value::as_object_mut) {
+                property.insert("description".to_string(), json!(note));
+            }
+        }
+        // editors read this from the file itself, and it's otherwise ignored
+        options.insert("$schema".to_string(), json!({ "type": "string" }));
+    }
+
+    schema
+}
@@ -0,0 +1,164 @@
+// `--daemon`: for init systems without user services (or a plain
+// `.xinitrc`), the program can put itself in the background, the
+// old-fashioned way: fork, start a new session, fork again, and write its
+// pid to a pidfile for whatever needs to find it later. Its output goes
+// to the log file from the config, once that's loaded, or nowhere if
+// there isn't one. Stopping it (with SIGTERM, or `stop`) works the same
+// as in the foreground, button release and all.
+//
+// This all has to happen before the tokio runtime starts, since only the
+// thread that forks carries on in the child.
+
+use std::{
+    fs::OpenOptions,
+    io::{Error, ErrorKind, Read, Write},
+    os::{fd::AsRawFd, unix::net::UnixStream},
+    path::{Path, PathBuf}
+};
+
+use nix::{
+    errno::Errno,
+    sys::signal::kill,
+    unistd::{dup2, fork, setsid, ForkResult, Pid}
+};
+
+
+/// Where the pidfile goes without `--pidfile`: next to the control socket.
+pub fn default_pidfile_path() -> Result<PathBuf, Error> {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) => Ok(PathBuf::from(runtime_dir).join("l3fd.pid")),
+        None => Err(
+            Error::new(
+                ErrorKind::NotFound,
+                "$XDG_RUNTIME_DIR not defined in environment, so give the pidfile's path with --pidfile"
+            )
+        )
+    }
+}
+
+
+/// The pidfile, which is removed when this is dropped (as long as it
+/// still has this process's pid in it).
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if read_pid(&self.path) == Some(std::process::id() as i32) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+
+fn read_pid(path: &Path) -> Option<i32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+
+/// Whether `pid` is a process that's still around.
+fn is_alive(pid: i32) -> bool {
+    // EPERM means it's there, just someone else's
+    matches!(kill(Pid::from_raw(pid), None), Ok(()) | Err(Errno::EPERM))
+}
+
+
+/// Puts the program in the background, with its pid in `pidfile`. This
+/// only returns in the background process; the one that was started
+/// exits once that's up (or prints why it couldn't be, and exits with 1).
+/// Must be called while the program only has the one thread.
+pub fn daemonize(pidfile: &Path) -> Result<PidFile, Error> {
+
+    match read_pid(pidfile) {
+        // re-executed (by `reexec`) from a daemon that already did all this
+        Some(pid) if pid == std::process::id() as i32 => {
+            return Ok(PidFile { path: pidfile.to_path_buf() });
+        },
+        Some(pid) if is_alive(pid) => return Err(
+            Error::new(
+                ErrorKind::AlreadyExists,
+                format!("already running as pid {} (going by {})", pid, pidfile.display())
+            )
+        ),
+        // anything else is left over from one that didn't exit cleanly
+        _ => ()
+    }
+
+    // the background process says how starting up went over this, so the
+    // one in the foreground can pass it on before exiting
+    let (mut report_reader, mut report_writer) = UnixStream::pair()?;
+
+    // SAFETY: there's only the one thread yet (see above)
+    if let ForkResult::Parent { .. } = unsafe { fork() }? {
+        drop(report_writer);
+        let mut report = String::new();
+        let _ = report_reader.read_to_string(&mut report);
+        match report.strip_prefix("ok ") {
+            Some(pid) => {
+                println!("linux-3-finger-drag is running in the background, as pid {}", pid);
+                std::process::exit(0);
+            },
+            None => {
+                eprintln!("Could not start in the background: {}", if report.is_empty() { "it exited right away" } else { &report });
+                std::process::exit(1);
+            }
+        }
+    }
+    drop(report_reader);
+
+    // a session of its own, so it doesn't go when the terminal does, and
+    // a second fork, so it can never get a terminal back
+    setsid()?;
+    // SAFETY: still only the one thread
+    if let ForkResult::Parent { .. } = unsafe { fork() }? {
+        std::process::exit(0);
+    }
+
+    // staying in the same folder, so relative paths (like `--device`'s,
+    // or logFile) still mean what they did
+    let result = write_pidfile(pidfile).and_then(|pidfile| {
+        detach_output()?;
+        Ok(pidfile)
+    });
+    let _ = match &result {
+        Ok(_) => write!(report_writer, "ok {}", std::process::id()),
+        Err(e) => write!(report_writer, "{}", e)
+    };
+    result
+}
+
+
+fn write_pidfile(path: &Path) -> Result<PidFile, Error> {
+    std::fs::write(path, format!("{}\n", std::process::id()))
+        .map_err(|e| Error::new(e.kind(), format!("could not write the pidfile {}: {}", path.display(), e)))?;
+    Ok(PidFile { path: path.to_path_buf() })
+}
+
+
+/// Points stdin, stdout and stderr at /dev/null, since the terminal
+/// they were is no longer the program's.
+fn detach_output() -> Result<(), Error> {
+    let dev_null = OpenOptions::new().read(true).write(true).open("/dev/null")?;
+    for fd in 0..=2 {
+        dup2(dev_null.as_raw_fd(), fd)?;
+    }
+    Ok(())
+}
+
+
+/// Sends stdout and stderr (so the lines printed before the logger's up,
+/// and any panic) to `log_file`, as logFile has it in the config. With
+/// `"stdout"` (or a file that won't open), they stay going nowhere.
+pub fn redirect_output(log_file: &str) {
+    if log_file == "stdout" {
+        return;
+    }
+    // nowhere to say so if this fails, which the logger will find out for itself
+    if let Ok(file) = OpenOptions::new().append(true).open(log_file) {
+        let _ = dup2(file.as_raw_fd(), 1);
+        let _ = dup2(file.as_raw_fd(), 2);
+    }
+}
+
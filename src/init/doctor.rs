@@ -0,0 +1,202 @@
+// `doctor`: goes through everything the program needs from the system,
+// one thing at a time, and says what to do about whatever's missing.
+// Unlike `setup`, it doesn't change anything, so it's safe to run (and
+// paste the output of) when reporting a bug. The daemon works most of
+// this out for itself when something fails, but only the first thing,
+// and only in the log.
+
+use std::{
+    fs::{metadata, read_dir, read_to_string},
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::Path,
+    process::Command
+};
+
+use users::{get_current_uid, get_group_by_gid, get_user_by_uid, get_user_groups, group_access_list};
+
+use super::{
+    libinput_init::{self, DeviceVerdict},
+    sandbox,
+    setup
+};
+
+
+const UDEV_RULE_DIRS: &[&str] = &["/etc/udev/rules.d", "/run/udev/rules.d", "/usr/lib/udev/rules.d", "/lib/udev/rules.d"];
+
+
+/// How one check went, with what to do about it if it failed.
+#[derive(Debug)]
+pub enum Diagnosis {
+    Pass(String),
+    Fail { problem: String, hint: String }
+}
+
+fn fail(problem: impl Into<String>, hint: impl Into<String>) -> Diagnosis {
+    Diagnosis::Fail { problem: problem.into(), hint: hint.into() }
+}
+
+
+/// Every check, in the order they're best fixed in, by name.
+pub fn examine() -> Vec<(&'static str, Diagnosis)> {
+    vec![
+        ("sandbox", check_sandbox()),
+        ("uinput module", check_uinput_module()),
+        ("udev rule", check_udev_rule()),
+        ("/dev/uinput", check_uinput_access()),
+        ("input group", check_input_group()),
+        ("session seat", check_seat()),
+        ("trackpad", check_trackpad())
+    ]
+}
+
+
+/// Prints `checks` as `doctor` does, returning whether they all passed.
+pub fn print(checks: &[(&str, Diagnosis)]) -> bool {
+    for (name, diagnosis) in checks {
+        match diagnosis {
+            Diagnosis::Pass(detail) => println!("[ ok ]  {name}: {detail}"),
+            Diagnosis::Fail { problem, hint } => {
+                println!("[FAIL]  {name}: {problem}");
+                println!("        -> {hint}");
+            }
+        }
+    }
+    checks.iter().all(|(_, diagnosis)| matches!(diagnosis, Diagnosis::Pass(_)))
+}
+
+
+fn check_sandbox() -> Diagnosis {
+    match sandbox::detect_sandbox() {
+        None => Diagnosis::Pass("not in a Flatpak, Snap, or container".to_string()),
+        Some(found) => {
+            let inside = match &found {
+                sandbox::Sandbox::Flatpak => "a Flatpak".to_string(),
+                sandbox::Sandbox::Snap => "a Snap".to_string(),
+                sandbox::Sandbox::Container(kind) => format!("a container ({kind})")
+            };
+            fail(format!("running inside {inside}"), sandbox::sandbox_hint(&found))
+        }
+    }
+}
+
+
+fn check_uinput_module() -> Diagnosis {
+    // /sys/module has it whether it's loaded or built in
+    if Path::new("/sys/module/uinput").exists() || Path::new("/dev/uinput").exists() {
+        Diagnosis::Pass("loaded".to_string())
+    } else {
+        fail(
+            "the uinput kernel module isn't loaded",
+            "load it with `sudo modprobe uinput`, and have it load on boot with \
+            `echo uinput | sudo tee /etc/modules-load.d/uinput.conf`"
+        )
+    }
+}
+
+
+fn check_udev_rule() -> Diagnosis {
+    if setup::udev_rule_installed() {
+        return Diagnosis::Pass("60-uinput.rules is installed".to_string());
+    }
+
+    // someone else's rule (a distro package's, say) does just as well
+    let other_rule = UDEV_RULE_DIRS.iter()
+        .filter_map(|dir| read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.path())
+        .find(|path| read_to_string(path).is_ok_and(|rule| rule.contains("KERNEL==\"uinput\"")));
+
+    match other_rule {
+        Some(path) => Diagnosis::Pass(format!("{} has a rule for uinput", path.display())),
+        None => fail(
+            "no udev rule gives access to /dev/uinput",
+            "install it with `sudo linux-3-finger-drag install-udev-rule`, or follow step 3.1 of the README"
+        )
+    }
+}
+
+
+fn check_uinput_access() -> Diagnosis {
+    if setup::uinput_is_writable() {
+        return Diagnosis::Pass("can be opened".to_string());
+    }
+
+    let Ok(found) = metadata("/dev/uinput") else {
+        return fail("there's no /dev/uinput", "see the uinput module check above");
+    };
+    let group = get_group_by_gid(found.gid())
+        .map(|group| group.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| found.gid().to_string());
+    fail(
+        format!("can't be opened (mode {:o}, group {})", found.permissions().mode() & 0o777, group),
+        "with the udev rule installed, it should be group input and mode 660; if it isn't, \
+        apply the rule with `sudo udevadm trigger /dev/uinput`, or reboot"
+    )
+}
+
+
+fn check_input_group() -> Diagnosis {
+
+    let Some(you) = get_user_by_uid(get_current_uid()) else {
+        return fail("the current user isn't in the user database", "this is odd; please open an issue");
+    };
+    let is_input = |groups: Vec<users::Group>| groups.iter().any(|group| group.name() == "input");
+
+    let in_database = get_user_groups(you.name(), you.primary_group_id()).is_some_and(is_input);
+    let in_session = group_access_list().is_ok_and(is_input);
+
+    match (in_database, in_session) {
+        (_, true) => Diagnosis::Pass("you're in the input group".to_string()),
+        (true, false) => fail(
+            "you've been added to the input group, but not in this session yet",
+            "log out and back in (or reboot)"
+        ),
+        (false, false) => fail(
+            "you're not in the input group, so trackpads can't be read",
+            format!("add yourself with `sudo gpasswd --add {} input`, then log out and back in", you.name().to_string_lossy())
+        )
+    }
+}
+
+
+fn check_seat() -> Diagnosis {
+
+    let seat = std::env::var("XDG_SEAT").ok()
+        .or_else(|| {
+            let id = std::env::var("XDG_SESSION_ID").ok()?;
+            let output = Command::new("loginctl")
+                .args(["show-session", &id, "--property=Seat", "--value"])
+                .output().ok()?;
+            output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .filter(|seat| !seat.is_empty());
+
+    match seat {
+        Some(seat) if seat == "seat0" => Diagnosis::Pass("on seat0".to_string()),
+        Some(seat) => fail(
+            format!("this session is on {}, but trackpads are only looked for on seat0", seat),
+            "run it from a session on seat0, or give the trackpad with --device"
+        ),
+        None => fail(
+            "this session isn't on a seat (over SSH, or from a service?)",
+            "run it from your desktop session; udev's uaccess only lets the seat's active session at the devices"
+        )
+    }
+}
+
+
+fn check_trackpad() -> Diagnosis {
+    match libinput_init::list_devices(&[]) {
+        Ok(devices) => match devices.iter().find(|device| device.verdict == DeviceVerdict::Trackpad) {
+            Some(trackpad) => Diagnosis::Pass(format!("found {} ({})", trackpad.name, trackpad.sysname)),
+            None => fail(
+                format!("none of the {} input devices can do gestures", devices.len()),
+                "check `linux-3-finger-drag list-devices`; if your trackpad is there, please open an issue"
+            )
+        },
+        Err(e) => fail(
+            format!("input devices can't be read ({})", e),
+            "see the input group check above"
+        )
+    }
+}
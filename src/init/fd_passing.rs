@@ -0,0 +1,55 @@
+// Passing open file descriptors between processes over a Unix socket
+// (with SCM_RIGHTS). This is how the setgid open helper hands devices
+// to the main process: the helper has the permissions to open them,
+// and the main process doesn't need any.
+
+use std::{
+    io::{Error, ErrorKind, IoSlice, IoSliceMut},
+    os::{fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd}, unix::net::UnixStream}
+};
+
+use nix::sys::socket::{recvmsg, sendmsg, ControlMessage, ControlMessageOwned, MsgFlags};
+
+
+/// Sends `fd` over `socket`. The receiving end gets its own copy
+/// of the fd, so the sender can close its copy afterward.
+pub fn send_fd(socket: &UnixStream, fd: BorrowedFd) -> Result<(), Error> {
+
+    let fds = [fd.as_raw_fd()];
+    let cmsgs = [ControlMessage::ScmRights(&fds)];
+
+    // at least one byte of regular data has to go along with the fd
+    let iov = [IoSlice::new(b"F")];
+
+    sendmsg::<()>(socket.as_raw_fd(), &iov, &cmsgs, MsgFlags::empty(), None)?;
+    Ok(())
+}
+
+
+/// Receives a single fd sent with `send_fd()`. If the other end closes
+/// the socket without sending one, this returns an `UnexpectedEof` error.
+pub fn recv_fd(socket: &UnixStream) -> Result<OwnedFd, Error> {
+
+    let mut buf = [0u8; 1];
+    let mut iov = [IoSliceMut::new(&mut buf)];
+    let mut cmsg_buffer = nix::cmsg_space!(RawFd);
+
+    let msg = recvmsg::<()>(
+        socket.as_raw_fd(),
+        &mut iov,
+        Some(&mut cmsg_buffer),
+        MsgFlags::MSG_CMSG_CLOEXEC
+    )?;
+
+    for cmsg in msg.cmsgs()? {
+        if let ControlMessageOwned::ScmRights(fds) = cmsg {
+            if let Some(&fd) = fds.first() {
+                // SAFETY: the kernel just gave us this fd,
+                // so nothing else in the process owns it
+                return Ok(unsafe { OwnedFd::from_raw_fd(fd) });
+            }
+        }
+    }
+
+    Err(Error::new(ErrorKind::UnexpectedEof, "no file descriptor was received"))
+}
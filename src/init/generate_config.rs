@@ -0,0 +1,147 @@
+// `generate-config`: writes out a config file with every field set to its
+// default, so new users have the field names in front of them instead of
+// having to look them up. TOML and YAML allow comments, so those come
+// with a line on what each field does; JSON doesn't, so it's bare (the
+// README has the details either way).
+
+use std::{
+    fs::{create_dir_all, write},
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf}
+};
+
+use super::config::{self, ConfigFormat, Configuration};
+
+
+// A line on each field, for the formats that allow comments
+pub(crate) const FIELD_NOTES: &[(&str, &str)] = &[
+    ("acceleration", "speed multiplier for three-finger drags"),
+    ("accelerationProfile", "how speed follows finger speed: adaptive (libinput's), flat, or linear"),
+    ("accelerationX", "acceleration for horizontal motion only (null follows acceleration)"),
+    ("accelerationY", "acceleration for vertical motion only (null follows acceleration)"),
+    ("activeProfile", "which of profiles is in use (null for none)"),
+    ("appProfiles", "settings to use instead while an app is focused, by app ID or window class"),
+    ("clickSuppressTime", "ms after a drag ends to ignore three-finger taps (0 is off)"),
+    ("dbusDragEvents", "send DragStarted and DragEnded D-Bus signals as drags start and end"),
+    ("deadZone", "trackpad units of jitter to ignore during a drag (0 is off)"),
+    ("devices", "settings for particular trackpads, by name or vendor:product"),
+    ("disabledDevices", "trackpads not to read at all, by name or sysname (* and ? allowed)"),
+    ("dragButton", "the button a drag holds: left, middle, or right"),
+    ("dragCancelDelay", "ms the button stays held after libinput cancels a drag (null follows dragEndDelay)"),
+    ("dragEndDelay", "ms the button stays held after your fingers lift, to reposition them"),
+    ("gestureMacros", "commands to run for sequences of three-finger gestures"),
+    ("holdToRightClick", "a long three-finger press right-clicks (or right-drags)"),
+    ("hyprlandDisableConflicts", "turn off Hyprland's three-finger workspace swipe while running"),
+    ("hyprlandDragEvents", "send custom events to Hyprland's event socket as drags start and end"),
+    ("idleExitHours", "exit once the trackpad's gone unused this long (0 never exits)"),
+    ("invertX", "drag left when the fingers move right, and the other way around"),
+    ("invertY", "drag down when the fingers move up, and the other way around"),
+    ("jiggleAmplitude", "how far jiggleInterval nudges the cursor, in pixels (at most 10)"),
+    ("jiggleInterval", "ms a held drag can rest before the cursor is jiggled (0 is off)"),
+    ("logFile", "a file to log to (which has to exist), or \"stdout\""),
+    ("logLevel", "off, error, warn, info, debug, or trace"),
+    ("maxEventRate", "most motion events per second during a drag (0 is no limit)"),
+    ("maxSpeed", "furthest the cursor moves for one motion update, in pixels (0 is no limit)"),
+    ("oneFingerContinue", "lift two fingers mid-drag and carry on with one"),
+    ("outputMode", "\"pointer\" for a held mouse button, or \"touch\" for a touchscreen drag"),
+    ("outputScales", "multipliers for scaleByOutput, by output name"),
+    ("pauseForApps", "app IDs or window classes to pause drags for while focused"),
+    ("pauseWhenFullscreen", "pause drags while the focused window is fullscreen"),
+    ("preset", "mac-like, precise, fast, or large-display, for the fields left out"),
+    ("profiles", "named sets of fields to switch between, with activeProfile or the profile command"),
+    ("responseTime", "ms the main loop waits between batches of events, or \"auto\" to follow the trackpad"),
+    ("restingThumbZone", "height of the strip where a resting thumb is ignored, as a fraction (0 is off)"),
+    ("rightClickHoldTime", "ms a press has to last for holdToRightClick"),
+    ("scaleByOutput", "multiply drags by the focused output's outputScales multiplier (Sway and Hyprland)"),
+    ("sensitivityUnits", "what acceleration is per: \"raw\" trackpad units, or \"mm\""),
+    ("startThreshold", "how far fingers move (in trackpad units) before a drag presses the button"),
+    ("strict", "unknown fields are an error instead of being ignored"),
+    ("suppressTapWhileDragging", "turn off the desktop's tap-to-click while a drag holds the button"),
+    ("syncDesktopSpeed", "follow your desktop's touchpad pointer speed"),
+    ("threeFingerTap", "what a three-finger tap clicks: \"left\", \"middle\", \"right\", or \"none\""),
+    ("wayland", "fields that change in a Wayland session"),
+    ("x11", "fields that change in an X11 session"),
+];
+
+
+/// Where `generate-config` writes a config in `format`: the program's
+/// config folder, with the file name for that format.
+pub fn generated_config_path(format: ConfigFormat) -> Result<PathBuf, Error> {
+    let file_name = match format {
+        ConfigFormat::Json => "3fd-config.json",
+        ConfigFormat::Toml => "3fd-config.toml",
+        ConfigFormat::Yaml => "3fd-config.yaml"
+    };
+    let config_folder = config::get_config_file_path()?
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no config folder to put the config in"))?;
+
+    Ok(config_folder.join(file_name))
+}
+
+
+/// The default config, written out in `format`, with comments if it can
+/// have them.
+pub fn default_config_text(format: ConfigFormat) -> Result<String, Error> {
+
+    let mut as_json = serde_json::to_value(Configuration::default())?;
+    // TOML has no null, so unset fields (like `preset`) are left out
+    if let (ConfigFormat::Toml, Some(options)) = (format, as_json.as_object_mut()) {
+        options.retain(|_, value| !value.is_null());
+    }
+    let text = format.serialize(&as_json)?;
+
+    let mut commented = String::new();
+    for line in text.lines() {
+        let note = top_level_key(format, line)
+            .and_then(|key| FIELD_NOTES.iter().find(|(field, _)| *field == key));
+        if let Some((_, note)) = note {
+            commented.push_str(&format!("# {}\n", note));
+        }
+        commented.push_str(line);
+        commented.push('\n');
+    }
+    Ok(commented)
+}
+
+
+/// The field a line of a config in `format` starts, if it starts one.
+fn top_level_key(format: ConfigFormat, line: &str) -> Option<&str> {
+    match format {
+        ConfigFormat::Json => None,
+        // `key = value`, or `[key]` for a table
+        ConfigFormat::Toml => line.split(" =").next()
+            .map(|key| key.trim_start_matches('[').trim_end_matches(']')),
+        ConfigFormat::Yaml => (!line.starts_with(' ')).then(|| line.split(':').next()).flatten()
+    }
+}
+
+
+/// Writes the default config in `format` to where `generate-config` puts
+/// it, creating the config folder if need be. An existing file is only
+/// replaced with `overwrite`.
+pub fn write_default_config(format: ConfigFormat, overwrite: bool) -> Result<PathBuf, Error> {
+
+    let path = generated_config_path(format)?;
+    if path.exists() && !overwrite {
+        return Err(Error::new(ErrorKind::AlreadyExists, format!("{} already exists", path.display())));
+    }
+
+    if let Some(config_folder) = path.parent() {
+        create_dir_all(config_folder)?;
+    }
+    write(&path, default_config_text(format)?)?;
+    Ok(path)
+}
+
+
+/// The format named on the command line.
+pub fn format_from_name(name: &str) -> Option<ConfigFormat> {
+    match name {
+        "json" => Some(ConfigFormat::Json),
+        "toml" => Some(ConfigFormat::Toml),
+        "yaml" | "yml" => Some(ConfigFormat::Yaml),
+        _ => None
+    }
+}
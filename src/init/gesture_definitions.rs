@@ -0,0 +1,153 @@
+// Named gestures, each in a drop-in file of its own under `gestures.d`
+// (next to the config file), saying which gesture to look for and the
+// command to run for it. See `gesture_matcher` for how they're matched.
+//
+// A file is a JSON object like:
+//
+// {
+//     "fingers": 4,
+//     "type": "swipe",
+//     "direction": "left",
+//     "command": "swaymsg workspace next",
+//     "priority": 10
+// }
+//
+// The gesture is named after its file (minus the `.json`), unless it has a
+// `"name"` of its own. Files are read in name order, and anything that
+// doesn't load is skipped with a warning, so one broken file doesn't take
+// the rest down with it.
+
+use std::{
+    fs::read_to_string,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf}
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+
+pub const GESTURES_DIR_NAME: &str = "gestures.d";
+
+
+/// The kind of libinput gesture a definition is for.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GestureKind { Swipe, Pinch, Hold }
+
+
+/// Which way a gesture went: a swipe's main direction, or whether the
+/// fingers of a pinch came together (`in`) or spread apart (`out`).
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GestureDirection { Left, Right, Up, Down, In, Out }
+
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GestureDefinition {
+    #[serde(default)]
+    pub name: String,
+
+    pub fingers: i32,
+
+    #[serde(rename = "type")]
+    pub kind: GestureKind,
+
+    #[serde(default)]
+    pub direction: Option<GestureDirection>,    // none matches any direction
+
+    #[serde(default = "default_min_distance")]
+    pub min_distance: f64,              // in trackpad units, for swipes
+
+    pub command: String,
+
+    #[serde(default)]
+    pub priority: i32,                  // the highest wins when several match
+}
+
+fn default_min_distance() -> f64 { 100.0 }
+
+
+impl GestureDefinition {
+
+    /// Checks for definitions that would load, but could never match (or
+    /// would fight the built-in drag).
+    fn validate(&self) -> Result<(), Error> {
+
+        let invalid = |message: String| Err(Error::new(ErrorKind::InvalidData, message));
+
+        if !(1..=5).contains(&self.fingers) {
+            return invalid(format!("fingers must be from 1 to 5, not {}", self.fingers));
+        }
+        match (self.kind, self.fingers) {
+            (GestureKind::Swipe, 3) => return invalid("three-finger swipes are drags; use gestureMacros \
+                to run commands for them, or pick another finger count".to_string()),
+            // libinput makes fewer fingers than this into scrolling
+            (GestureKind::Swipe, fingers) if fingers < 3 => return invalid("swipes need at least 3 fingers".to_string()),
+            (GestureKind::Pinch, fingers) if fingers < 2 => return invalid("pinches need at least 2 fingers".to_string()),
+            _ => {}
+        }
+        if !self.min_distance.is_finite() || self.min_distance < 0.0 {
+            return invalid(format!("minDistance must be a non-negative number, not {}", self.min_distance));
+        }
+
+        use GestureDirection::*;
+        match (self.kind, self.direction) {
+            (_, None) => Ok(()),
+            (GestureKind::Swipe, Some(Left | Right | Up | Down)) => Ok(()),
+            (GestureKind::Pinch, Some(In | Out)) => Ok(()),
+            (kind, Some(direction)) => invalid(format!("a {kind:?} can't go {direction:?}").to_lowercase())
+        }
+    }
+}
+
+
+/// Where the named gestures go for the config file at `config_path`.
+pub fn gestures_dir(config_path: &Path) -> PathBuf {
+    config_path.with_file_name(GESTURES_DIR_NAME)
+}
+
+
+/// Loads every gesture definition in `dir`, skipping (and warning about)
+/// any that don't load. There being no such directory just means there
+/// aren't any.
+pub fn load_gesture_definitions(dir: &Path) -> Vec<GestureDefinition> {
+
+    let Ok(entries) = dir.read_dir() else { return Vec::new() };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    let definitions: Vec<GestureDefinition> = paths.iter()
+        .filter_map(|path| match load_definition(path) {
+            Ok(definition) => Some(definition),
+            Err(e) => {
+                warn!("Skipping the gesture in {:?}: {}", path, e);
+                None
+            }
+        })
+        .collect();
+
+    if !definitions.is_empty() {
+        info!("Loaded {} named gesture(s) from {:?}", definitions.len(), dir);
+    }
+    definitions
+}
+
+
+fn load_definition(path: &Path) -> Result<GestureDefinition, Error> {
+
+    let mut definition: GestureDefinition = serde_json::from_str(&read_to_string(path)?)?;
+    if definition.name.is_empty() {
+        definition.name = path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+    }
+
+    definition.validate()?;
+    Ok(definition)
+}
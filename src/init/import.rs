@@ -0,0 +1,298 @@
+// Importing settings from the config files of other gesture tools
+// (libinput-gestures, fusuma, and touchegg), for anyone switching over.
+//
+// Not much carries over directly, since those tools mostly bind gestures
+// to commands, and this program only does drags. What does carry over is
+// fusuma's three-finger drag recipe (`xdotool mouseDown` on `begin`),
+// whose `accel` is the same thing as `acceleration` here, and how far a
+// swipe has to go before it counts (libinput-gestures' `swipe_threshold`,
+// and fusuma's swipe `threshold`), which is `startThreshold` here, since
+// both measure libinput's motion the same way. Everything else is flagged, most importantly any three-finger swipe bindings, since
+// those will fight with three-finger drags until they're moved to four
+// fingers.
+//
+// None of these formats get a full parser; the files are simple enough,
+// and only a handful of settings are of interest, so they're scanned
+// line by line instead.
+
+use std::{
+    fs::read_to_string,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf}
+};
+
+use serde_json::{json, Map, Value};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool { LibinputGestures, Fusuma, Touchegg }
+
+impl Tool {
+
+    pub const ALL: [Tool; 3] = [Tool::LibinputGestures, Tool::Fusuma, Tool::Touchegg];
+
+    pub fn from_name(name: &str) -> Option<Tool> {
+        match name {
+            "libinput-gestures" => Some(Tool::LibinputGestures),
+            "fusuma"            => Some(Tool::Fusuma),
+            "touchegg"          => Some(Tool::Touchegg),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::LibinputGestures => "libinput-gestures",
+            Tool::Fusuma           => "fusuma",
+            Tool::Touchegg         => "touchegg"
+        }
+    }
+
+    /// Where the tool's config usually lives, the user's own first.
+    fn default_paths(self) -> Vec<PathBuf> {
+        let user_config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+        let (user_file, system_file) = match self {
+            Tool::LibinputGestures => ("libinput-gestures.conf", "/etc/libinput-gestures.conf"),
+            Tool::Fusuma           => ("fusuma/config.yml", "/etc/fusuma/config.yml"),
+            Tool::Touchegg         => ("touchegg/touchegg.conf", "/usr/share/touchegg/touchegg.conf")
+        };
+
+        user_config.map(|dir| dir.join(user_file))
+            .into_iter()
+            .chain([PathBuf::from(system_file)])
+            .collect()
+    }
+
+    /// The tool's config file, if it has one where it usually would.
+    pub fn find_config(self) -> Option<PathBuf> {
+        self.default_paths().into_iter().find(|path| path.exists())
+    }
+}
+
+
+/// What came out of an import: config values in this program's format,
+/// and notes on whatever couldn't be mapped.
+#[derive(Debug, Default)]
+pub struct Import {
+    pub values: Map<String, Value>,
+    pub notes: Vec<String>
+}
+
+
+/// Reads `path` as a config file for `tool`, and converts what it can.
+pub fn import_from(tool: Tool, path: &Path) -> Result<Import, Error> {
+    let text = read_to_string(path)
+        .map_err(|e| Error::new(e.kind(), format!("Unable to read {:?}: {}", path, e)))?;
+
+    let mut import = match tool {
+        Tool::LibinputGestures => import_libinput_gestures(&text),
+        Tool::Fusuma           => import_fusuma(&text),
+        Tool::Touchegg         => import_touchegg(&text)
+    };
+
+    // anything out of range is no use, so it's flagged instead
+    let defaults = super::config::Configuration::default();
+    import.values.retain(|key, value| match defaults.with_value(key, value.clone()) {
+        Ok(_) => true,
+        Err(e) => {
+            import.notes.push(format!("{key} would be {value}, which can't be used: {e}"));
+            false
+        }
+    });
+
+    Ok(import)
+}
+
+
+const FOUR_FINGER_HINT: &str = "it will conflict with three-finger drags, so move it to four fingers";
+
+
+// fusuma's swipe threshold is a multiplier on this distance (its
+// swipe detector's BASE_THRESHOLD), in libinput's motion units
+const FUSUMA_BASE_SWIPE_THRESHOLD: f64 = 25.0;
+
+
+/// libinput-gestures only runs commands, so besides its swipe threshold,
+/// all there is to do here is point out the gestures that will conflict,
+/// and the settings that have no equivalent.
+fn import_libinput_gestures(text: &str) -> Import {
+
+    let mut import = Import::default();
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            // `gesture swipe up 3 <command>`, or with no finger count, for any number of them
+            ["gesture", "swipe" | "hold", rest @ ..] => {
+                let fingers = rest.iter().take(2).find_map(|word| word.parse::<u32>().ok());
+                if fingers.is_none_or(|fingers| fingers == 3) {
+                    import.notes.push(format!("`{line}`: {FOUR_FINGER_HINT}{}",
+                        if fingers.is_none() { " (with no finger count, it applies to three too)" } else { "" }
+                    ));
+                } else {
+                    import.notes.push(format!("`{line}`: gesture commands aren't something this \
+                        program does, so keep libinput-gestures around for these"));
+                }
+            },
+            ["gesture", ..] => import.notes.push(format!("`{line}`: gesture commands aren't something \
+                this program does, so keep libinput-gestures around for these")),
+            ["swipe_threshold", threshold] => match threshold.parse::<f64>() {
+                Ok(threshold) => { import.values.insert("startThreshold".to_string(), json!(threshold)); },
+                Err(_) => import.notes.push(format!("`{line}`: {threshold:?} isn't a number"))
+            },
+            [setting, ..] => import.notes.push(format!("`{line}`: {setting} has no equivalent here")),
+            [] => {}
+        }
+    }
+
+    import
+}
+
+
+/// Flattens YAML's nesting into dotted paths (`swipe.3.begin.command`),
+/// one per line with a value. Lists and multi-line values are skipped,
+/// since fusuma's config doesn't use either for anything read here.
+fn flatten_yaml(text: &str) -> Vec<(String, String)> {
+
+    let mut parents: Vec<(usize, String)> = Vec::new();
+    let mut flattened = Vec::new();
+
+    for line in text.lines() {
+        let content = line.split(" #").next().unwrap_or_default().trim_end();
+        let trimmed = content.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('-') {
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else { continue };
+
+        let indent = content.len() - trimmed.len();
+        while parents.last().is_some_and(|(parent_indent, _)| *parent_indent >= indent) {
+            parents.pop();
+        }
+
+        let key = key.trim().trim_matches(['"', '\'']).to_string();
+        let value = value.trim().trim_matches(['"', '\'']);
+        let path = parents.iter()
+            .map(|(_, parent)| parent.as_str())
+            .chain([key.as_str()])
+            .collect::<Vec<_>>()
+            .join(".");
+
+        if value.is_empty() {
+            parents.push((indent, key));
+        } else {
+            flattened.push((path, value.to_string()));
+        }
+    }
+
+    flattened
+}
+
+
+/// fusuma's three-finger drag recipe maps onto this program as a whole:
+/// `accel` on its `update` is `acceleration` here. Its swipe threshold is
+/// `startThreshold`. Any other three-finger swipe is a conflict.
+fn import_fusuma(text: &str) -> Import {
+
+    let mut import = Import::default();
+    let flattened = flatten_yaml(text);
+
+    let is_drag_recipe = flattened.iter().any(|(path, value)|
+        path.starts_with("swipe.3.begin.") && value.contains("mouseDown")
+    );
+
+    for (path, value) in &flattened {
+        let segments: Vec<&str> = path.split('.').collect();
+
+        match segments.as_slice() {
+            ["swipe", "3", "update", "accel"] if is_drag_recipe => match value.parse::<f64>() {
+                Ok(accel) => { import.values.insert("acceleration".to_string(), json!(accel)); },
+                Err(_) => import.notes.push(format!("{path}: {value:?} isn't a number"))
+            },
+            ["swipe", "3", "begin" | "update" | "end", ..] if is_drag_recipe => {},
+            ["swipe", "3", direction, ..] => import.notes.push(format!("three-finger swipe {direction} \
+                ({path}: {value}): {FOUR_FINGER_HINT}")),
+            ["threshold", "swipe"] => match value.parse::<f64>() {
+                Ok(multiplier) => {
+                    import.values.insert("startThreshold".to_string(), json!(multiplier * FUSUMA_BASE_SWIPE_THRESHOLD));
+                },
+                Err(_) => import.notes.push(format!("{path}: {value:?} isn't a number"))
+            },
+            ["threshold" | "interval", ..] => import.notes.push(format!("{path}: {value}: fusuma's \
+                other thresholds and intervals have no equivalent here")),
+            _ => import.notes.push(format!("{path}: {value}: this isn't something this program does, \
+                so keep fusuma around for it"))
+        }
+    }
+
+    if is_drag_recipe {
+        import.notes.push("fusuma's three-finger drag recipe was found; remove it from fusuma's \
+            config once this program is set up, or the two will both be dragging".to_string());
+    }
+
+    import
+}
+
+
+/// The value of `name="..."` in an XML tag.
+fn xml_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!("{name}=\""))? + name.len() + 2;
+    let length = tag[start..].find('"')?;
+    Some(&tag[start..start + length])
+}
+
+
+/// touchegg binds gestures to actions, none of which are drags, so this
+/// just flags three-finger gestures and touchegg's own settings.
+fn import_touchegg(text: &str) -> Import {
+
+    let mut import = Import::default();
+
+    for tag in text.split('<').skip(1) {
+        let tag = tag.split('>').next().unwrap_or_default();
+
+        if tag.starts_with("gesture ") {
+            let kind = xml_attribute(tag, "type").unwrap_or("?");
+            let fingers = xml_attribute(tag, "fingers").unwrap_or("?");
+            let direction = xml_attribute(tag, "direction").unwrap_or("?");
+
+            if fingers == "3" && matches!(kind, "SWIPE" | "TAP") {
+                import.notes.push(format!("three-finger {} {}: {FOUR_FINGER_HINT}",
+                    kind.to_lowercase(), direction.to_lowercase()
+                ));
+            }
+        } else if tag.starts_with("property ") {
+            if let Some(name) = xml_attribute(tag, "name") {
+                import.notes.push(format!("touchegg's {name} setting has no equivalent here"));
+            }
+        }
+    }
+
+    if import.notes.is_empty() {
+        import.notes.push("no three-finger gestures found, so touchegg shouldn't get in the way".to_string());
+    }
+
+    import
+}
+
+
+/// Every tool with a config where it usually would be, for when the
+/// user doesn't say which to import from.
+pub fn find_all_configs() -> Result<Vec<(Tool, PathBuf)>, Error> {
+    let found: Vec<(Tool, PathBuf)> = Tool::ALL.into_iter()
+        .filter_map(|tool| tool.find_config().map(|path| (tool, path)))
+        .collect();
+
+    if found.is_empty() {
+        return Err(Error::new(ErrorKind::NotFound, "No libinput-gestures, fusuma, or touchegg \
+            config was found; give the path to one instead"));
+    }
+    Ok(found)
+}
@@ -0,0 +1,90 @@
+// One instance per user (and one system-wide, for `--system`), since two
+// would each turn every gesture into a drag, so everything injected would
+// come out twice. The lock is a socket in the abstract namespace, which
+// the kernel takes back as soon as the process is gone, however that
+// happens, so there's never a stale one to clean up (unlike a pidfile).
+// It's closed on exec, so `reexec` gets it back like anything else.
+//
+// Nothing ever connects to it for real: connecting is just how a second
+// instance finds out who's holding it.
+
+use std::{
+    io::{Error, ErrorKind},
+    os::{linux::net::SocketAddrExt, unix::net::{SocketAddr, UnixListener, UnixStream}}
+};
+
+use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+use users::get_current_uid;
+
+
+/// What the program exits with when another instance is already running.
+pub const EXIT_ALREADY_RUNNING: i32 = 4;
+
+
+/// Held for as long as the daemon runs.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _socket: UnixListener
+}
+
+
+fn lock_name(system: bool) -> String {
+    if system {
+        "linux-3-finger-drag/system".to_string()
+    } else {
+        format!("linux-3-finger-drag/user/{}", get_current_uid())
+    }
+}
+
+
+/// Takes the lock for a user's instance, or with `system`, the
+/// system-wide one. If another instance has it (or for a user's, if a
+/// system-wide instance is running, which would drag at once too), this
+/// fails with `AlreadyExists`, saying which.
+pub fn acquire(system: bool) -> Result<InstanceLock, Error> {
+
+    if !system {
+        if let Some(pid) = held_by(true) {
+            return Err(already_running(pid, true));
+        }
+    }
+
+    let address = SocketAddr::from_abstract_name(lock_name(system))?;
+    match UnixListener::bind_addr(&address) {
+        Ok(socket) => Ok(InstanceLock { _socket: socket }),
+        Err(e) if e.kind() == ErrorKind::AddrInUse => Err(already_running(held_by(system).flatten(), system)),
+        Err(e) => Err(e)
+    }
+}
+
+
+/// Whether a user's instance (or with `system`, the system-wide one) is
+/// running. Unlike connecting to the control socket, this can't start
+/// one, with the socket unit enabled. A dry run takes no lock, so it
+/// doesn't count.
+pub fn is_running(system: bool) -> bool {
+    held_by(system).is_some()
+}
+
+
+/// Whether the lock is held, and if so, by which pid (if that can be told).
+fn held_by(system: bool) -> Option<Option<i32>> {
+    let address = SocketAddr::from_abstract_name(lock_name(system)).ok()?;
+    let stream = UnixStream::connect_addr(&address).ok()?;
+    Some(getsockopt(&stream, PeerCredentials).ok().map(|credentials| credentials.pid()))
+}
+
+
+fn already_running(pid: Option<i32>, system: bool) -> Error {
+    let pid = pid.map(|pid| format!(" (pid {pid})")).unwrap_or_default();
+    let message = if system {
+        format!("A system-wide instance of linux-3-finger-drag is already running{pid}, and another \
+            instance would double every drag. Stop it first, with \
+            `sudo systemctl stop three-finger-drag-system.service`.")
+    } else {
+        format!("linux-3-finger-drag is already running{pid}, and a second instance would double \
+            every drag. Stop it first with `linux-3-finger-drag stop`, or restart it in place \
+            with `linux-3-finger-drag reexec`.")
+    };
+    Error::new(ErrorKind::AlreadyExists, message)
+}
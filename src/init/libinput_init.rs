@@ -2,16 +2,27 @@ use std::io::{Error, ErrorKind};
 use nix::libc::{O_RDWR, O_WRONLY};
 use std::fs::{File, OpenOptions};
 use std::os::unix::{fs::OpenOptionsExt, io::OwnedFd};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use input::{
     Libinput, 
     LibinputInterface, 
     event::EventTrait, 
-    DeviceCapability::{Gesture, Pointer}
+    DeviceCapability::{self, Gesture, Pointer}
 };
-use tracing::{debug, info, error};
+use tracing::{debug, info, error, warn};
 use users::{get_user_by_uid, get_current_uid, get_user_groups};
 
+use crate::runtime::virtual_trackpad::is_virtual_trackpad;
+use super::{
+    mac_check::log_if_input_denied,
+    sandbox::log_if_sandboxed,
+    open_helper::{is_permission_error, open_via_helper}
+};
+
+// Devices given with `--device`, read instead of searching for trackpads
+static EXPLICIT_DEVICES: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
 // straight from the docs for input.rs, if I'm honest
 pub struct Interface;
 
@@ -23,6 +34,10 @@ impl LibinputInterface for Interface {
             .write(flags & O_WRONLY != 0 || flags & O_RDWR != 0)
             .open(path)
             .map(|file| file.into())
+            // not allowed to open it ourselves, so see if the setgid helper can
+            .or_else(|err| 
+                if is_permission_error(&err) { open_via_helper(path, flags) } else { Err(err) }
+            )
             .map_err(|err| err.raw_os_error().unwrap_or(-1))
     }
     fn close_restricted(&mut self, fd: OwnedFd) {
@@ -99,6 +114,17 @@ fn raise_correct_error(devices_added: u8) -> Result<Libinput, std::io::Error> {
     //       warrants an issue being opened on GitHub.
 
 
+    // in a Flatpak or container, there's usually no /dev/input to have
+    // permission for in the first place, so none of the below applies
+    if devices_added == 0 && log_if_sandboxed() {
+        return Err(
+            Error::new(ErrorKind::NotFound,
+                "no input devices available inside this sandbox or container"
+            )
+        );
+    }
+
+
     // define condition 1b
 
     let you = match get_user_by_uid(get_current_uid()) {
@@ -140,6 +166,16 @@ fn raise_correct_error(devices_added: u8) -> Result<Libinput, std::io::Error> {
         .any(|group| group.name() == "input");
         
 
+    // Being in the group and still having no access at all points to
+    // SELinux or AppArmor, where the advice below would only mislead
+    if devices_added == 0 && in_input_group && log_if_input_denied() {
+        return Err(
+            Error::new(ErrorKind::PermissionDenied,
+                "access to input devices denied by SELinux/AppArmor"
+            )
+        );
+    }
+
     if devices_added == 0 || !in_input_group {
         error!("This program does not have permission to access \
             /dev/input to read trackpad events, most likely because you are \
@@ -178,10 +214,151 @@ fn raise_correct_error(devices_added: u8) -> Result<Libinput, std::io::Error> {
 }
 
 
+/// Whether `text` matches `pattern`, where `*` stands for any run of
+/// characters (even none), and `?` for any one character.
+fn matches_glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches_glob_chars(&pattern, &text)
+}
+
+fn matches_glob_chars(pattern: &[char], text: &[char]) -> bool {
+    match (pattern.split_first(), text.split_first()) {
+        (None, _) => text.is_empty(),
+        (Some(('*', rest)), _) => (0..=text.len()).any(|i| matches_glob_chars(rest, &text[i..])),
+        (Some((wanted, rest)), Some((c, text_rest))) => 
+            (*wanted == '?' || wanted == c) && matches_glob_chars(rest, text_rest),
+        (Some(_), None) => false
+    }
+}
+
+
+/// Whether the device with `name` and `sysname` (like `event5`) is one
+/// the user turned off with `disabledDevices`, by either of them.
+fn is_disabled(name: &str, sysname: &str, disabled: &[String]) -> bool {
+    disabled.iter().any(|pattern| matches_glob(pattern, name) || matches_glob(pattern, sysname))
+}
+
+
+/// What `find_real_trackpads()` makes of an input device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceVerdict {
+    Trackpad,           // gestures are read from it
+    Disabled,           // would be, but it's in disabledDevices
+    VirtualTrackpad,    // this program's own
+    NoGestures          // anything else, like a keyboard or mouse
+}
+
+fn verdict_for(device: &input::Device, disabled: &[String]) -> DeviceVerdict {
+    // The virtual trackpad only has "pointer" capability, so the
+    // capability check alone would already leave it out. But we
+    // don't want to lean on that, so it's excluded by identity too.
+    if is_virtual_trackpad(device.name(), device.id_vendor(), device.id_product()) {
+        DeviceVerdict::VirtualTrackpad
+    } else if !(device.has_capability(Pointer) && device.has_capability(Gesture)) {
+        DeviceVerdict::NoGestures
+    } else if is_disabled(device.name(), device.sysname(), disabled) {
+        DeviceVerdict::Disabled
+    } else {
+        DeviceVerdict::Trackpad
+    }
+}
+
+
+/// An input device, as `list-devices` shows it.
+#[derive(Debug, Clone)]
+pub struct DeviceListing {
+    pub name: String,
+    pub sysname: String,                    // like `event5`
+    pub id: String,                         // vendor:product, in hex
+    pub capabilities: Vec<&'static str>,
+    pub verdict: DeviceVerdict
+}
+
+const CAPABILITY_NAMES: [(DeviceCapability, &str); 7] = [
+    (DeviceCapability::Keyboard, "keyboard"),
+    (DeviceCapability::Pointer, "pointer"),
+    (DeviceCapability::Touch, "touch"),
+    (DeviceCapability::TabletTool, "tablet tool"),
+    (DeviceCapability::TabletPad, "tablet pad"),
+    (DeviceCapability::Gesture, "gesture"),
+    (DeviceCapability::Switch, "switch")
+];
+
+
+/// Every input device libinput can see, with what `find_real_trackpads()`
+/// would make of it, given `disabled` (`disabledDevices`).
+pub fn list_devices(disabled: &[String]) -> Result<Vec<DeviceListing>, std::io::Error> {
+
+    let mut all_inputs: Libinput = Libinput::new_with_udev(Interface);
+    all_inputs.udev_assign_seat("seat0")
+        .expect("Failed to assign udev seat - this should never fail as it returns unit type");
+
+    let devices: Vec<DeviceListing> = all_inputs.map(|event| {
+        let device = event.device();
+        DeviceListing {
+            name: device.name().to_string(),
+            sysname: device.sysname().to_string(),
+            id: format!("{:04x}:{:04x}", device.id_vendor(), device.id_product()),
+            capabilities: CAPABILITY_NAMES.iter()
+                .filter(|(capability, _)| device.has_capability(*capability))
+                .map(|(_, name)| *name)
+                .collect(),
+            verdict: verdict_for(&device, disabled)
+        }
+    }).collect();
+
+    // nothing at all is the same as for find_real_trackpads(): most
+    // likely permissions, which this explains
+    if devices.is_empty() {
+        raise_correct_error(0)?;
+    }
+    Ok(devices)
+}
+
+
+/// Has `find_real_trackpads()` open `paths` (like `/dev/input/event5`)
+/// from here on, instead of searching for trackpads, for `--device`.
+/// Only the first call counts.
+pub fn use_explicit_devices(paths: Vec<PathBuf>) {
+    let _ = EXPLICIT_DEVICES.set(paths);
+}
+
+
+/// Opens each of `paths` for gestures, whatever libinput makes of them,
+/// without going through udev.
+fn open_explicit_devices(paths: &[PathBuf]) -> Result<Libinput, std::io::Error> {
+
+    let mut trackpads = Libinput::new_from_path(Interface);
+
+    for path in paths {
+        let Some(device) = trackpads.path_add_device(&path.to_string_lossy()) else {
+            error!("Could not open {} (given with --device).", path.display());
+            let kind = if path.exists() { ErrorKind::PermissionDenied } else { ErrorKind::NotFound };
+            return Err(Error::new(kind, format!("could not open {}", path.display())));
+        };
+
+        info!("Reading gestures from \"{}\" ({}), as given with --device", device.name(), path.display());
+        if !device.has_capability(Gesture) {
+            warn!("libinput doesn't take \"{}\" for a touchpad (it has no gesture capability), \
+                so there may be no three-finger gestures to read from it", device.name());
+        }
+    }
+
+    Ok(trackpads)
+}
+
+
 /// Find all devices that function as trackpads, returning
 /// a `Libinput` struct that will receive events from all
-/// trackpads.
-pub fn find_real_trackpads() -> Result<Libinput, std::io::Error> {
+/// trackpads. Any matching one of `disabled` (`disabledDevices`)
+/// is left out. With `--device` (see `use_explicit_devices()`), 
+/// those devices are opened instead, and there's no search.
+pub fn find_real_trackpads(disabled: &[String]) -> Result<Libinput, std::io::Error> {
+
+    if let Some(paths) = EXPLICIT_DEVICES.get().filter(|paths| !paths.is_empty()) {
+        return open_explicit_devices(paths);
+    }
 
     let mut all_inputs: Libinput = Libinput::new_with_udev(Interface);
     // Note: udev_assign_seat will not throw an error on failure, it returns unit type
@@ -195,20 +372,83 @@ pub fn find_real_trackpads() -> Result<Libinput, std::io::Error> {
     
     // Libinput adds "touchpad" to the device you use for a trackpad.
     // This finds theat device among all active ones on your computer.
-    let all_trackpads: Vec<input::Device> = all_inputs.filter(
+    let all_trackpads: Vec<(input::Device, DeviceVerdict)> = all_inputs.filter_map(
         |event| {
             dev_added_count += 1;
-            event.device().has_capability(Pointer) 
-            && event.device().has_capability(Gesture)
-            // virtual trackpad only has "pointer" capability,
-            // so that will not be added here
+            let device = event.device();
+
+            match verdict_for(&device, disabled) {
+                DeviceVerdict::VirtualTrackpad => {
+                    debug!("Skipping our own virtual trackpad (udev path: /dev/input/{})", device.sysname());
+                    None
+                },
+                DeviceVerdict::NoGestures => None,
+                verdict => Some((device, verdict))
+            }
         }
-    ).map(|event| event.device())
-    .collect();
+    ).collect();
 
     if all_trackpads.is_empty() { 
         return raise_correct_error(dev_added_count); 
     }
 
-    bind_to_real_trackpads(all_trackpads)
+    let (excluded, trackpads): (Vec<_>, Vec<_>) = all_trackpads.into_iter()
+        .partition(|(_, verdict)| *verdict == DeviceVerdict::Disabled);
+    for (device, _) in &excluded {
+        info!("Leaving out the touchpad \"{}\" (udev path: /dev/input/{}), since it's in disabledDevices",
+            device.name(), device.sysname()
+        );
+    }
+    if trackpads.is_empty() {
+        error!("Every touchpad found is in disabledDevices, so there's nothing to read gestures from.");
+        return Err(Error::new(ErrorKind::NotFound, "every touchpad is in disabledDevices"));
+    }
+
+    bind_to_real_trackpads(trackpads.into_iter().map(|(device, _)| device).collect())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOUCHPAD: &str = "ELAN0670:00 04F3:3150 Touchpad";
+
+    #[test]
+    fn star_matches_any_run() {
+        assert!(matches_glob("ELAN*", TOUCHPAD));
+        assert!(matches_glob("*Touchpad", TOUCHPAD));
+        assert!(matches_glob("*", TOUCHPAD));
+        assert!(matches_glob("*", ""));
+        assert!(!matches_glob("*Tablet*", TOUCHPAD));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(matches_glob("event?", "event5"));
+        assert!(!matches_glob("event?", "event12"));
+        assert!(!matches_glob("event?", "event"));
+    }
+
+    #[test]
+    fn empty_pattern_only_matches_nothing() {
+        assert!(matches_glob("", ""));
+        assert!(!matches_glob("", TOUCHPAD));
+        assert!(!is_disabled(TOUCHPAD, "event5", &[String::new()]));
+    }
+
+    #[test]
+    fn literal_names_match_whole() {
+        assert!(matches_glob(TOUCHPAD, TOUCHPAD));
+        assert!(!matches_glob("ELAN0670:00", TOUCHPAD));
+        assert!(is_disabled(TOUCHPAD, "event5", &[TOUCHPAD.to_string()]));
+        assert!(!is_disabled(TOUCHPAD, "event5", &["Wacom Intuos Pro M Finger".to_string()]));
+    }
+
+    #[test]
+    fn sysnames_match_too() {
+        assert!(is_disabled(TOUCHPAD, "event5", &["event*".to_string()]));
+        assert!(is_disabled(TOUCHPAD, "event5", &["event5".to_string()]));
+        assert!(!is_disabled(TOUCHPAD, "event5", &["event6".to_string()]));
+    }
 }
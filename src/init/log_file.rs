@@ -0,0 +1,82 @@
+// The log file, as something that can be closed and opened again while
+// running (with the `reopen-log` command), for logrotate. Once logrotate
+// moves the file aside, the program would otherwise keep writing to the
+// moved file, since it still has it open.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Error, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError}
+};
+
+use tracing_subscriber::fmt::MakeWriter;
+
+
+// there's only ever one logger, so there's only ever one of these
+static LOG_FILE: OnceLock<ReopenableLog> = OnceLock::new();
+
+
+/// A log file that can be swapped for a fresh open of the same path.
+#[derive(Debug, Clone)]
+pub struct ReopenableLog {
+    path: PathBuf,
+    file: Arc<Mutex<File>>
+}
+
+impl ReopenableLog {
+
+    /// Opens `path` for appending, and makes it the one `reopen()` reopens.
+    pub fn open(path: &Path) -> Result<ReopenableLog, Error> {
+        let log = ReopenableLog {
+            path: path.to_path_buf(),
+            file: Arc::new(Mutex::new(OpenOptions::new().append(true).open(path)?))
+        };
+        let _ = LOG_FILE.set(log.clone());
+        Ok(log)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, File> {
+        // a panic mid-write leaves nothing worth protecting in a File
+        self.file.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+
+/// A handle for writing one log line.
+pub struct LogWriter<'a>(&'a ReopenableLog);
+
+impl Write for LogWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.lock().flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for ReopenableLog {
+    type Writer = LogWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogWriter(self)
+    }
+}
+
+
+/// Closes the log file and opens it again, creating it if it's been moved
+/// away. Returns the path reopened, or `None` if logging isn't to a file.
+pub fn reopen() -> Option<Result<PathBuf, Error>> {
+    let log = LOG_FILE.get()?;
+
+    let reopened = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&log.path)
+        .map(|fresh| {
+            *log.lock() = fresh;
+            log.path.clone()
+        });
+    Some(reopened)
+}
@@ -0,0 +1,38 @@
+// The log level, as something that can be changed while running (when the
+// config file is reloaded, or with `config set logLevel`), instead of being
+// fixed once the logger is set up. Where the log goes can't be changed
+// this way; that still takes a restart.
+
+use std::sync::OnceLock;
+
+use tracing::warn;
+use tracing_subscriber::{filter::LevelFilter, reload, Registry};
+
+use super::config::LogLevel;
+
+
+// there's only ever one logger, so there's only ever one of these
+static LEVEL: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+
+/// The filter for the logger to be built with, starting out at `level`,
+/// and changed with `set()` afterwards.
+pub fn reloadable(level: LogLevel) -> reload::Layer<LevelFilter, Registry> {
+    let (filter, handle) = reload::Layer::new(LevelFilter::from(level));
+    let _ = LEVEL.set(handle);
+    filter
+}
+
+
+/// Changes the log level, if the logger was built with `reloadable()`.
+pub fn set(level: LogLevel) {
+    let Some(handle) = LEVEL.get() else { return };
+    let level = LevelFilter::from(level);
+
+    if handle.clone_current() == Some(level) {
+        return;
+    }
+    if let Err(e) = handle.modify(|filter| *filter = level) {
+        warn!("Could not change the log level to {}: {}", level, e);
+    }
+}
@@ -0,0 +1,124 @@
+// Checks for mandatory access control (SELinux or AppArmor) being the
+// reason a device can't be opened. When that's the case, the usual
+// advice (join the `input` group, fix the udev rules, reboot) doesn't
+// help at all, since the user already has the regular Unix permissions
+// they need. Fedora users in particular tend to run into this.
+
+use std::{
+    fs::{read_dir, read_to_string, OpenOptions},
+    io::ErrorKind,
+    os::unix::fs::MetadataExt,
+    path::Path
+};
+
+use tracing::error;
+use users::{get_current_uid, group_access_list};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacSystem { SELinux, AppArmor }
+
+
+/// Which MAC system is enforcing policy on this process, if any.
+pub fn enforcing_mac() -> Option<MacSystem> {
+
+    let selinux_enforcing = read_to_string("/sys/fs/selinux/enforce")
+        .is_ok_and(|mode| mode.trim() == "1");
+    if selinux_enforcing {
+        return Some(MacSystem::SELinux);
+    }
+
+    // AppArmor being on isn't enough; this process has to be confined
+    // by a profile in enforce mode for it to be denying anything
+    let apparmor_enabled = read_to_string("/sys/module/apparmor/parameters/enabled")
+        .is_ok_and(|enabled| enabled.trim() == "Y");
+    let apparmor_confined = read_to_string("/proc/self/attr/current")
+        .is_ok_and(|profile| profile.contains("(enforce)"));
+    if apparmor_enabled && apparmor_confined {
+        return Some(MacSystem::AppArmor);
+    }
+
+    None
+}
+
+
+/// Whether the regular Unix permissions on `path` allow this
+/// process to both read and write it.
+pub fn unix_permissions_allow_rw(path: &Path) -> bool {
+
+    let Ok(metadata) = path.metadata() else { return false };
+    let mode = metadata.mode();
+
+    if metadata.uid() == get_current_uid() {
+        return mode & 0o600 == 0o600;
+    }
+
+    let in_file_group = group_access_list()
+        .is_ok_and(|groups| groups.iter().any(|g| g.gid() == metadata.gid()));
+    if in_file_group {
+        return mode & 0o060 == 0o060;
+    }
+
+    mode & 0o006 == 0o006
+}
+
+
+/// Hints for finding and fixing the denial, for each MAC system.
+pub fn audit_hint(mac: MacSystem) -> &'static str {
+    match mac {
+        MacSystem::SELinux => "SELinux is enforcing, and is likely what's blocking access. \
+            To confirm, look for recent denials with `sudo ausearch -m avc -ts recent` \
+            (or `sudo journalctl -t setroubleshoot`). If this program shows up there, \
+            `sudo ausearch -m avc -ts recent | audit2allow -M linux-3-finger-drag` will \
+            generate a policy module allowing it, which `sudo semodule -i linux-3-finger-drag.pp` \
+            installs. To check whether SELinux is the culprit before doing that, \
+            `sudo setenforce 0` turns enforcement off until the next boot.",
+        MacSystem::AppArmor => "This program is confined by an AppArmor profile in enforce \
+            mode, which is likely what's blocking access. To confirm, look for recent denials \
+            with `sudo journalctl -k | grep 'apparmor=\"DENIED\"'`. The profile needs to allow \
+            access to /dev/uinput and /dev/input/event*, or you can put it in complain mode \
+            with `sudo aa-complain <profile>` to check whether AppArmor is the culprit."
+    }
+}
+
+
+/// Logs the MAC hint if a permission error on `path` looks like it came
+/// from SELinux or AppArmor, i.e. the Unix permissions allow access, but
+/// one of them is enforcing. Returns whether it did.
+pub fn log_if_mac_denial(path: &Path) -> bool {
+
+    if !unix_permissions_allow_rw(path) {
+        return false;
+    }
+
+    match enforcing_mac() {
+        Some(mac) => {
+            error!("Access to {:?} was denied, even though its file permissions allow it. {}",
+                path, audit_hint(mac)
+            );
+            true
+        },
+        None => false
+    }
+}
+
+
+/// Tries opening each /dev/input/event* device, logging the MAC hint for the
+/// first one that's denied despite having the right file permissions.
+/// Returns whether there was one.
+pub fn log_if_input_denied() -> bool {
+
+    let Ok(entries) = read_dir("/dev/input") else { return false };
+
+    entries.flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("event"))
+        )
+        .any(|path| {
+            let open_result = OpenOptions::new().read(true).write(true).open(&path);
+            matches!(open_result, Err(ref e) if e.kind() == ErrorKind::PermissionDenied)
+                && log_if_mac_denial(&path)
+        })
+}
@@ -1,2 +1,19 @@
+pub mod build_info;
+pub mod cli;
 pub mod config;
+pub mod config_schema;
+pub mod daemonize;
+pub mod doctor;
+pub mod fd_passing;
+pub mod generate_config;
+pub mod gesture_definitions;
+pub mod import;
+pub mod instance_lock;
 pub mod libinput_init;
+pub mod log_file;
+pub mod log_level;
+pub mod mac_check;
+pub mod open_helper;
+pub mod run_mode;
+pub mod sandbox;
+pub mod setup;
@@ -0,0 +1,142 @@
+// An alternative to adding the user to the `input` group: a tiny helper
+// binary, installed setgid `input`, that opens input devices (and
+// /dev/uinput) on the main process's behalf and passes back the fds.
+// This way, only the helper ever has `input` group permissions, and it
+// only opens the handful of device paths this program actually needs.
+//
+// The main process falls back on the helper only when opening a device
+// itself fails for lack of permission, and only if the helper has been
+// installed next to the main executable.
+
+use std::{
+    fs::OpenOptions,
+    io::{Error, ErrorKind},
+    os::{
+        fd::{AsFd, FromRawFd, OwnedFd},
+        unix::{fs::{FileTypeExt, OpenOptionsExt}, net::UnixStream}
+    },
+    path::{Path, PathBuf},
+    process::{Command, Stdio}
+};
+
+use nix::libc::{EPERM, O_ACCMODE, O_CLOEXEC, O_NONBLOCK, O_RDONLY, O_RDWR, O_WRONLY};
+use tracing::debug;
+
+use super::fd_passing::{recv_fd, send_fd};
+
+
+pub const HELPER_NAME: &str = "linux-3-finger-drag-open-helper";
+
+
+/// The helper's path, if it's installed (alongside the main executable).
+pub fn helper_path() -> Option<PathBuf> {
+    let helper = std::env::current_exe()
+        .ok()?
+        .with_file_name(HELPER_NAME);
+
+    helper.exists().then_some(helper)
+}
+
+
+/// Whether an error from opening a device is one the helper might get around.
+pub fn is_permission_error(err: &Error) -> bool {
+    err.kind() == ErrorKind::PermissionDenied
+}
+
+
+/// Has the helper open `path` with `flags`, and returns the resulting fd.
+pub fn open_via_helper(path: &Path, flags: i32) -> Result<OwnedFd, Error> {
+
+    let helper = helper_path()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("{} is not installed", HELPER_NAME)))?;
+    debug!("Opening {:?} through {:?}", path, helper);
+
+    // the helper gets one end of this pair as its stdout,
+    // and sends the fd back through it
+    let (ours, theirs) = UnixStream::pair()?;
+    let mut child = Command::new(helper)
+        .arg(path)
+        .arg(flags.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(OwnedFd::from(theirs)))
+        .spawn()?;
+
+    let received = recv_fd(&ours);
+    let status = child.wait()?;
+
+    // on failure, the helper exits with the errno it got (if any)
+    received.map_err(|recv_err|
+        match status.code() {
+            Some(code) if code > 0 => Error::from_raw_os_error(code),
+            _ => recv_err
+        }
+    )
+}
+
+
+/// Whether the helper should agree to open `path`: only /dev/uinput and
+/// /dev/input/event* character devices, after resolving any symlinks.
+fn is_allowed_device(path: &Path) -> bool {
+
+    let Ok(real_path) = path.canonicalize() else { return false };
+    let Ok(metadata) = real_path.metadata() else { return false };
+    if !metadata.file_type().is_char_device() { return false }
+
+    if real_path == Path::new("/dev/uinput") { return true }
+
+    let is_event_node = real_path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix("event"))
+        .is_some_and(|num| !num.is_empty() && num.chars().all(|c| c.is_ascii_digit()));
+
+    real_path.parent() == Some(Path::new("/dev/input")) && is_event_node
+}
+
+
+/// The helper itself: opens the device named in `args`, and sends the fd
+/// out over stdout (which must be a Unix socket). Returns the exit code.
+pub fn run_helper(args: &[String]) -> i32 {
+
+    let [path, flags] = args else {
+        eprintln!("usage: {} <device path> <open flags>", HELPER_NAME);
+        return EPERM;
+    };
+
+    let path = Path::new(path);
+    if !is_allowed_device(path) {
+        eprintln!("{}: refusing to open {:?}", HELPER_NAME, path);
+        return EPERM;
+    }
+
+    // Only the access mode and a couple of harmless flags get through;
+    // anything else the caller asks for (like O_CREAT) is dropped
+    let flags: i32 = flags.parse().unwrap_or(O_RDONLY);
+    let access_mode = flags & O_ACCMODE;
+    let extra_flags = flags & (O_NONBLOCK | O_CLOEXEC);
+
+    let device = OpenOptions::new()
+        .read(access_mode == O_RDONLY || access_mode == O_RDWR)
+        .write(access_mode == O_WRONLY || access_mode == O_RDWR)
+        .custom_flags(extra_flags)
+        .open(path);
+
+    let device = match device {
+        Ok(device) => device,
+        Err(e) => {
+            eprintln!("{}: could not open {:?}: {}", HELPER_NAME, path, e);
+            return e.raw_os_error().unwrap_or(EPERM);
+        }
+    };
+
+    // SAFETY: stdout was set up by the main process as our end of
+    // the socket pair, and nothing else here uses it
+    let socket = unsafe { UnixStream::from_raw_fd(1) };
+
+    match send_fd(&socket, device.as_fd()) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}: could not send the fd for {:?}: {}", HELPER_NAME, path, e);
+            e.raw_os_error().unwrap_or(EPERM)
+        }
+    }
+}
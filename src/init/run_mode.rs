@@ -0,0 +1,117 @@
+// `--foreground` and `--service`: who's reading the output. In the
+// foreground, that's someone at a terminal, so the log is in color (when
+// it's going to one), and the lines from before the logger's up (the
+// "PRE-LOG" ones, about loading the config) are printed as always. As a
+// service, it's journald, which has timestamps of its own and no use for
+// color codes, so those are left out, as are the pre-log lines, apart
+// from warnings and errors. Those go to stderr with the priority prefix
+// journald reads, so `journalctl` highlights them. Startup is reported
+// once as a line of JSON, and to systemd itself (for `Type=notify`).
+//
+// Without either flag, it's a service if systemd has the output going to
+// the journal, which it says with `$JOURNAL_STREAM`.
+
+use std::{
+    fmt::Display,
+    os::{linux::net::SocketAddrExt, unix::net::{SocketAddr, UnixDatagram}},
+    sync::OnceLock
+};
+
+
+static RUN_MODE: OnceLock<RunMode> = OnceLock::new();
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunMode {
+    Foreground,
+    Service
+}
+
+
+/// Sets the mode, for `--foreground` or `--service`. Only the first call
+/// counts, and it has to come before anything's printed.
+pub fn set(mode: RunMode) {
+    let _ = RUN_MODE.set(mode);
+}
+
+
+pub fn current() -> RunMode {
+    *RUN_MODE.get_or_init(||
+        if std::env::var_os("JOURNAL_STREAM").is_some() { RunMode::Service } else { RunMode::Foreground }
+    )
+}
+
+
+pub fn is_service() -> bool {
+    current() == RunMode::Service
+}
+
+
+/// Prints a line from before the logger's up, in the foreground.
+pub fn pre_info(message: impl Display) {
+    if !is_service() {
+        println!("[PRE-LOG: INFO]: {message}");
+    }
+}
+
+
+/// Prints a warning from before the logger's up.
+pub fn pre_warn(message: impl Display) {
+    match current() {
+        RunMode::Foreground => println!("[PRE-LOG: WARNING]: {message}"),
+        // sd-daemon's prefix for LOG_WARNING
+        RunMode::Service => eprintln!("<4>{message}")
+    }
+}
+
+
+/// Prints an error from before the logger's up.
+pub fn pre_error(message: impl Display) {
+    match current() {
+        RunMode::Foreground => eprintln!("[PRE-LOG: ERROR]: {message}"),
+        // and for LOG_ERR
+        RunMode::Service => eprintln!("<3>{message}")
+    }
+}
+
+
+/// Says the daemon is up and reading trackpads: to systemd, if it's
+/// listening, and as a service, as a line of JSON, for scripts watching
+/// the journal.
+pub fn report_started(dry_run: bool) {
+    notify_systemd("READY=1\nSTATUS=Dragging");
+    if is_service() {
+        println!("{}", serde_json::json!({
+            "event": "started",
+            "version": env!("CARGO_PKG_VERSION"),
+            "pid": std::process::id(),
+            "dryRun": dry_run
+        }));
+    }
+}
+
+
+/// Says the daemon is on its way out, likewise.
+pub fn report_stopping() {
+    notify_systemd("STOPPING=1");
+    if is_service() {
+        println!("{}", serde_json::json!({ "event": "stopping", "pid": std::process::id() }));
+    }
+}
+
+
+/// Sends `state` to systemd's notify socket, if there is one. Nothing
+/// depends on it getting there, so failing is fine.
+fn notify_systemd(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else { return };
+    let Ok(socket) = UnixDatagram::unbound() else { return };
+
+    // an `@` means a socket in the abstract namespace
+    let address = match path.as_encoded_bytes().strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name),
+        None => SocketAddr::from_pathname(&path)
+    };
+    if let Ok(address) = address {
+        let _ = socket.send_to_addr(state.as_bytes(), &address);
+    }
+}
@@ -0,0 +1,95 @@
+// Checks for running inside a Flatpak, a Snap, or a container, where
+// there's usually no /dev/input, /dev/uinput, or udev to be had at all.
+// The usual advice (join the `input` group, fix the udev rules, reboot)
+// does nothing there, so when devices can't be opened from inside one of
+// these, the user is told what does work instead.
+//
+// There's no portal (or libei) backend to fall back on yet, since no
+// portal offers raw trackpad gestures, so the way out is always the host.
+
+use std::{env, fs::read_to_string, path::Path};
+
+use tracing::error;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sandbox {
+    Flatpak,
+    Snap,
+    Container(String)   // what kind, as best as can be told
+}
+
+
+/// Which sandbox this process is running in, if any.
+pub fn detect_sandbox() -> Option<Sandbox> {
+
+    if Path::new("/.flatpak-info").exists() {
+        return Some(Sandbox::Flatpak);
+    }
+    if env::var_os("SNAP_NAME").is_some() {
+        return Some(Sandbox::Snap);
+    }
+
+    // most specific first, since toolbox and distrobox are podman underneath
+    let kind = if Path::new("/run/.toolboxenv").exists() {
+        Some("toolbox".to_string())
+    } else if env::var_os("DISTROBOX_ENTER_PATH").is_some() {
+        Some("distrobox".to_string())
+    } else if Path::new("/run/.containerenv").exists() {
+        Some("podman".to_string())
+    } else if Path::new("/.dockerenv").exists() {
+        Some("docker".to_string())
+    } else {
+        // set by systemd-nspawn, LXC, and others, for PID 1 at least
+        env::var("container").ok()
+            .or_else(|| read_to_string("/run/systemd/container").ok())
+            .map(|kind| kind.trim().to_string())
+            .filter(|kind| !kind.is_empty())
+    };
+
+    kind.map(Sandbox::Container)
+}
+
+
+/// What to do about not having devices in `sandbox`.
+pub fn sandbox_hint(sandbox: &Sandbox) -> String {
+    match sandbox {
+        Sandbox::Flatpak => "This is running inside a Flatpak sandbox, which has no access to \
+            /dev/input or /dev/uinput (and there's no portal that offers trackpad gestures). \
+            Install linux-3-finger-drag on the host instead (see the README). If it's only \
+            being started from a Flatpak'd app, like a terminal or editor, start it on the \
+            host with `flatpak-spawn --host linux-3-finger-drag`.".to_string(),
+
+        Sandbox::Snap => "This is running inside a Snap, whose confinement blocks access to \
+            /dev/input and /dev/uinput. Install linux-3-finger-drag on the host instead \
+            (see the README).".to_string(),
+
+        Sandbox::Container(kind) if kind == "toolbox" || kind == "distrobox" => format!(
+            "This is running inside a {kind} container, which doesn't get the host's input \
+            devices. Run linux-3-finger-drag on the host instead, e.g. with \
+            `flatpak-spawn --host linux-3-finger-drag` or `distrobox-host-exec \
+            linux-3-finger-drag`, or install it there (see the README)."
+        ),
+
+        Sandbox::Container(kind) => format!(
+            "This is running inside a container ({kind}), which doesn't have the host's input \
+            devices by default. Either run linux-3-finger-drag on the host, or give the \
+            container the trackpad, /dev/uinput, and udev's database, e.g. for Docker or \
+            Podman: `--device /dev/uinput --device /dev/input -v /run/udev:/run/udev:ro`."
+        )
+    }
+}
+
+
+/// Logs what to do instead if this is running in a sandbox. Meant for when
+/// devices couldn't be opened, in place of the usual advice. Returns
+/// whether it did.
+pub fn log_if_sandboxed() -> bool {
+    match detect_sandbox() {
+        Some(sandbox) => {
+            error!("Input devices could not be opened. {}", sandbox_hint(&sandbox));
+            true
+        },
+        None => false
+    }
+}
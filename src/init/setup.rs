@@ -0,0 +1,249 @@
+// The pieces of the `setup` wizard: checks for each thing the program
+// needs to work (access to /dev/uinput and the trackpad, a config file,
+// a way to start on login), and fixes for each one, run with the user's
+// go-ahead. The fixes do what `install.sh` (or the manual install steps
+// in the README) would, minus building and copying the binary, so they
+// work from an already installed program too.
+//
+// Anything that needs root goes through `sudo`, so the wizard itself
+// runs as the user, which is who the config and the systemd unit are for.
+
+use std::{
+    fs::{read_to_string, OpenOptions},
+    io::{Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio}
+};
+
+use users::{get_current_uid, get_current_username, get_user_by_name, get_user_groups};
+
+use super::config::{self, Configuration};
+
+
+const UDEV_RULE: &str = include_str!("../../60-uinput.rules");
+pub const UDEV_RULE_PATH: &str = "/etc/udev/rules.d/60-uinput.rules";
+const MODULES_LOAD_PATH: &str = "/etc/modules-load.d/uinput.conf";
+
+const SERVICE_UNIT: &str = include_str!("../../three-finger-drag.service");
+const SERVICE_NAME: &str = "three-finger-drag.service";
+const INSTALLED_PATH: &str = "/usr/bin/linux-3-finger-drag";
+
+
+#[derive(Debug)]
+pub enum StepOutcome {
+    Passed,
+    Fixed(String),
+    Skipped(String),
+    Failed(String)
+}
+
+
+/// What happened at each step, to be summed up at the end.
+#[derive(Debug, Default)]
+pub struct SetupReport {
+    steps: Vec<(&'static str, StepOutcome)>
+}
+
+impl SetupReport {
+
+    pub fn record(&mut self, step: &'static str, outcome: StepOutcome) {
+        self.steps.push((step, outcome));
+    }
+
+    pub fn outcome(&self, step: &str) -> Option<&StepOutcome> {
+        self.steps.iter()
+            .find(|(name, _)| *name == step)
+            .map(|(_, outcome)| outcome)
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.steps.iter().any(|(_, outcome)| matches!(outcome, StepOutcome::Failed(_)))
+    }
+
+    pub fn print(&self) {
+        println!("\nSummary:");
+        for (step, outcome) in &self.steps {
+            match outcome {
+                StepOutcome::Passed         => println!("  [ ok ]  {step}"),
+                StepOutcome::Fixed(note)    => println!("  [fixed] {step}: {note}"),
+                StepOutcome::Skipped(note)  => println!("  [skip]  {step}: {note}"),
+                StepOutcome::Failed(note)   => println!("  [FAIL]  {step}: {note}")
+            }
+        }
+    }
+}
+
+
+/// Asks a yes/no question on the terminal. Just pressing enter means yes.
+pub fn confirm(question: &str) -> Result<bool, Error> {
+    print!("{question} [Y/n] ");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+
+    Ok(answer.is_empty() || answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
+
+pub fn is_root() -> bool {
+    get_current_uid() == 0
+}
+
+
+/// The user this is being run for: the one who ran `sudo`, if it's run
+/// that way. `None` for root itself, which needs no group to be added to.
+pub fn invoking_user() -> Option<String> {
+    if !is_root() {
+        return get_current_username().map(|user| user.to_string_lossy().into_owned());
+    }
+    std::env::var("SUDO_USER").ok().filter(|user| user != "root")
+}
+
+
+/// Whether `user` is in the `input` group, going by the group database
+/// (rather than their current login, which only catches up next time).
+pub fn user_in_input_group(user: &str) -> bool {
+    get_user_by_name(user)
+        .and_then(|user| get_user_groups(user.name(), user.primary_group_id()))
+        .is_some_and(|groups| groups.iter().any(|group| group.name() == "input"))
+}
+
+
+/// Runs a command as root, through `sudo` (which may ask for a password
+/// on the terminal) unless this is root already, feeding it `input` on
+/// stdin if there is any.
+fn run_as_root(args: &[&str], input: Option<&str>) -> Result<(), Error> {
+
+    let mut command = if is_root() {
+        Command::new(args[0])
+    } else {
+        let mut sudo = Command::new("sudo");
+        sudo.arg(args[0]);
+        sudo
+    };
+    let mut child = command
+        .args(&args[1..])
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::inherit() })
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(format!("`{}` failed ({status})", args.join(" "))))
+    }
+}
+
+
+/// Whether this process can open /dev/uinput to create the virtual
+/// trackpad, either itself or through the setgid helper.
+pub fn uinput_is_writable() -> bool {
+    OpenOptions::new().read(true).write(true).open("/dev/uinput").is_ok()
+        || super::open_helper::open_via_helper(
+            Path::new("/dev/uinput"), nix::libc::O_RDWR | nix::libc::O_NONBLOCK
+        ).is_ok()
+}
+
+
+pub fn udev_rule_installed() -> bool {
+    read_to_string(UDEV_RULE_PATH).is_ok_and(|rule| rule.trim() == UDEV_RULE.trim())
+}
+
+
+/// Installs the udev rule giving the `input` group (and whoever is
+/// logged in at the seat) access to /dev/uinput, makes sure the uinput
+/// module loads on boot, and applies the rule right away.
+pub fn install_udev_rule() -> Result<(), Error> {
+    run_as_root(&["tee", UDEV_RULE_PATH], Some(UDEV_RULE))?;
+    run_as_root(&["tee", MODULES_LOAD_PATH], Some("uinput\n"))?;
+    run_as_root(&["modprobe", "uinput"], None)?;
+    run_as_root(&["udevadm", "control", "--reload-rules"], None)?;
+    run_as_root(&["udevadm", "trigger", "/dev/uinput"], None)
+}
+
+
+/// Adds the user this is run for (see `invoking_user()`) to the `input`
+/// group, for reading the trackpad. This only takes effect from their
+/// next login.
+pub fn add_to_input_group() -> Result<(), Error> {
+    let user = invoking_user()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "the current user could not be found"))?;
+
+    run_as_root(&["gpasswd", "--add", &user, "input"], None)
+}
+
+
+/// Checks the config file, returning where it is and whether it exists.
+/// A file that exists but doesn't load is an error.
+pub fn check_config_file() -> Result<(PathBuf, bool), Error> {
+    let path = config::get_config_file_path()?;
+    if !path.exists() {
+        return Ok((path, false));
+    }
+
+    config::parse_config_file()?;
+    Ok((path, true))
+}
+
+
+/// Writes out a config file with every field set to its default, so
+/// there's something to edit.
+pub fn write_default_config(path: &Path) -> Result<(), Error> {
+    if let Some(config_folder) = path.parent() {
+        std::fs::create_dir_all(config_folder)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&Configuration::default())? + "\n")
+}
+
+
+pub fn has_systemd() -> bool {
+    Path::new("/run/systemd/system").exists()
+}
+
+
+pub fn service_unit_path() -> Result<PathBuf, Error> {
+    let config_folder = config::get_config_file_path()?
+        .parent()
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no config folder to put the unit in"))?;
+
+    Ok(config_folder.join("systemd/user").join(SERVICE_NAME))
+}
+
+
+/// Writes the systemd user unit and enables it, starting the program
+/// now and on every login. The unit normally points at /usr/bin, so if
+/// this copy of the program lives somewhere else (say, ~/.cargo/bin),
+/// the unit is pointed there instead.
+pub fn install_user_service() -> Result<(), Error> {
+
+    let unit_path = service_unit_path()?;
+    let exe = std::env::current_exe()?;
+    let unit = SERVICE_UNIT.replace(INSTALLED_PATH, &exe.to_string_lossy());
+
+    if let Some(unit_folder) = unit_path.parent() {
+        std::fs::create_dir_all(unit_folder)?;
+    }
+    std::fs::write(&unit_path, unit)?;
+
+    run_systemctl(&["daemon-reload"])?;
+    run_systemctl(&["enable", "--now", SERVICE_NAME])
+}
+
+
+fn run_systemctl(args: &[&str]) -> Result<(), Error> {
+    let status = Command::new("systemctl").arg("--user").args(args).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(format!("`systemctl --user {}` failed ({status})", args.join(" "))))
+    }
+}
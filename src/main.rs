@@ -1,43 +1,195 @@
 use std::{
+    io::IsTerminal,
+    path::PathBuf,
     sync::{
         Arc, atomic::{AtomicBool, Ordering}
     }, 
-    time::Duration,
-    os::unix::io::AsRawFd
+    time::{Duration, Instant},
+    os::unix::{io::AsRawFd, net::UnixListener as StdUnixListener}
 };
 use tokio::{
-    sync::mpsc::{self, Receiver},
-    io::unix::AsyncFd
+    sync::{mpsc::{self, Receiver}, watch},
+    io::unix::AsyncFd,
+    task::JoinHandle
 };
+use clap::ArgMatches;
+use input::event::{DeviceEvent, Event, EventTrait};
 use signal_hook::{self, consts::{SIGINT, SIGTERM}, flag};
-use tracing::{debug, error, info, trace};
-use tracing_subscriber::fmt::time::ChronoLocal;
+use tracing::{debug, error, info, trace, warn};
+use tracing_subscriber::{fmt::{self, time::ChronoLocal}, layer::SubscriberExt, util::SubscriberInitExt};
 
 use linux_3_finger_drag::{
-    init::{config, libinput_init},
+    init::{build_info, cli, config, config_schema, daemonize, doctor, generate_config, gesture_definitions, import, instance_lock, libinput_init, log_file, log_level, run_mode::{self, RunMode}, setup::{self, SetupReport, StepOutcome}},
     runtime::{
+        bench,
+        bench_backends::{self, MotionWatcher},
+        capabilities::Capabilities,
+        config_reload::{self, ConfigSource},
+        dbus_properties,
+        dbus_signals,
         event_handler::{ControlSignal, GestureTranslator, GtError}, 
+        desktop_speed,
+        focused_window,
+        hyprland,
+        ipc::{self, IpcMessage, IpcRequest, IpcResponse},
+        output_scale,
+        pointer_grab,
+        reexec,
+        replay,
+        self_test,
+        sessions,
+        stats::Stats,
+        status::{self, Status},
+        supervisor::{self, Backoff, Failure, LoopHealth},
+        tap_suppression,
+        calibrate,
+        gesture_reader::GestureReader,
+        simulate,
+        trace,
         virtual_trackpad
     }
 };
 
+// How long `test-gesture` gives the user to put the pointer somewhere
+// safe before it starts dragging
+const TEST_GESTURE_WAIT: Duration = Duration::from_secs(3);
+
+// How long shutdown may spend waiting on the drag end timer thread
+// before giving up on it and releasing the button regardless. This
+// needs to stay well under systemd's default stop timeout (90s), and
+// under the IPC client's timeout, so the `stop` command gets its reply.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+
+fn main() -> Result<(), GtError> {
+
+    let matches = cli::command().get_matches();
+    // this goes for every command, so it's set before any of them load the config
+    if let Some(path) = matches.get_one::<PathBuf>("config") {
+        config::use_config_file(path);
+    }
+    if matches.get_flag("version") {
+        println!("{}", build_info::version_text());
+        return Ok(());
+    }
+    if matches.get_flag("self-test") {
+        return run_self_test();
+    }
+    if matches.get_flag("check-config") {
+        return run_check_config();
+    }
+    if matches.get_flag("print-config-schema") {
+        return run_print_config_schema();
+    }
+
+    // the daemon's flags are read (and checked) up front, while a mistake
+    // in them can still be printed to the terminal
+    let daemon = match matches.subcommand() {
+        None => Some(daemon_flags(&matches)),
+        Some(("run", args)) => Some(daemon_flags(args)),
+        Some(_) => None
+    };
+
+    // taken before going into the background, so a second instance is
+    // turned away where it can still say so. A dry run doesn't drag, so
+    // it can run alongside
+    let _instance_lock = match daemon.as_ref().filter(|flags| !flags.dry_run) {
+        Some(flags) => match instance_lock::acquire(flags.system) {
+            Ok(lock) => Some(lock),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                run_mode::pre_error(e);
+                std::process::exit(instance_lock::EXIT_ALREADY_RUNNING);
+            },
+            Err(e) => {
+                run_mode::pre_warn(format!("Could not check for another running instance: {e}"));
+                None
+            }
+        },
+        None => None
+    };
+
+    // systemd's control socket, if it handed one over, is taken before
+    // the runtime starts too, since taking it clears the environment
+    // variables it came with
+    let activated_socket = daemon.as_ref().and_then(|_| ipc::take_activated_socket());
+
+    // going into the background has to happen before the runtime starts
+    // any threads. The pidfile's removed once the daemon is done, as this
+    // goes out of scope
+    let _pidfile = match daemon.as_ref().and_then(|flags| flags.pidfile.as_deref()) {
+        Some(pidfile) => Some(daemonize::daemonize(pidfile)?),
+        None => None
+    };
+
+    run(matches, daemon, activated_socket)
+}
+
 
 #[tokio::main]
-async fn main() -> Result<(), GtError> {
+async fn run(
+    matches: ArgMatches,
+    daemon: Option<DaemonFlags>,
+    activated_socket: Option<Result<StdUnixListener, std::io::Error>>
+) -> Result<(), GtError> {
 
-    let configs = config::init_cfg();
+    // with no command, or `run`, this is the daemon itself. `stop`, 
+    // `reexec`, `config` and the like talk to an already-running one 
+    // (`stop` is what the systemd unit uses for ExecStop=, and `reexec`
+    // for ExecReload=), and the rest run on their own instead.
+    match matches.subcommand() {
+        None | Some(("run", _)) => run_daemon(daemon.unwrap_or_default(), activated_socket).await,
+        Some(("check-config", _)) => run_check_config(),
+        Some(("version", _)) => {
+            println!("{}", build_info::version_text());
+            Ok(())
+        },
+        Some(("list-devices", _)) => run_list_devices(),
+        Some(("status", _)) => run_status(),
+        Some(("stats", args)) => run_stats(args),
+        Some(("simulate-drag", args)) => run_simulation(args).await,
+        Some(("test-gesture", args)) => run_test_gesture(args).await,
+        Some(("record", args)) => run_record(args).await,
+        Some(("replay", args)) => run_replay(args).await,
+        Some(("bench", args)) => run_bench(args).await,
+        Some(("bench-backends", _)) => run_bench_backends().await,
+        Some(("calibrate", args)) => run_calibrate(args).await,
+        Some(("doctor", _)) => run_doctor(),
+        Some(("install-udev-rule", _)) => run_install_udev_rule(),
+        Some(("setup", _)) => run_setup().await,
+        Some(("import", args)) => run_import(args),
+        Some(("generate-config", args)) => run_generate_config(args),
+        Some(("self-test", _)) => run_self_test(),
+        Some(("print-config-schema", _)) => run_print_config_schema(),
+        Some((command, args)) => run_client_command(command, args)
+    }
+}
 
-    match config::init_file_logger(configs.clone()) {
-        Some(logger) => logger.init(), 
-        None => {
-            tracing_subscriber::fmt()
-                .with_writer(std::io::stdout)
-                .with_max_level(configs.log_level)
-                .with_timer(ChronoLocal::rfc_3339())
-                .init();
-        }
+
+/// Runs the daemon, until it's stopped or can't go on. `activated_socket`
+/// is the control socket systemd handed over, if it did.
+async fn run_daemon(flags: DaemonFlags, activated_socket: Option<Result<StdUnixListener, std::io::Error>>) -> Result<(), GtError> {
+
+    status::mark_started();
+
+    let configs = if flags.system {
+        config::init_system_cfg(flags.preset)
+    } else {
+        config::init_cfg(flags.preset)
     };
-    println!("[PRE-LOG: INFO]: Logger initialized!"); 
+    if flags.pidfile.is_some() {
+        daemonize::redirect_output(&configs.log_file);
+    }
+    init_logger(&configs);
+
+    // named gestures live next to whichever config file is in use
+    let config_path = if flags.system {
+        Ok(config::system_config_path())
+    } else {
+        config::find_config_file().or_else(|_| config::get_config_file_path())
+    };
+    let gestures_dir = config_path
+        .map(|path| gesture_definitions::gestures_dir(&path))
+        .unwrap_or_default();
 
     // handling SIGINT and SIGTERM
     let should_exit = Arc::new(AtomicBool::new(false));
@@ -47,25 +199,99 @@ async fn main() -> Result<(), GtError> {
         .expect("Failed to register SIGINT handler");
 
     let (sender, recvr) = mpsc::channel::<ControlSignal>(3);
-    let vtrackpad = virtual_trackpad::start_handler()?;
+    let (ipc_sender, ipc_recvr) = mpsc::channel::<IpcMessage>(3);
+    let (config_sender, config_recvr) = mpsc::channel::<config::Configuration>(3);
+
+    // saving the config file (or SIGHUP) reloads it, through the same
+    // channel as a session change's config
+    if let Err(e) = config_reload::reload_on_sighup(config_sender.clone(), flags.system, flags.preset) {
+        warn!("Could not register the SIGHUP handler, so the config can't be reloaded that way: {}", e);
+    }
+    if let Err(e) = config_reload::reload_on_change(config_sender.clone(), flags.system, flags.preset) {
+        warn!("Can't watch the config file for changes, so it won't be reloaded when saved \
+            (send a SIGHUP to reload it instead): {}", e);
+    }
+
+    // a system-wide instance pauses for (and takes its config from)
+    // whichever session is active
+    let session_pause = flags.system.then(|| {
+        let (pause_sender, pause_recvr) = watch::channel(true);
+        tokio::spawn(sessions::follow_active_session(pause_sender, config_sender, configs.clone(), flags.preset));
+        pause_recvr
+    });
+
+    // if this process was re-executed from a running one, keep using its
+    // virtual trackpad rather than creating another
+    let vtrackpad = if flags.dry_run {
+        virtual_trackpad::VirtualTrackpad::dry_run(configs.output_mode)
+    } else {
+        match reexec::take_handoff() {
+            Some(vtp) => vtp,
+            None => virtual_trackpad::start_handler_for(configs.output_mode)?
+        }
+    };
+
+    // Hyprland's own three-finger gestures get in the way, so they're 
+    // checked for (and maybe turned off) here, and put back on exit
+    let mut hyprland_changes = hyprland::ChangedOptions::default();
+    if hyprland::is_running() {
+        hyprland_changes = hyprland::resolve_gesture_conflicts(configs.hyprland_disable_conflicts);
+        if configs.hyprland_drag_events {
+            tokio::spawn(hyprland::forward_drag_events(vtrackpad.subscribe_button_events()));
+        }
+    }
+
+    // SIGUSR1 and SIGUSR2 go through the control socket's channel, so
+    // they're dealt with in the main loop like its requests are
+    if let Err(e) = ipc::forward_signals(ipc_sender.clone()) {
+        warn!("Could not register the SIGUSR1 and SIGUSR2 handlers, so they won't pause or dump the state: {}", e);
+    }
+
+    if configs.dbus_drag_events {
+        tokio::spawn(dbus_signals::forward_drag_events(vtrackpad.subscribe_button_events(), flags.system));
+    }
+    // a system-wide instance would need a bus policy to take the name, and
+    // a dry run shouldn't take it from the real one
+    if !flags.system && !flags.dry_run {
+        tokio::spawn(dbus_properties::serve_capabilities(ipc_sender.clone()));
+    }
+    // tapping is the session user's setting, which a system-wide instance
+    // (running as root) can't reach, and a dry run shouldn't touch
+    if !flags.system && !flags.dry_run {
+        tap_suppression::restore_stale_tapping();
+        if configs.suppress_tap_while_dragging {
+            tap_suppression::restore_tapping_on_panic();
+            tokio::spawn(tap_suppression::suppress_taps_while_dragging(vtrackpad.subscribe_button_events()));
+        }
+    } else if configs.suppress_tap_while_dragging {
+        warn!("suppressTapWhileDragging is ignored with --system or --dry-run, since it \
+            changes the session user's desktop settings.");
+    }
+
+    // the control socket is a convenience, so the program can run without it
+    match ipc::bind_listener(activated_socket) {
+        Ok(listener) => { tokio::spawn(ipc::listen(listener, ipc_sender)); },
+        Err(e) => warn!("Could not open the control socket ({}), so `stop` \
+            commands will not reach this instance.", e)
+    }
 
     info!("Searching for the trackpad on your device...");
 
     info!("end evdev search");
     // using a match case here instead of a `?` here so the program can destruct 
     // the virtual trackpad before it exits
-    let main_result = match libinput_init::find_real_trackpads() {
+    let main_result = match libinput_init::find_real_trackpads(&configs.disabled_devices) {
 
         Ok(real_trackpad) => {
 
-            let translator = GestureTranslator::new(
-                vtrackpad, 
-                configs,
-                sender
-            );
-            run_main_event_loop(
+            let mut translator = build_translator(vtrackpad, configs, sender, session_pause);
+            translator.use_named_gestures(gesture_definitions::load_gesture_definitions(&gestures_dir));
+            supervise_main_event_loop(
                 translator, 
                 recvr, 
+                ipc_recvr,
+                config_recvr,
+                ConfigSource { system: flags.system, preset_override: flags.preset },
                 &should_exit, 
                 real_trackpad
             ).await
@@ -76,52 +302,443 @@ async fn main() -> Result<(), GtError> {
     // the program arrives here if either a signal is received, 
     // or there was some issue during initialization
     info!("Cleaning up and exiting...");
+    ipc::remove_socket_file();
+    hyprland::restore_options(hyprland_changes);
+    tap_suppression::restore_tapping();
     
     // Cleanup: access vtrackpad through translator if available
-    if let Ok(mut translator) = main_result {
-        translator.vtp.mouse_up()?;      // just in case
-        translator.vtp.destruct()?;      // we don't need virtual devices cluttering the system
-        info!("Clean up successful.");
+    let (mut translator, ending_request) = main_result?;
+
+    if let Some(IpcMessage { request: IpcRequest::Reexec, reply }) = ending_request {
+        let _ = reply.send(IpcResponse::ok("re-executing"));
+        flush_reply().await;
+
+        // only returns if exec() failed, in which case we clean up and exit as normal
+        let (vtp, exec_err) = reexec::exec_with_handoff(translator.vtp);
+        error!("Could not re-execute: {}", exec_err);
+        translator.vtp = vtp;
+        return clean_up(translator);
+    }
+
+    run_mode::report_stopping();
+    let cleanup_result = clean_up(translator);
+
+    // let whoever sent the `stop` know how it went
+    if let Some(IpcMessage { reply, .. }) = ending_request {
+        let response = match &cleanup_result {
+            Ok(())  => IpcResponse::ok("stopped"),
+            Err(e) => IpcResponse::err(format!("stopped, but cleanup failed: {:?}", e))
+        };
+        let _ = reply.send(response);
+        flush_reply().await;
+    }
+
+    cleanup_result
+}
+
+
+fn get_config_value(cfg: &config::Configuration, key: Option<&str>) -> IpcResponse {
+
+    let value = match key {
+        Some(key) => cfg.get_value(key),
+        None => serde_json::to_value(cfg).ok()
+    };
+
+    match value {
+        Some(value) => IpcResponse::ok(value.to_string()),
+        None => IpcResponse::err(format!("no config option named '{}'", key.unwrap_or_default()))
+    }
+}
+
+
+/// Changes a single config value for the running translator, and 
+/// optionally saves it to the config file. Nothing changes at all
+/// unless the new value is valid.
+async fn set_config_value(
+    translator: &mut GestureTranslator, 
+    key: &str, 
+    value: serde_json::Value, 
+    persist: bool
+) -> IpcResponse {
+
+    let new_cfg = match translator.cfg.with_value(key, value.clone()) {
+        Ok(cfg) => cfg,
+        Err(e) => return IpcResponse::err(format!("{key} not changed: {e}"))
+    };
+
+    if persist {
+        if let Err(e) = config::persist_value(key, value.clone()) {
+            return IpcResponse::err(format!("{key} not changed, since it could not be saved: {e}"));
+        }
+    }
+
+    if let Err(e) = translator.apply_config(new_cfg).await {
+        return IpcResponse::err(format!("{key} could not be applied: {e:?}"));
+    }
+    info!("Config option {} set to {} over the control socket", key, value);
+
+    let mut message = format!("{key} = {value}");
+    if persist { message.push_str(" (saved)"); }
+    if !config::LIVE_OPTIONS.contains(&key) {
+        message.push_str("; this option only takes effect after a restart");
+    }
+    IpcResponse::ok(message)
+}
+
+
+/// Gives the control connection task a moment to write out a reply before
+/// the runtime (and the task along with it) is torn down, or the process
+/// image is replaced.
+async fn flush_reply() {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+}
+
+
+/// Logs to the config's log file, if it has one, or else to stdout, shaped
+/// for a terminal or for the journal (see `run_mode`).
+fn init_logger(configs: &config::Configuration) {
+    // the level is a layer of its own, so it can be changed while running
+    let logger = tracing_subscriber::registry().with(log_level::reloadable(configs.log_level));
+    let format = fmt::layer().with_timer(ChronoLocal::rfc_3339());
+
+    // color is only for a person at a terminal, and journald (see
+    // `run_mode`) stamps the lines itself
+    match config::init_file_logger(configs.clone()) {
+        Some(log_file) => logger.with(format.with_ansi(false).with_writer(log_file)).init(),
+        None if run_mode::is_service() => logger.with(fmt::layer().without_time().with_ansi(false).with_writer(std::io::stdout)).init(),
+        None => logger.with(format.with_ansi(std::io::stdout().is_terminal()).with_writer(std::io::stdout)).init()
+    };
+    run_mode::pre_info("Logger initialized!"); 
+}
+
+
+/// Sets up the translator, along with the background tasks that feed it
+/// (desktop speed, output scale, and the focused window). With
+/// `session_pause` (for `--system`), drags are paused by that instead,
+/// and nothing that needs to be in a session is watched.
+fn build_translator(
+    vtrackpad: virtual_trackpad::VirtualTrackpad,
+    configs: config::Configuration,
+    sender: mpsc::Sender<ControlSignal>,
+    session_pause: Option<watch::Receiver<bool>>
+) -> GestureTranslator {
+
+    if let Some(pause_recvr) = session_pause {
+        return GestureTranslator::new(
+            vtrackpad, 
+            configs, 
+            sender,
+            watch::channel(1.0).1,
+            watch::channel(1.0).1,
+            pause_recvr,
+            watch::channel(None).1
+        );
+    }
+
+    // if the desktop's speed isn't followed, the sender is dropped here,
+    // and the translator just keeps the neutral 1.0 multiplier
+    let (speed_sender, speed_recvr) = watch::channel(1.0);
+    if configs.sync_desktop_speed {
+        tokio::spawn(desktop_speed::watch_desktop_speed(speed_sender));
+    }
+
+    // same deal here
+    let (scale_sender, scale_recvr) = watch::channel(1.0);
+    if configs.scale_by_output {
+        tokio::spawn(output_scale::watch_output_scale(scale_sender, configs.output_scales.clone()));
+    }
+
+    // the focused window is only followed if something needs it, and
+    // drags are never paused if nothing is set up to pause them
+    let (focus_sender, focus_recvr) = watch::channel(None);
+    let (pause_sender, pause_recvr) = watch::channel(false);
+    let pauses_for_apps = configs.pause_when_fullscreen || !configs.pause_for_apps.is_empty();
+    if pauses_for_apps || !configs.app_profiles.is_empty() {
+        tokio::spawn(focused_window::watch_focused_window(focus_sender));
+    }
+    if pauses_for_apps {
+        tokio::spawn(pointer_grab::watch_pointer_grabs(
+            focus_recvr.clone(),
+            pause_sender, 
+            configs.pause_for_apps.clone(), 
+            configs.pause_when_fullscreen
+        ));
+    }
+
+    GestureTranslator::new(
+        vtrackpad, 
+        configs,
+        sender,
+        speed_recvr,
+        scale_recvr,
+        pause_recvr,
+        focus_recvr
+    )
+}
+
+
+/// Releases the mouse button and destroys the virtual trackpad. Both are
+/// attempted even if the first one fails, since a stuck button and a
+/// leftover virtual device are each bad enough on their own.
+fn clean_up(translator: GestureTranslator) -> Result<(), GtError> {
+
+    let mut vtp = translator.vtp;
+    let release_result = vtp.mouse_up();    // just in case
+    let destruct_result = vtp.destruct();   // we don't need virtual devices cluttering the system
+
+    release_result?;
+    destruct_result?;
+    info!("Clean up successful.");
+    Ok(())
+}
+
+
+/// Flags for starting the daemon (as opposed to running a command).
+#[derive(Debug, Default)]
+struct DaemonFlags {
+    preset: Option<config::Preset>,   // in place of the one in the config file
+    system: bool,                     // one system-wide instance, following the active session
+    dry_run: bool,                    // log drags instead of making them
+    pidfile: Option<PathBuf>          // with --daemon, for going into the background
+}
+
+
+/// Reads the daemon's flags from `matches`, passing the ones for config
+/// fields on to the config.
+fn daemon_flags(matches: &ArgMatches) -> DaemonFlags {
+
+    // checked now, so a typo doesn't quietly leave the whole config at its defaults
+    if let Err(e) = config::set_overrides(serde_json::Value::Object(cli::config_overrides(matches))) {
+        eprintln!("Invalid value given on the command line (or in an L3FD_ environment variable): {}", e);
+        std::process::exit(2);
+    }
+
+    if let Some(paths) = matches.get_many::<PathBuf>("device") {
+        libinput_init::use_explicit_devices(paths.cloned().collect());
+    }
+    if matches.get_flag("service") {
+        run_mode::set(RunMode::Service);
+    } else if matches.get_flag("foreground") {
+        run_mode::set(RunMode::Foreground);
+    }
+
+    let pidfile = matches.get_flag("daemon").then(|| match matches.get_one::<PathBuf>("pidfile") {
+        Some(path) => path.clone(),
+        None => daemonize::default_pidfile_path().unwrap_or_else(|e| {
+            eprintln!("Could not start in the background: {}", e);
+            std::process::exit(2);
+        })
+    });
+
+    DaemonFlags {
+        preset: matches.get_one::<config::Preset>("preset").copied(),
+        system: matches.get_flag("system"),
+        dry_run: matches.get_flag("dry-run"),
+        pidfile
+    }
+}
+
+
+/// `status`: asks the running instance how it's doing, and prints that,
+/// or that there isn't one (exiting with 3, like `systemctl status` does
+/// for a stopped service).
+fn run_status() -> Result<(), GtError> {
+
+    // the system-wide instance has a control socket of its own, elsewhere
+    if !instance_lock::is_running(false) {
+        println!("linux-3-finger-drag is not running.");
+        std::process::exit(3);
+    }
+
+    let response = ipc::send_request(&IpcRequest::Status)?;
+    if !response.ok {
+        eprintln!("{}", response.message);
+        std::process::exit(1);
+    }
+    let status: Status = serde_json::from_str(&response.message).map_err(std::io::Error::from)?;
+    println!("{}", status.describe());
+    Ok(())
+}
+
+
+/// `stats`: prints the running instance's counts, readably, or with
+/// `--json`, as they came.
+fn run_stats(args: &ArgMatches) -> Result<(), GtError> {
+
+    let response = ipc::send_request(&IpcRequest::Stats)?;
+    if !response.ok {
+        eprintln!("{}", response.message);
+        std::process::exit(1);
+    }
+    if args.get_flag("json") {
+        println!("{}", response.message);
+    } else {
+        let stats: Stats = serde_json::from_str(&response.message).map_err(std::io::Error::from)?;
+        println!("{}", stats.describe());
+    }
+    Ok(())
+}
+
+
+/// Handles the case where the program is run as a client to a running
+/// instance, rather than as the daemon itself.
+fn run_client_command(command: &str, args: &ArgMatches) -> Result<(), GtError> {
+
+    let request = match (command, args.subcommand()) {
+        ("stop", _)   => IpcRequest::Stop,
+        ("reexec", _) => IpcRequest::Reexec,
+        ("capabilities", _) => IpcRequest::Capabilities,
+        ("reload", _) => IpcRequest::Reload,
+        ("reopen-log", _) => IpcRequest::ReopenLog,
+        ("pause", _) => IpcRequest::Pause,
+        ("resume", _) => IpcRequest::Resume,
+        ("dump-state", _) => IpcRequest::DumpState,
+        ("pointer-lock", _) => IpcRequest::PointerLock { 
+            locked: match args.get_one::<String>("state").map(String::as_str) {
+                Some("on") => Some(true),
+                Some("off") => Some(false),
+                _ => None
+            }
+        },
+        // switching profiles is setting `activeProfile`, just without saving it
+        ("profile", _) => match args.get_one::<String>("name").map(String::as_str) {
+            None => IpcRequest::ConfigGet { key: Some("activeProfile".to_string()) },
+            Some(name) => IpcRequest::ConfigSet {
+                key: "activeProfile".to_string(),
+                value: match name { "none" => serde_json::Value::Null, name => serde_json::json!(name) },
+                persist: false
+            }
+        },
+        ("config", Some(("get", args))) => IpcRequest::ConfigGet { key: args.get_one::<String>("option").cloned() },
+        ("config", Some(("set", args))) => {
+            let value = args.get_one::<String>("value").map(String::as_str).unwrap_or_default();
+            IpcRequest::ConfigSet { 
+                key: args.get_one::<String>("option").cloned().unwrap_or_default(), 
+                // so things like `config set logLevel debug` work without extra quoting
+                value: serde_json::from_str(value)
+                    .unwrap_or_else(|_| serde_json::Value::String(value.to_string())), 
+                persist: args.get_flag("persist")
+            }
+        },
+        // every command is handled somewhere, so this is a command
+        // that was added to the CLI and forgotten about here
+        (command, _) => unreachable!("no handling for the command '{}'", command)
+    };
+
+    let response = ipc::send_request(&request)?;
+
+    if response.ok {
+        println!("{}", response.message);
         Ok(())
     } else {
-        main_result.map(|_| ())
+        eprintln!("{}", response.message);
+        std::process::exit(1);
     }
 }
 
 
-// This function is placed in `main.rs` since it's essentially a 
-// part of `main`, and I wanted to break it out so the `main` isn't
-// too sprawling
-async fn run_main_event_loop(
+/// How one run of the main loop ended: either the program was asked to
+/// stop (with the control request that asked, if it came that way), or
+/// something failed that the loop can be restarted after.
+enum LoopEnd {
+    Finished(Option<IpcMessage>),
+    Failed(Failure)
+}
+
+
+/// Runs the main loop, restarting it after any failure it can recover 
+/// from (see `supervisor`), until the program is asked to stop. Along 
+/// with the translator, this returns the control request that ended it 
+/// (`stop` or `reexec`), if that's how it ended.
+async fn supervise_main_event_loop(
     mut translator: GestureTranslator,
     recvr: Receiver<ControlSignal>,
+    mut ipc_recvr: Receiver<IpcMessage>,
+    mut config_recvr: Receiver<config::Configuration>,
+    config_source: ConfigSource,
     should_exit: &Arc<AtomicBool>,
-    real_trackpad: input::Libinput
-) -> Result<GestureTranslator, GtError> {
+    mut real_trackpad: input::Libinput
+) -> Result<(GestureTranslator, Option<IpcMessage>), GtError> {
 
-    // spawn 1 separate thread to handle mouse_up_delay timeouts
-    debug!("Creating new thread to manage drag end timer");
-    let mut vtp_clone = translator.vtp.clone();
-    let delay = translator.cfg.drag_end_delay;
+    let mut mouse_up_listener = spawn_drag_end_timer(&translator, recvr);
+    let mut backoff = Backoff::default();
+    let mut health = LoopHealth::default();
 
-    let fork_fn = async move {
-        vtp_clone.handle_mouse_up_timeout(delay, recvr)
-            .await
-            .map_err(GtError::from)
+    info!("linux-3-finger-drag started successfully!");
+    run_mode::report_started(translator.vtp.is_dry_run());
+
+    let ending_request = loop {
+        let end = run_main_event_loop(
+            &mut translator, 
+            &mut mouse_up_listener, 
+            &mut ipc_recvr, 
+            &mut config_recvr,
+            config_source,
+            should_exit, 
+            &mut real_trackpad,
+            &mut health
+        ).await;
+
+        let failure = match end {
+            LoopEnd::Finished(request) => break request,
+            LoopEnd::Failed(failure) => failure
+        };
+
+        // The timer is restarted either way: a crashed one has already
+        // stopped, and any other has its own copy of the virtual trackpad,
+        // which recovering might replace
+        if !matches!(failure, Failure::TimerCrashed(_)) {
+            if let Err(e) = stop_drag_end_timer(&mut translator, mouse_up_listener).await {
+                warn!("Could not stop the delay timer thread: {:?}", e);
+            }
+        }
+
+        let recovered = supervisor::recover(&failure, &mut translator, &mut real_trackpad, &mut backoff, should_exit).await;
+        health.recovered_from(&failure);
+
+        let recvr = translator.reconnect_timer();
+        mouse_up_listener = spawn_drag_end_timer(&translator, recvr);
+
+        if !recovered {
+            break None;
+        }
     };
 
-    let mouse_up_listener = tokio::spawn(fork_fn);
+    stop_drag_end_timer(&mut translator, mouse_up_listener).await?;
+    
+    // Return translator for cleanup
+    Ok((translator, ending_request))
+}
 
-    info!("linux-3-finger-drag started successfully!");
+
+// This function is placed in `main.rs` since it's essentially a 
+// part of `main`, and I wanted to break it out so the `main` isn't
+// too sprawling
+#[allow(clippy::too_many_arguments)]
+async fn run_main_event_loop(
+    translator: &mut GestureTranslator,
+    mouse_up_listener: &mut JoinHandle<Result<(), GtError>>,
+    ipc_recvr: &mut Receiver<IpcMessage>,
+    config_recvr: &mut Receiver<config::Configuration>,
+    config_source: ConfigSource,
+    should_exit: &Arc<AtomicBool>,
+    real_trackpad: &mut input::Libinput,
+    health: &mut LoopHealth
+) -> LoopEnd {
 
     // Wrap the libinput file descriptor for async event-driven polling
+    // (AsyncFd only wraps the FD, so real_trackpad is kept separate)
     let fd_raw = real_trackpad.as_raw_fd();
-    let async_fd = AsyncFd::new(fd_raw)
-        .expect("Failed to create AsyncFd for libinput file descriptor");
-    
-    // We need to move real_trackpad into a position where we can use it with the async_fd
-    // Since AsyncFd only wraps the FD, we keep real_trackpad separate
-    let mut real_trackpad = real_trackpad;
+    let async_fd = match AsyncFd::new(fd_raw) {
+        Ok(async_fd) => async_fd,
+        Err(e) => {
+            error!("Could not poll the trackpad: {}", e);
+            return LoopEnd::Failed(Failure::TrackpadLost);
+        }
+    };
+
+    // for `idleExitHours`
+    let mut last_activity = Instant::now();
 
     loop {
         tokio::select! {
@@ -131,44 +748,935 @@ async fn run_main_event_loop(
             Ok(mut guard) = async_fd.readable() => {
                 // Clear the ready state
                 guard.clear_ready();
-                
+
+                last_activity = Instant::now();
+                let batch_start = Instant::now();
+
                 // Process all available events
                 if let Err(e) = real_trackpad.dispatch() {
                     error!("A {} error occured in reading device buffer: {}", e.kind(), e);
+                    if let Some(failure) = health.dispatch_failed() {
+                        return LoopEnd::Failed(failure);
+                    }
                 }
 
-                for event in &mut real_trackpad {
+                let mut failure = None;
+                for event in &mut *real_trackpad {
                     trace!("Event received from libinput");
 
+                    match &event {
+                        Event::Device(DeviceEvent::Added(added)) => {
+                            health.device_added(added.device().name());
+                            translator.trackpad_added(&added.device());
+                        },
+                        Event::Device(DeviceEvent::Removed(removed)) => {
+                            warn!("A trackpad was removed");
+                            failure = failure.or(health.device_removed(removed.device().name()));
+                        },
+                        _ => {}
+                    }
+
                     // Process the gesture
-                    if let Err(e) = translator.translate_gesture(event).await { 
+                    let result = translator.translate_gesture(trace::TraceEvent::from_event(&event)).await;
+                    if let Err(e) = &result { 
                         error!("{:?}", e); 
                     }
+                    failure = failure.or(health.event_handled(&result));
+                }
+
+                if let Some(failure) = failure {
+                    return LoopEnd::Failed(failure);
                 }
                 
                 // Check if mouse_up_listener crashed (once per batch)
                 if mouse_up_listener.is_finished() {
-                    let fork_err = mouse_up_listener.await?.unwrap_err();
+                    let fork_err = match mouse_up_listener.await {
+                        Ok(Ok(())) => None,
+                        Ok(Err(e)) => Some(e),
+                        Err(e) => Some(GtError::from(e))
+                    };
                     error!("Error raised in fork: {:?}", fork_err);
-                    return Err(fork_err);
+                    return LoopEnd::Failed(Failure::TimerCrashed(fork_err));
+                }
+
+                // `responseTime`: let the next batch build up for a bit,
+                // rather than waking up for every report
+                let wait = translator.response_time().saturating_sub(batch_start.elapsed());
+                if !wait.is_zero() {
+                    tokio::time::sleep(wait).await;
+                }
+            }
+            
+            // Requests from the control socket
+            Some(msg) = ipc_recvr.recv() => {
+                match msg.request {
+                    IpcRequest::Stop => info!("Stop requested over the control socket"),
+                    IpcRequest::Reexec => info!("Re-exec requested over the control socket"),
+                    IpcRequest::Capabilities => {
+                        let capabilities = Capabilities::current(health.devices());
+                        let response = match serde_json::to_string_pretty(&capabilities) {
+                            Ok(json) => IpcResponse::ok(json),
+                            Err(e) => IpcResponse::err(format!("could not serialize capabilities: {e}"))
+                        };
+                        let _ = msg.reply.send(response);
+                        continue;
+                    },
+                    IpcRequest::Status => {
+                        let status = Status::current(health.devices(), translator.drag_is_held(), translator.is_paused(), &translator.cfg);
+                        let response = match serde_json::to_string(&status) {
+                            Ok(json) => IpcResponse::ok(json),
+                            Err(e) => IpcResponse::err(format!("could not serialize the status: {e}"))
+                        };
+                        let _ = msg.reply.send(response);
+                        continue;
+                    },
+                    IpcRequest::Stats => {
+                        let response = match serde_json::to_string(&Stats::current()) {
+                            Ok(json) => IpcResponse::ok(json),
+                            Err(e) => IpcResponse::err(format!("could not serialize the stats: {e}"))
+                        };
+                        let _ = msg.reply.send(response);
+                        continue;
+                    },
+                    IpcRequest::DumpState => {
+                        let state = translator.dump_state(health.devices());
+                        info!("Current state:\n{}", state);
+                        let _ = msg.reply.send(IpcResponse::ok(state));
+                        continue;
+                    },
+                    IpcRequest::Reload => {
+                        info!("Reload requested over the control socket");
+                        let response = match config_source.reload() {
+                            Ok(cfg) => match translator.apply_config(cfg).await {
+                                Ok(()) => IpcResponse::ok("reloaded the config file"),
+                                Err(e) => IpcResponse::err(format!("reloaded the config file, but could not apply it: {e:?}"))
+                            },
+                            Err(e) => {
+                                warn!("Keeping the current config, since the config file could not be reloaded: {}", e);
+                                IpcResponse::err(format!("kept the current config, since the config file could not be reloaded: {e}"))
+                            }
+                        };
+                        let _ = msg.reply.send(response);
+                        continue;
+                    },
+                    IpcRequest::ReopenLog => {
+                        let response = match log_file::reopen() {
+                            Some(Ok(path)) => {
+                                info!("Reopened the log file at {:?}", path);
+                                IpcResponse::ok(format!("reopened {}", path.display()))
+                            },
+                            Some(Err(e)) => IpcResponse::err(format!("could not reopen the log file: {e}")),
+                            None => IpcResponse::ok("not logging to a file, so there's nothing to reopen")
+                        };
+                        let _ = msg.reply.send(response);
+                        continue;
+                    },
+                    IpcRequest::Pause | IpcRequest::Resume | IpcRequest::TogglePause => {
+                        let pause = match msg.request {
+                            IpcRequest::Pause => true,
+                            IpcRequest::Resume => false,
+                            _ => !translator.is_paused_on_request()
+                        };
+                        let response = match translator.pause_on_request(pause).await {
+                            Ok(()) if pause => {
+                                info!("Paused on request");
+                                IpcResponse::ok("paused; gestures are left alone until `resume`")
+                            },
+                            Ok(()) => {
+                                info!("Resumed on request");
+                                IpcResponse::ok("resumed")
+                            },
+                            Err(e) => IpcResponse::err(format!("could not release the drag: {e:?}"))
+                        };
+                        let _ = msg.reply.send(response);
+                        continue;
+                    },
+                    IpcRequest::PointerLock { locked } => {
+                        translator.hint_pointer_lock(locked);
+                        let message = match locked {
+                            Some(true)  => "pointer marked as locked",
+                            Some(false) => "pointer marked as unlocked",
+                            None        => "guessing pointer lock from fullscreen windows again"
+                        };
+                        info!("Pointer lock hint over the control socket: {}", message);
+                        let _ = msg.reply.send(IpcResponse::ok(message));
+                        continue;
+                    },
+                    IpcRequest::ConfigGet { ref key } => {
+                        let response = get_config_value(&translator.cfg, key.as_deref());
+                        let _ = msg.reply.send(response);
+                        continue;
+                    },
+                    IpcRequest::ConfigSet { ref key, ref value, persist } => {
+                        let response = set_config_value(translator, key, value.clone(), persist).await;
+                        let _ = msg.reply.send(response);
+                        continue;
+                    }
+                }
+                return LoopEnd::Finished(Some(msg));
+            }
+
+            // Whole new configs, like when another user's session comes to 
+            // the front (for `--system`), or the config file is reloaded
+            Some(cfg) = config_recvr.recv() => {
+                if let Err(e) = translator.apply_config(cfg).await {
+                    error!("{:?}", e);
                 }
             }
             
-            // Periodically check for exit signal
+            // Periodically check for exit signal (this only fires after 
+            // 100ms without events, which is also when a drag may need
+            // jiggling to stay alive)
             _ = tokio::time::sleep(Duration::from_millis(100)) => {
                 if should_exit.load(Ordering::Acquire) {
-                    break;
+                    return LoopEnd::Finished(None);
+                }
+                if translator.cfg.idle_exit_after().is_some_and(|limit| last_activity.elapsed() >= limit) {
+                    info!("The trackpad hasn't been used in {} hours, exiting", translator.cfg.idle_exit_hours);
+                    return LoopEnd::Finished(None);
+                }
+                if let Err(e) = translator.keep_drag_alive() {
+                    error!("{:?}", e);
                 }
             }
         }
     }
+}
+
+
+/// Spawns 1 separate thread to handle mouse_up_delay timeouts.
+fn spawn_drag_end_timer(
+    translator: &GestureTranslator, 
+    recvr: Receiver<ControlSignal>
+) -> JoinHandle<Result<(), GtError>> {
+
+    debug!("Creating new thread to manage drag end timer");
+    let mut vtp_clone = translator.vtp.clone();
+    let delay = translator.timer_delay();
+
+    let fork_fn = async move {
+        vtp_clone.handle_mouse_up_timeout(delay, recvr)
+            .await
+            .map_err(GtError::from)
+    };
+
+    tokio::spawn(fork_fn)
+}
+
+
+async fn stop_drag_end_timer(
+    translator: &mut GestureTranslator, 
+    mut mouse_up_listener: JoinHandle<Result<(), GtError>>
+) -> Result<(), GtError> {
 
     debug!("Joining delay timer thread");
-    translator.send_signal(ControlSignal::TerminateThread).await?;
 
-    // Wait for the mouse_up_listener to finish
-    mouse_up_listener.await??;
-    
-    // Return translator for cleanup
-    Ok(translator)
-}
\ No newline at end of file
+    // The timer thread gets a bounded amount of time to wind down. If it
+    // doesn't make it, it's abandoned, since releasing the button and 
+    // destroying the device (done by the caller) matter more than a tidy join.
+    let join_timer_thread = async {
+        translator.send_signal(ControlSignal::TerminateThread).await?;
+        (&mut mouse_up_listener).await?
+    };
+
+    match tokio::time::timeout(SHUTDOWN_TIMEOUT, join_timer_thread).await {
+        Ok(join_result) => join_result?,
+        Err(_) => {
+            warn!("Delay timer thread did not stop within {:?}, aborting it", SHUTDOWN_TIMEOUT);
+            mouse_up_listener.abort();
+        }
+    }
+
+    Ok(())
+}
+
+
+/// `calibrate`: walks the user through a few gestures to work out
+/// settings for them (see `calibrate`). This reads the trackpad directly,
+/// so it doesn't need (or want) a running instance.
+async fn run_calibrate(args: &ArgMatches) -> Result<(), GtError> {
+
+    if instance_lock::is_running(false) || instance_lock::is_running(true) {
+        eprintln!("linux-3-finger-drag is already running, and would turn the gestures \
+            for calibration into drags. Stop it first, with `linux-3-finger-drag stop` \
+            (or `systemctl --user stop three-finger-drag`, if it runs as a service).");
+        std::process::exit(1);
+    }
+
+    // config messages would only get in the way of the prompts, so this
+    // skips init_cfg(); with no config file, the defaults are fine here
+    let configs = config::parse_config_file().unwrap_or_default();
+
+    let real_trackpad = libinput_init::find_real_trackpads(&configs.disabled_devices)?;
+    let mut reader = GestureReader::new(real_trackpad)?;
+
+    let swipes = args.get_one::<u8>("swipes").map_or(calibrate::DEFAULT_SWIPES, |&swipes| swipes.into());
+    Ok(calibrate::run_calibration(&mut reader, &configs, swipes).await?)
+}
+
+
+/// `self-test`: checks that events written to a fresh virtual trackpad
+/// come back out of its evdev node (see `self_test`), exiting with 1 if
+/// they don't, so it can gate a service's startup.
+fn run_self_test() -> Result<(), GtError> {
+
+    init_logger(&config::Configuration::default());
+
+    match self_test::run_self_test() {
+        Ok(()) => {
+            println!("Self-test passed: the virtual trackpad works.");
+            Ok(())
+        },
+        Err(e) => {
+            eprintln!("Self-test failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+/// `check-config`: loads the config file (with its drop-ins, and any
+/// `L3FD_` variables) the way the daemon would, and prints what it comes
+/// out as, along with anything wrong with it, without starting anything.
+/// Exits with 1 if there's something wrong, for CI.
+fn run_check_config() -> Result<(), GtError> {
+
+    let path = match config::find_config_file() {
+        Ok(path) => path,
+        Err(e) => {
+            println!("{}\nThe defaults would be used:", e);
+            println!("{}", serde_json::to_string_pretty(&config::Configuration::default()).map_err(std::io::Error::from)?);
+            return Ok(());
+        }
+    };
+    println!("Config file: {}", path.display());
+    for drop_in in config::drop_in_files(&path) {
+        println!("  with drop-in: {}", drop_in.display());
+    }
+
+    let mut problems = 0;
+    match config::unknown_keys(&path) {
+        Ok(keys) => for key in keys {
+            println!("Unknown option '{}', which is ignored (check its spelling?)", key);
+            problems += 1;
+        },
+        Err(e) => {
+            println!("Could not be read: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    match config::parse_config_file_at(&path, None) {
+        Ok(cfg) => println!("\nEffective configuration (with defaults for unspecified values):\n{}",
+            serde_json::to_string_pretty(&cfg).map_err(std::io::Error::from)?),
+        Err(e) => {
+            println!("Invalid, so the daemon would fall back on the defaults: {}", e);
+            problems += 1;
+        }
+    }
+
+    if problems > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+
+/// `install-udev-rule`: installs the udev rule giving access to
+/// /dev/uinput (the README's step 3.1), and then offers to add whoever
+/// ran it to the `input` group (step 3.2). Meant to be run with `sudo`,
+/// but goes through it for each step otherwise.
+fn run_install_udev_rule() -> Result<(), GtError> {
+
+    println!("Installing {} (and loading the uinput module, now and on boot)...", setup::UDEV_RULE_PATH);
+    if let Err(e) = setup::install_udev_rule() {
+        eprintln!("Could not install the udev rule: {}", e);
+        std::process::exit(1);
+    }
+    if setup::uinput_is_writable() {
+        println!("Installed.");
+    } else {
+        println!("Installed, but /dev/uinput can't be opened yet; log out and back in (or reboot).");
+    }
+
+    let Some(user) = setup::invoking_user() else { return Ok(()) };
+    if setup::user_in_input_group(&user) {
+        return Ok(());
+    }
+    if setup::confirm(&format!("{user} isn't in the input group, so trackpads can't be read. Add them?"))? {
+        if let Err(e) = setup::add_to_input_group() {
+            eprintln!("Could not add {} to the input group: {}", user, e);
+            std::process::exit(1);
+        }
+        println!("Added {user} to the input group, which takes effect once they log out and back in.");
+    }
+    Ok(())
+}
+
+
+/// `doctor`: checks everything the program needs from the system, and
+/// what to do about anything missing, exiting with 1 if anything is.
+fn run_doctor() -> Result<(), GtError> {
+    if !doctor::print(&doctor::examine()) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+
+/// `list-devices`: prints every input device libinput can see, marking
+/// the trackpads (the ones drags would be read from), for working out why
+/// one isn't being found.
+fn run_list_devices() -> Result<(), GtError> {
+
+    // as for calibrate, the config only matters for disabledDevices here
+    let configs = config::parse_config_file().unwrap_or_default();
+    let devices = libinput_init::list_devices(&configs.disabled_devices)?;
+
+    for device in &devices {
+        let marker = match device.verdict {
+            libinput_init::DeviceVerdict::Trackpad => "  <- trackpad",
+            libinput_init::DeviceVerdict::Disabled => "  <- trackpad, but in disabledDevices",
+            libinput_init::DeviceVerdict::VirtualTrackpad => "  <- this program's own virtual trackpad",
+            libinput_init::DeviceVerdict::NoGestures => ""
+        };
+        println!("/dev/input/{}: {} ({}){}", device.sysname, device.name, device.id, marker);
+        println!("    {}", if device.capabilities.is_empty() { "no capabilities".to_string() } else { device.capabilities.join(", ") });
+    }
+
+    let trackpads = devices.iter()
+        .filter(|device| device.verdict == libinput_init::DeviceVerdict::Trackpad)
+        .count();
+    println!();
+    match trackpads {
+        0 => println!("No trackpads found. A trackpad needs both the pointer and gesture \
+            capabilities; if yours is listed without gesture, libinput doesn't take it for a \
+            touchpad, so there are no three-finger gestures to read from it."),
+        1 => println!("1 trackpad found, which drags are read from."),
+        n => println!("{} trackpads found, which drags are read from.", n)
+    }
+    Ok(())
+}
+
+
+/// `print-config-schema`: prints a JSON Schema for the config file (see
+/// `config_schema`), for pointing an editor at.
+fn run_print_config_schema() -> Result<(), GtError> {
+    println!("{}", serde_json::to_string_pretty(&config_schema::config_schema()).map_err(std::io::Error::from)?);
+    Ok(())
+}
+
+
+/// `record`: writes the trackpad's events to a trace file until Ctrl+C
+/// (see `trace`), without dragging. A running instance is left be, since
+/// it reads the same events without taking them from anyone.
+async fn run_record(args: &ArgMatches) -> Result<(), GtError> {
+
+    let path = args.get_one::<PathBuf>("path").expect("path is required");
+    // as for calibrate, without the config messages
+    let configs = config::parse_config_file().unwrap_or_default();
+    let real_trackpad = libinput_init::find_real_trackpads(&configs.disabled_devices)?;
+
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let should_stop = Arc::new(AtomicBool::new(false));
+    flag::register(SIGINT, Arc::clone(&should_stop))?;
+    flag::register(SIGTERM, Arc::clone(&should_stop))?;
+
+    println!("Recording to {}; make the gestures that misbehave, then press Ctrl+C.", path.display());
+    let recorded = trace::record(real_trackpad, &configs, &mut out, &should_stop).await?;
+    println!("\nRecorded {} events to {}.", recorded, path.display());
+    Ok(())
+}
+
+
+/// `replay`: plays a trace from `record` back through a translator of
+/// its own (see `replay`). Nothing from the desktop (its pointer speed,
+/// the focused app) is followed, so a trace comes out the same wherever
+/// it's replayed.
+async fn run_replay(args: &ArgMatches) -> Result<(), GtError> {
+
+    let path = args.get_one::<PathBuf>("path").expect("path is required");
+    let (header, events) = match std::fs::File::open(path).and_then(|file| trace::read_trace(std::io::BufReader::new(file))) {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("Could not read the trace {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut configs = if args.get_flag("my-config") {
+        config::parse_config_file().unwrap_or_default()
+    } else {
+        let mut recorded: config::Configuration = serde_json::from_value(header.config).unwrap_or_else(|e| {
+            eprintln!("The trace's config doesn't load here ({}), so the defaults are used instead.", e);
+            config::Configuration::default()
+        });
+        // whoever recorded the trace shouldn't get to run commands here
+        recorded.gesture_macros.clear();
+        recorded
+    };
+    // and its log file is likely on another machine
+    configs.log_file = "stdout".to_string();
+    init_logger(&configs);
+    info!("Replaying {}, recorded by version {}", path.display(), header.version);
+
+    let (sender, recvr) = mpsc::channel::<ControlSignal>(3);
+    let vtrackpad = if args.get_flag("dry-run") {
+        virtual_trackpad::VirtualTrackpad::dry_run(configs.output_mode)
+    } else {
+        virtual_trackpad::start_handler_for(configs.output_mode)?
+    };
+    let mut translator = GestureTranslator::new(
+        vtrackpad,
+        configs,
+        sender,
+        watch::channel(1.0).1,
+        watch::channel(1.0).1,
+        watch::channel(false).1,
+        watch::channel(None).1
+    );
+    let timer = spawn_drag_end_timer(&translator, recvr);
+
+    let replay_result = replay::replay(&mut translator, &events).await;
+    let stop_result = stop_drag_end_timer(&mut translator, timer).await;
+    let cleanup_result = clean_up(translator);
+
+    replay_result?;
+    stop_result?;
+    cleanup_result
+}
+
+
+/// `simulate-drag`: performs a made-up drag through a fresh virtual 
+/// trackpad, with the usual config, to check that drags come out the
+/// other end (see `simulate`).
+async fn run_simulation(args: &ArgMatches) -> Result<(), GtError> {
+
+    // these all have defaults
+    let dx = args.get_one::<f64>("dx").copied().unwrap_or_default();
+    let dy = args.get_one::<f64>("dy").copied().unwrap_or_default();
+    let duration = args.get_one::<Duration>("duration").copied().unwrap_or_default();
+
+    let configs = config::init_cfg(None);
+    init_logger(&configs);
+
+    simulate_once(configs, dx, dy, duration).await
+}
+
+
+/// One simulated drag, start to finish: its own virtual trackpad and
+/// translator, torn down again once the drag has ended.
+async fn simulate_once(configs: config::Configuration, dx: f64, dy: f64, duration: Duration) -> Result<(), GtError> {
+
+    let (sender, recvr) = mpsc::channel::<ControlSignal>(3);
+    let vtrackpad = virtual_trackpad::start_handler_for(configs.output_mode)?;
+    let mut translator = build_translator(vtrackpad, configs, sender, None);
+    let timer = spawn_drag_end_timer(&translator, recvr);
+
+    let simulation_result = simulate::simulate_drag(&mut translator, dx, dy, duration).await;
+    let stop_result = stop_drag_end_timer(&mut translator, timer).await;
+    let cleanup_result = clean_up(translator);
+
+    simulation_result?;
+    stop_result?;
+    cleanup_result
+}
+
+
+/// `test-gesture`: drags a square through a fresh virtual trackpad, with
+/// no translator (or trackpad) involved, for checking the uinput side on
+/// its own.
+async fn run_test_gesture(args: &ArgMatches) -> Result<(), GtError> {
+
+    // this has a default
+    let size = args.get_one::<f64>("size").copied().unwrap_or_default();
+
+    let configs = config::init_cfg(None);
+    init_logger(&configs);
+
+    let mut vtrackpad = virtual_trackpad::start_handler_for(configs.output_mode)?;
+    println!("Dragging a {size} pixel square from where the pointer is in {} seconds; \
+        put it over something safe to drag (like a window's title bar, or an empty spot \
+        on the desktop, which should show a selection box).", TEST_GESTURE_WAIT.as_secs());
+    tokio::time::sleep(TEST_GESTURE_WAIT).await;
+
+    let drag_result = simulate::drag_square(&mut vtrackpad, size).await;
+    let destruct_result = vtrackpad.destruct();
+
+    match drag_result.and(destruct_result) {
+        Ok(()) => {
+            println!("Done. If nothing moved, the virtual trackpad's events aren't reaching \
+                your desktop; try `linux-3-finger-drag self-test`, and `linux-3-finger-drag doctor`.");
+            Ok(())
+        },
+        Err(e) => {
+            eprintln!("Could not drag the square: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+/// `bench-backends`: times the same made-up drag through each backend
+/// (see `bench_backends`), and prints how each did.
+async fn run_bench_backends() -> Result<(), GtError> {
+
+    // the defaults (rather than the config file) keep the drags the same
+    // from one machine to the next, and the desktop's speed out of it
+    let configs = config::Configuration { sync_desktop_speed: false, ..Default::default() };
+    init_logger(&configs);
+
+    let backends = [
+        ("uinput (pointer)", config::OutputMode::Pointer),
+        ("uinput (touchscreen)", config::OutputMode::Touch)
+    ];
+    for (name, mode) in backends {
+        match bench_once(config::Configuration { output_mode: mode, ..configs.clone() }).await {
+            Ok(stats) => println!("{}: {}", name, stats),
+            Err(e) => println!("{}: failed ({:?})", name, e)
+        }
+    }
+    for (name, reason) in bench_backends::UNAVAILABLE_BACKENDS {
+        println!("{}: unavailable ({})", name, reason);
+    }
+    Ok(())
+}
+
+
+/// One benchmarked drag, on its own virtual device and translator.
+async fn bench_once(configs: config::Configuration) -> Result<bench_backends::BenchStats, GtError> {
+
+    let (sender, recvr) = mpsc::channel::<ControlSignal>(3);
+    let vtrackpad = virtual_trackpad::start_handler_for(configs.output_mode)?;
+    let mut watcher = match vtrackpad.evdev_path().and_then(|path| MotionWatcher::open(&path)) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            let _ = vtrackpad.destruct();
+            return Err(e.into());
+        }
+    };
+    let mut translator = build_translator(vtrackpad, configs, sender, None);
+    let timer = spawn_drag_end_timer(&translator, recvr);
+
+    let (dx, dy) = bench_backends::BENCH_DRAG;
+    let simulation_result = simulate::simulate_drag_observed(
+        &mut translator, dx, dy, bench_backends::BENCH_DURATION,
+        |sent| watcher.wait_for_motion(sent)
+    ).await;
+    let stop_result = stop_drag_end_timer(&mut translator, timer).await;
+    let cleanup_result = clean_up(translator);
+
+    simulation_result?;
+    stop_result?;
+    cleanup_result?;
+    Ok(watcher.finish()?)
+}
+
+
+/// `bench`: times drags from a made-up touchpad, through the main loop,
+/// to the virtual trackpad (see `bench`), and prints how they did.
+async fn run_bench(args: &ArgMatches) -> Result<(), GtError> {
+
+    // this has a default
+    let updates = args.get_one::<u32>("updates").copied().unwrap_or(bench::DEFAULT_UPDATES);
+
+    // the config file's, since that's what's being measured, but without
+    // anything that could pause drags or change their speed partway
+    let mut configs = config::Configuration {
+        sync_desktop_speed: false,
+        scale_by_output: false,
+        pause_for_apps: Vec::new(),
+        pause_when_fullscreen: false,
+        ..config::init_cfg(None)
+    };
+    if let Some(response_time) = args.get_one::<Duration>("response-time") {
+        configs.response_time = Some(*response_time);
+    }
+    init_logger(&configs);
+
+    let touchpad = bench::BenchTouchpad::create()?;
+    let bench_result = bench_through(touchpad, configs, updates).await;
+
+    match bench_result {
+        Ok(stats) => {
+            println!("{}", stats);
+            Ok(())
+        },
+        Err(e) => {
+            eprintln!("The benchmark failed: {:?}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+/// The benchmark itself: the main loop reading `touchpad` while another
+/// thread moves its fingers. The touchpad is destroyed at the end.
+async fn bench_through(
+    touchpad: bench::BenchTouchpad,
+    configs: config::Configuration,
+    updates: u32
+) -> Result<bench_backends::BenchStats, GtError> {
+
+    let mut real_trackpad = match bench::open_grabbed(&touchpad) {
+        Ok(libinput) => libinput,
+        Err(e) => {
+            let _ = touchpad.destroy();
+            return Err(e.into());
+        }
+    };
+    let vtrackpad = virtual_trackpad::start_handler_for(configs.output_mode)?;
+    let watcher = match vtrackpad.evdev_path().and_then(|path| MotionWatcher::open(&path)) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            let _ = vtrackpad.destruct();
+            let _ = touchpad.destroy();
+            return Err(e.into());
+        }
+    };
+
+    let (sender, recvr) = mpsc::channel::<ControlSignal>(3);
+    let mut translator = build_translator(vtrackpad, configs, sender, None);
+    let mut timer = spawn_drag_end_timer(&translator, recvr);
+
+    // set when the fingers are done (or Ctrl+C), which ends the main
+    // loop, or when the main loop fails, which stops the fingers
+    let should_exit = Arc::new(AtomicBool::new(false));
+    let _ = flag::register(SIGINT, Arc::clone(&should_exit));
+    let driver = tokio::task::spawn_blocking({
+        let should_exit = Arc::clone(&should_exit);
+        let mut touchpad = touchpad;
+        move || {
+            let stats = bench::drive(&mut touchpad, watcher, updates, &should_exit);
+            should_exit.store(true, Ordering::Release);
+            (touchpad, stats)
+        }
+    });
+
+    // nothing's sent over these, but the main loop wants them
+    let (_ipc_sender, mut ipc_recvr) = mpsc::channel::<IpcMessage>(1);
+    let (_config_sender, mut config_recvr) = mpsc::channel::<config::Configuration>(1);
+    let end = run_main_event_loop(
+        &mut translator,
+        &mut timer,
+        &mut ipc_recvr,
+        &mut config_recvr,
+        ConfigSource { system: false, preset_override: None },
+        &should_exit,
+        &mut real_trackpad,
+        &mut LoopHealth::default()
+    ).await;
+    should_exit.store(true, Ordering::Release);
+
+    let (touchpad, stats) = driver.await?;
+    let stop_result = stop_drag_end_timer(&mut translator, timer).await;
+    let cleanup_result = clean_up(translator);
+    drop(real_trackpad);
+    if let Err(e) = touchpad.destroy() {
+        warn!("Could not remove the benchmark's touchpad: {}", e);
+    }
+
+    if let LoopEnd::Failed(failure) = end {
+        return Err(std::io::Error::other(format!("the main loop failed ({:?})", failure)).into());
+    }
+    stop_result?;
+    cleanup_result?;
+    Ok(stats?)
+}
+
+
+const UINPUT_STEP: &str   = "Virtual trackpad access (/dev/uinput)";
+const TRACKPAD_STEP: &str = "Trackpad access";
+const CONFIG_STEP: &str   = "Config file";
+const DRAG_STEP: &str     = "Test drag";
+const SERVICE_STEP: &str  = "Start on login (systemd user unit)";
+
+const TEST_DRAG_DX: f64 = 400.0;
+const TEST_DRAG_WAIT: Duration = Duration::from_secs(5);
+
+
+/// `setup`: a guided first run, checking each thing the program needs 
+/// and offering to fix what's missing (see `init::setup`), then trying
+/// out a drag. Each step is reported at the end, passed or not.
+async fn run_setup() -> Result<(), GtError> {
+
+    if setup::is_root() {
+        eprintln!("Run setup as yourself, not as root (or with sudo); it asks for \
+            your password when it needs to change something system-wide.");
+        std::process::exit(1);
+    }
+
+    println!("This checks that linux-3-finger-drag has everything it needs, and offers \
+        to set up anything that's missing.\n");
+    let mut report = SetupReport::default();
+
+    // 1. uinput, without which nothing can be dragged at all
+    let uinput = if setup::uinput_is_writable() {
+        StepOutcome::Passed
+    } else if setup::udev_rule_installed() {
+        StepOutcome::Failed("the udev rule is installed, but /dev/uinput still can't be \
+            opened; try rebooting, or see the README's troubleshooting section".to_string())
+    } else if setup::confirm("/dev/uinput can't be opened. Install the udev rule that allows it?")? {
+        match setup::install_udev_rule() {
+            Ok(()) if setup::uinput_is_writable() => StepOutcome::Fixed("installed the udev rule".to_string()),
+            Ok(()) => StepOutcome::Failed("installed the udev rule, but it hasn't \
+                taken effect yet; reboot and run setup again".to_string()),
+            Err(e) => StepOutcome::Failed(e.to_string())
+        }
+    } else {
+        StepOutcome::Skipped("the udev rule wasn't installed".to_string())
+    };
+    report.record(UINPUT_STEP, uinput);
+
+    // 2. reading the trackpad
+    // any trackpad at all will do, to know they can be read
+    let trackpad = match libinput_init::find_real_trackpads(&[]) {
+        Ok(_) => StepOutcome::Passed,
+        Err(e) if e.kind() != std::io::ErrorKind::PermissionDenied => StepOutcome::Failed(e.to_string()),
+        Err(_) if setup::confirm("The trackpad can't be read. Add yourself to the `input` group?")? => {
+            match setup::add_to_input_group() {
+                Ok(()) => StepOutcome::Fixed("added you to the `input` group, which takes \
+                    effect once you log out and back in".to_string()),
+                Err(e) => StepOutcome::Failed(e.to_string())
+            }
+        },
+        Err(_) => StepOutcome::Skipped("not added to the `input` group (see the README \
+            for the setgid helper, if you'd rather not be)".to_string())
+    };
+    report.record(TRACKPAD_STEP, trackpad);
+
+    // 3. the config file
+    let config_outcome = match setup::check_config_file() {
+        Ok((_, true)) => StepOutcome::Passed,
+        Ok((path, false)) if setup::confirm(&format!("There's no config file yet. \
+            Write one with the defaults to {}?", path.display()))? => {
+            match setup::write_default_config(&path) {
+                Ok(()) => StepOutcome::Fixed(format!("wrote {}", path.display())),
+                Err(e) => StepOutcome::Failed(e.to_string())
+            }
+        },
+        Ok(_) => StepOutcome::Skipped("no config file, so the defaults will be used".to_string()),
+        Err(e) => StepOutcome::Failed(format!("the config file doesn't load: {e}"))
+    };
+    report.record(CONFIG_STEP, config_outcome);
+
+    // 4. a drag, to see it all working
+    let drag = if matches!(report.outcome(UINPUT_STEP), Some(StepOutcome::Failed(_) | StepOutcome::Skipped(_))) {
+        StepOutcome::Skipped("no access to /dev/uinput".to_string())
+    } else if setup::confirm("Try a test drag? It will drag whatever is under the pointer to the right.")? {
+        println!("Put the pointer over a window's title bar; the drag starts in {} seconds.", TEST_DRAG_WAIT.as_secs());
+        tokio::time::sleep(TEST_DRAG_WAIT).await;
+
+        let configs = config::parse_config_file().unwrap_or_default();
+        match simulate_once(configs, TEST_DRAG_DX, 0.0, Duration::from_millis(500)).await {
+            Ok(()) if setup::confirm("Did the window move?")? => StepOutcome::Passed,
+            Ok(()) => StepOutcome::Failed("the drag didn't come through; see \
+                \"Checking that drags work, without a trackpad\" in the README".to_string()),
+            Err(e) => StepOutcome::Failed(format!("{e:?}"))
+        }
+    } else {
+        StepOutcome::Skipped("not tried".to_string())
+    };
+    report.record(DRAG_STEP, drag);
+
+    // 5. starting on login
+    let service = if !setup::has_systemd() {
+        StepOutcome::Skipped("systemd isn't running; see the README for other ways to autostart".to_string())
+    } else if setup::service_unit_path()?.exists() {
+        StepOutcome::Passed
+    } else if setup::confirm("Install and start the systemd user unit, so the program runs on every login?")? {
+        match setup::install_user_service() {
+            Ok(()) => StepOutcome::Fixed("enabled and started three-finger-drag.service".to_string()),
+            Err(e) => StepOutcome::Failed(e.to_string())
+        }
+    } else {
+        StepOutcome::Skipped("not installed".to_string())
+    };
+    report.record(SERVICE_STEP, service);
+
+    report.print();
+    if report.has_failures() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+
+/// `import`: converts what it can from other gesture tools' configs (see
+/// `init::import`), and prints it along with what it couldn't. With
+/// `--save`, the converted values are written into the config file too.
+fn run_import(args: &ArgMatches) -> Result<(), GtError> {
+
+    let save = args.get_flag("save");
+    let sources = match args.get_one::<import::Tool>("tool").copied() {
+        None => import::find_all_configs()?,
+        Some(tool) => {
+            let path = match args.get_one::<String>("path") {
+                Some(path) => path.into(),
+                None => tool.find_config().ok_or_else(|| std::io::Error::new(
+                    std::io::ErrorKind::NotFound, 
+                    format!("No {} config was found; give the path to one instead", tool.name())
+                ))?
+            };
+            vec![(tool, path)]
+        }
+    };
+
+    let mut values = serde_json::Map::new();
+    for (tool, path) in sources {
+        let imported = import::import_from(tool, &path)?;
+
+        println!("From {} ({}):", tool.name(), path.display());
+        for (key, value) in &imported.values {
+            println!("  {key}: {value}");
+        }
+        if imported.values.is_empty() {
+            println!("  (no settings that carry over)");
+        }
+        for note in &imported.notes {
+            println!("  note: {note}");
+        }
+        println!();
+
+        values.extend(imported.values);
+    }
+
+    if values.is_empty() {
+        println!("Nothing to save.");
+    } else if save {
+        for (key, value) in values {
+            config::persist_value(&key, value)?;
+        }
+        println!("Saved to {}.", config::get_config_file_path()?.display());
+    } else {
+        println!("Nothing was saved; run again with --save to write these into your config file.");
+    }
+
+    Ok(())
+}
+
+
+/// `generate-config`: writes a config file with every field at its
+/// default (see `init::generate_config`), so there's something to edit.
+fn run_generate_config(args: &ArgMatches) -> Result<(), GtError> {
+
+    let format = args.get_one::<config::ConfigFormat>("format").copied().unwrap_or(config::ConfigFormat::Json);
+    let overwrite = args.get_flag("force");
+
+    let path = match generate_config::write_default_config(format, overwrite) {
+        Ok(path) => path,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            eprintln!("{}; run again with --force to replace it.", e);
+            std::process::exit(1);
+        },
+        Err(e) => return Err(e.into())
+    };
+    println!("Wrote the default config to {}.", path.display());
+
+    // a TOML or YAML file elsewhere in the folder would be used instead
+    if config::get_config_file_path().is_ok_and(|in_use| in_use != path) {
+        println!("Note that {} is what gets loaded, though, so move it aside for this one to be used.",
+            config::get_config_file_path()?.display());
+    }
+    Ok(())
+}
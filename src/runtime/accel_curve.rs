@@ -0,0 +1,73 @@
+// `accelerationProfile`: how a drag's speed depends on how fast the fingers
+// are moving. libinput already accelerates the motion it reports for
+// gestures, with the same curve as for moving the cursor with one finger,
+// so `adaptive` just uses that as it comes. The others start from
+// libinput's unaccelerated motion instead: `flat` as it is, so the cursor
+// always moves the same distance for the same finger travel, and `linear`
+// scaled in proportion to the speed of the fingers, within limits.
+//
+// `acceleration` (and the rest of the speed settings) are applied on top
+// of this, whichever it is.
+
+use crate::init::config::AccelerationProfile;
+use super::{
+    event_handler::{Gesture, MM_PER_UNIT},
+    trace::{TraceEvent, TraceKind}
+};
+
+
+// the finger speed at which `linear` leaves motion as it is, in mm/s
+const LINEAR_UNIT_SPEED: f64 = 100.0;
+// and how far it goes either way, so the slowest drags still move and
+// a flick doesn't throw the cursor off the screen
+const LINEAR_MIN_FACTOR: f64 = 0.3;
+const LINEAR_MAX_FACTOR: f64 = 3.0;
+
+// libinput timestamps can repeat, so this is the least time between two
+// updates, to keep the speed finite
+const MIN_UPDATE_GAP_USEC: u64 = 1000;
+
+
+/// Applies `accelerationProfile` to swipe updates, following the time
+/// between them for the speed.
+#[derive(Debug, Default)]
+pub struct AccelCurve {
+    last_update_usec: Option<u64>
+}
+
+impl AccelCurve {
+
+    /// `gesture` (made from `event`), with its motion following `profile`.
+    /// Anything but a swipe update comes back as it was.
+    pub fn apply(&mut self, profile: AccelerationProfile, event: &TraceEvent, gesture: Gesture) -> Gesture {
+
+        match event.kind {
+            TraceKind::SwipeUpdate => (),
+            // a new swipe's speed has nothing to do with the last one's
+            TraceKind::SwipeBegin | TraceKind::SwipeEnd => {
+                self.last_update_usec = None;
+                return gesture;
+            },
+            _ => return gesture
+        }
+        let Gesture::SwipeUpdate { .. } = gesture else { return gesture };
+
+        let now = event.time_usec;
+        let gap_usec = self.last_update_usec.map(|last| now.saturating_sub(last).max(MIN_UPDATE_GAP_USEC));
+        self.last_update_usec = Some(now);
+
+        let (dx, dy) = (event.dx_unaccelerated, event.dy_unaccelerated);
+        let factor = match (profile, gap_usec) {
+            (AccelerationProfile::Adaptive, _) => return gesture,
+            (AccelerationProfile::Flat, _) => 1.0,
+            // the first update has no speed to go by yet
+            (AccelerationProfile::Linear, None) => 1.0,
+            (AccelerationProfile::Linear, Some(gap_usec)) => {
+                let speed = dx.hypot(dy) * MM_PER_UNIT / (gap_usec as f64 / 1_000_000.0);
+                (speed / LINEAR_UNIT_SPEED).clamp(LINEAR_MIN_FACTOR, LINEAR_MAX_FACTOR)
+            }
+        };
+
+        Gesture::SwipeUpdate { dx: dx * factor, dy: dy * factor }
+    }
+}
@@ -0,0 +1,334 @@
+// `bench`: how long a drag takes to get through the whole program, from
+// fingers moving on a trackpad to the pointer moving. A made-up touchpad
+// (another uinput device) has three fingers go around in circles on it,
+// libinput reads it like any trackpad, and its gestures go through the
+// main loop (`responseTime` and all) and out the virtual trackpad, where
+// each update is timed like in `bench-backends`. So this is the one to
+// measure `responseTime`, or changes to the main loop, with.
+//
+// Both devices are grabbed for the run, so neither the desktop nor a
+// running instance sees the fingers, or the drag they make (though the
+// desktop may notice a touchpad come and go).
+
+use std::{
+    fs::File,
+    io::{Error, ErrorKind},
+    os::fd::{AsRawFd, OwnedFd},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant}
+};
+
+use input::{DeviceCapability, Libinput, LibinputInterface};
+use input_linux::{
+    sys::input_event,
+    AbsoluteAxis, AbsoluteEvent, AbsoluteInfo, AbsoluteInfoSetup,
+    EvdevHandle, EventKind, EventTime, InputEvent, InputId, InputProperty,
+    Key, KeyEvent, KeyState,
+    SynchronizeEvent, SynchronizeKind,
+    UInputHandle
+};
+use tracing::debug;
+
+use crate::init::libinput_init;
+use super::{
+    bench_backends::{BenchStats, MotionWatcher},
+    virtual_trackpad::{open_uinput, VIRTUAL_VENDOR_ID}
+};
+
+
+// Like the virtual trackpad's, these mark the touchpad as ours, so device
+// discovery (a running instance's, too) skips it
+pub const BENCH_TOUCHPAD_NAME: &str = "Benchmark touchpad (created by linux-3-finger-drag)";
+pub const BENCH_TOUCHPAD_PRODUCT_ID: u16 = 0x567a;
+
+pub const DEFAULT_UPDATES: u32 = 300;
+
+// 100 x 60 mm, at about the resolution of a laptop's
+const UNITS_PER_MM: i32 = 40;
+const SIZE_MM: (f64, f64) = (100.0, 60.0);
+
+// three fingers side by side, going around a circle in the middle of the
+// touchpad a millimeter at a time, about as often as a real one reports
+const FINGERS: usize = 3;
+const FINGER_SPACING_MM: f64 = 15.0;
+const CIRCLE_RADIUS_MM: f64 = 15.0;
+const STEP_MM: f64 = 1.0;
+const FRAME_INTERVAL: Duration = Duration::from_millis(10);
+
+// libinput needs a bit of motion before it calls it a swipe, so the
+// timing starts once the first update comes through, or gives up after
+// this many
+const WARM_UP_FRAMES: usize = 100;
+
+// how long udev gets to give the new touchpad's node its permissions
+const OPEN_TIMEOUT: Duration = Duration::from_secs(3);
+const OPEN_RETRY: Duration = Duration::from_millis(50);
+
+const ZERO: EventTime = EventTime::new(0, 0);
+
+
+/// The made-up touchpad.
+pub struct BenchTouchpad {
+    handle: UInputHandle<File>,
+    angle: f64      // how far around the circle the fingers are, in radians
+}
+
+impl BenchTouchpad {
+
+    /// Creates the touchpad, with no fingers on it.
+    pub fn create() -> Result<BenchTouchpad, Error> {
+
+        let handle = UInputHandle::new(open_uinput()?);
+
+        handle.set_evbit(EventKind::Key)?;
+        for key in [Key::ButtonLeft, Key::ButtonTouch, Key::ButtonToolFinger, Key::ButtonToolDoubleTap, Key::ButtonToolTripleTap] {
+            handle.set_keybit(key)?;
+        }
+        handle.set_evbit(EventKind::Absolute)?;
+        // a clickpad, like most laptops have now
+        handle.set_propbit(InputProperty::Pointer)?;
+        handle.set_propbit(InputProperty::ButtonPad)?;
+
+        let axis = |axis, maximum, resolution| AbsoluteInfoSetup {
+            axis,
+            info: AbsoluteInfo { value: 0, minimum: 0, maximum, fuzz: 0, flat: 0, resolution }
+        };
+        let (width, height) = (to_units(SIZE_MM.0), to_units(SIZE_MM.1));
+        let axes = [
+            axis(AbsoluteAxis::X, width, UNITS_PER_MM),
+            axis(AbsoluteAxis::Y, height, UNITS_PER_MM),
+            axis(AbsoluteAxis::MultitouchSlot, 4, 0),
+            axis(AbsoluteAxis::MultitouchPositionX, width, UNITS_PER_MM),
+            axis(AbsoluteAxis::MultitouchPositionY, height, UNITS_PER_MM),
+            axis(AbsoluteAxis::MultitouchTrackingId, i32::from(u16::MAX), 0)
+        ];
+        for setup in &axes {
+            handle.set_absbit(setup.axis)?;
+        }
+
+        let input_id = InputId {
+            bustype: input_linux::sys::BUS_USB,
+            vendor: VIRTUAL_VENDOR_ID,
+            product: BENCH_TOUCHPAD_PRODUCT_ID,
+            version: 0,
+        };
+        handle.create(&input_id, BENCH_TOUCHPAD_NAME.as_bytes(), 0, &axes)?;
+        debug!("Benchmark touchpad created");
+
+        Ok(BenchTouchpad { handle, angle: 0.0 })
+    }
+
+
+    /// The evdev node (`/dev/input/eventN`) the kernel made for it.
+    pub fn evdev_path(&self) -> Result<PathBuf, Error> {
+        self.handle.evdev_path()
+    }
+
+
+    pub fn destroy(self) -> Result<(), Error> {
+        self.handle.dev_destroy()
+    }
+
+
+    /// Puts the fingers down.
+    fn put_down(&self) -> Result<(), Error> {
+        let mut events = self.position_events(true);
+        events.extend([
+            key(Key::ButtonTouch, true),
+            key(Key::ButtonToolTripleTap, true),
+            sync()
+        ]);
+        self.write(&events)
+    }
+
+
+    /// Moves the fingers a step further around the circle.
+    fn step(&mut self) -> Result<(), Error> {
+        self.angle += STEP_MM / CIRCLE_RADIUS_MM;
+        let mut events = self.position_events(false);
+        events.push(sync());
+        self.write(&events)
+    }
+
+
+    fn lift(&self) -> Result<(), Error> {
+        let mut events = Vec::new();
+        for slot in 0..FINGERS {
+            events.push(abs(AbsoluteAxis::MultitouchSlot, slot as i32));
+            events.push(abs(AbsoluteAxis::MultitouchTrackingId, -1));
+        }
+        events.extend([
+            key(Key::ButtonTouch, false),
+            key(Key::ButtonToolTripleTap, false),
+            sync()
+        ]);
+        self.write(&events)
+    }
+
+
+    /// Where each finger is now, in both the multitouch and (for the
+    /// first finger) the single-touch axes. With `landing`, each finger
+    /// gets its tracking id too.
+    fn position_events(&self, landing: bool) -> Vec<input_event> {
+
+        let centre = (
+            SIZE_MM.0 / 2.0 + CIRCLE_RADIUS_MM * self.angle.cos(),
+            SIZE_MM.1 / 2.0 + CIRCLE_RADIUS_MM * self.angle.sin()
+        );
+        let mut events = Vec::new();
+
+        for slot in 0..FINGERS {
+            let x = to_units(centre.0 + (slot as f64 - 1.0) * FINGER_SPACING_MM);
+            let y = to_units(centre.1);
+            events.push(abs(AbsoluteAxis::MultitouchSlot, slot as i32));
+            if landing {
+                events.push(abs(AbsoluteAxis::MultitouchTrackingId, slot as i32));
+            }
+            events.extend([abs(AbsoluteAxis::MultitouchPositionX, x), abs(AbsoluteAxis::MultitouchPositionY, y)]);
+            if slot == 0 {
+                events.extend([abs(AbsoluteAxis::X, x), abs(AbsoluteAxis::Y, y)]);
+            }
+        }
+        events
+    }
+
+
+    fn write(&self, events: &[input_event]) -> Result<(), Error> {
+        self.handle.write(events).map(|_| ())
+    }
+}
+
+
+fn to_units(mm: f64) -> i32 {
+    (mm * UNITS_PER_MM as f64).round() as i32
+}
+
+fn abs(axis: AbsoluteAxis, value: i32) -> input_event {
+    InputEvent::from(AbsoluteEvent::new(ZERO, axis, value)).into_raw()
+}
+
+fn key(key: Key, pressed: bool) -> input_event {
+    InputEvent::from(KeyEvent::new(ZERO, key, KeyState::pressed(pressed))).into_raw()
+}
+
+fn sync() -> input_event {
+    InputEvent::from(SynchronizeEvent::new(ZERO, SynchronizeKind::Report, 0)).into_raw()
+}
+
+
+/// Whether a device with the given name and IDs is `bench`'s touchpad.
+pub fn is_bench_touchpad(name: &str, vendor: u32, product: u32) -> bool {
+    name == BENCH_TOUCHPAD_NAME
+    && vendor == VIRTUAL_VENDOR_ID as u32
+    && product == BENCH_TOUCHPAD_PRODUCT_ID as u32
+}
+
+
+/// Opens devices like the daemon does, but grabs them too, so only this
+/// libinput context gets their events.
+struct GrabbingInterface;
+
+impl LibinputInterface for GrabbingInterface {
+    fn open_restricted(&mut self, path: &Path, flags: i32) -> Result<OwnedFd, i32> {
+        let fd = libinput_init::Interface.open_restricted(path, flags)?;
+        EvdevHandle::new(fd.as_raw_fd()).grab(true)
+            .map_err(|err| err.raw_os_error().unwrap_or(-1))?;
+        Ok(fd)
+    }
+    fn close_restricted(&mut self, fd: OwnedFd) {
+        drop(File::from(fd));
+    }
+}
+
+
+/// A libinput context reading only the touchpad, grabbed. This waits for
+/// udev to let its node be opened, which takes a moment for a new device.
+pub fn open_grabbed(touchpad: &BenchTouchpad) -> Result<Libinput, Error> {
+
+    let path = touchpad.evdev_path()?;
+    let mut libinput = Libinput::new_from_path(GrabbingInterface);
+    let started = Instant::now();
+
+    let device = loop {
+        if let Some(device) = libinput.path_add_device(&path.to_string_lossy()) {
+            break device;
+        }
+        if started.elapsed() > OPEN_TIMEOUT {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("could not open the benchmark's touchpad ({}); see `linux-3-finger-drag doctor`", path.display())
+            ));
+        }
+        std::thread::sleep(OPEN_RETRY);
+    };
+
+    if !device.has_capability(DeviceCapability::Gesture) {
+        return Err(Error::new(ErrorKind::Unsupported, "libinput doesn't take the benchmark's touchpad for a touchpad"));
+    }
+    Ok(libinput)
+}
+
+
+/// Moves the touchpad's fingers around, timing how long each movement
+/// takes to come out of the virtual trackpad (which `watcher` is on), for
+/// `updates` movements after the first to come through. The fingers are
+/// lifted at the end, or as soon as `should_stop` is set.
+pub fn drive(
+    touchpad: &mut BenchTouchpad,
+    mut watcher: MotionWatcher,
+    updates: u32,
+    should_stop: &AtomicBool
+) -> Result<BenchStats, Error> {
+
+    touchpad.put_down()?;
+    let moved = move_fingers(touchpad, &mut watcher, updates, should_stop);
+    // whatever happened, so the drag ends
+    let lifted = touchpad.lift();
+
+    moved?;
+    lifted?;
+    watcher.finish()
+}
+
+
+fn move_fingers(
+    touchpad: &mut BenchTouchpad,
+    watcher: &mut MotionWatcher,
+    updates: u32,
+    should_stop: &AtomicBool
+) -> Result<(), Error> {
+
+    let mut step = |watcher: &mut MotionWatcher| -> Result<(), Error> {
+        std::thread::sleep(FRAME_INTERVAL);
+        let sent = Instant::now();
+        touchpad.step()?;
+        watcher.wait_for_motion(sent);
+        Ok(())
+    };
+
+    for _ in 0..WARM_UP_FRAMES {
+        if should_stop.load(Ordering::Relaxed) || watcher.came_through() > 0 {
+            break;
+        }
+        step(watcher)?;
+    }
+    if should_stop.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    if watcher.came_through() == 0 {
+        return Err(Error::new(
+            ErrorKind::TimedOut,
+            "no drag came out of the virtual trackpad (does the config drag with three fingers?)"
+        ));
+    }
+
+    watcher.reset();
+    for _ in 0..updates {
+        if should_stop.load(Ordering::Relaxed) {
+            break;
+        }
+        step(watcher)?;
+    }
+    Ok(())
+}
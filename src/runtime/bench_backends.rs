@@ -0,0 +1,179 @@
+// `bench-backends`: the same made-up drag (see `simulate`) through each
+// way of injecting drags there is, timing how long each motion update
+// takes to come out of the virtual device's evdev node, so users can pick
+// the one that works best for their session.
+//
+// Like `--self-test`, the virtual device's evdev node is grabbed for the
+// run, so the drags only reach the benchmark, not the desktop. That also
+// means this times getting the events through the kernel, not how long the
+// compositor takes to act on them.
+//
+// libei, XTest, and the RemoteDesktop portal were asked for here too, but
+// only uinput exists so far (as a pointer or a touchscreen, with
+// `outputMode`), so those are reported as unavailable.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{Error, ErrorKind},
+    path::Path,
+    time::{Duration, Instant}
+};
+
+use input_linux::{EventKind, EventTime, EvdevHandle, InputEvent, SynchronizeEvent, SynchronizeKind};
+
+use super::self_test::open_evdev;
+
+
+// the drag benchmarked, in trackpad units and time: big enough for every
+// update to move at least a unit, with default settings
+pub const BENCH_DRAG: (f64, f64) = (300.0, 150.0);
+pub const BENCH_DURATION: Duration = Duration::from_millis(1000);
+
+// an update that hasn't come out by then is counted as lost
+const MOTION_TIMEOUT: Duration = Duration::from_millis(100);
+const POLL_INTERVAL: Duration = Duration::from_micros(200);
+
+// what's listed but can't be benchmarked yet, and why
+pub const UNAVAILABLE_BACKENDS: &[(&str, &str)] = &[
+    ("libei", "not supported yet"),
+    ("XTest", "not supported yet"),
+    ("portal", "not supported yet")
+];
+
+
+/// Watches a virtual device's evdev node for the motion a benchmarked
+/// drag writes to it, timing each update.
+pub struct MotionWatcher {
+    evdev: EvdevHandle<File>,
+    latencies: Vec<Duration>,
+    lost: usize,
+    failure: Option<Error>
+}
+
+impl MotionWatcher {
+
+    /// Opens and grabs the evdev node at `path`.
+    pub fn open(path: &Path) -> Result<MotionWatcher, Error> {
+        let evdev = EvdevHandle::new(open_evdev(path)?);
+        evdev.grab(true)
+            .map_err(|e| Error::new(e.kind(), format!("Could not grab {:?}, so the benchmark's drags \
+                would reach the desktop: {}", path, e)))?;
+
+        Ok(MotionWatcher { evdev, latencies: Vec::new(), lost: 0, failure: None })
+    }
+
+
+    /// Waits for the next frame with motion in it, noting how long after
+    /// `sent` it came (or that it never did).
+    pub fn wait_for_motion(&mut self, sent: Instant) {
+        if self.failure.is_some() {
+            return;
+        }
+
+        let (key, relative, absolute, sync) = (
+            EventKind::Key as u16, EventKind::Relative as u16, EventKind::Absolute as u16, EventKind::Synchronize as u16
+        );
+        // anything will do to fill the buffer with, since it's read over
+        let filler = InputEvent::from(SynchronizeEvent::new(EventTime::new(0, 0), SynchronizeKind::Report, 0)).into_raw();
+        let mut buffer = [filler; 16];
+        let (mut moved, mut pressed) = (false, false);
+
+        while sent.elapsed() < MOTION_TIMEOUT {
+            let read = match self.evdev.read(&mut buffer) {
+                Ok(read) => read,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(POLL_INTERVAL);
+                    continue;
+                },
+                Err(e) => {
+                    self.failure = Some(e);
+                    return;
+                }
+            };
+
+            // frames with a button press or release in them (like the
+            // touchscreen's touch down, which has a position too) don't count
+            for event in &buffer[..read] {
+                match event.type_ {
+                    t if t == key => pressed = true,
+                    t if t == relative || t == absolute => moved = true,
+                    t if t == sync => {
+                        if moved && !pressed {
+                            self.latencies.push(sent.elapsed());
+                            return;
+                        }
+                        (moved, pressed) = (false, false);
+                    },
+                    _ => {}
+                }
+            }
+        }
+
+        self.lost += 1;
+    }
+
+
+    /// How many updates have come through so far.
+    pub fn came_through(&self) -> usize {
+        self.latencies.len()
+    }
+
+
+    /// Forgets the updates so far, for starting the timing over.
+    pub fn reset(&mut self) {
+        self.latencies.clear();
+        self.lost = 0;
+    }
+
+
+    /// Lets go of the evdev node, and sums up the timings.
+    pub fn finish(self) -> Result<BenchStats, Error> {
+        let _ = self.evdev.grab(false);
+        if let Some(e) = self.failure {
+            return Err(e);
+        }
+        BenchStats::from_latencies(self.latencies, self.lost)
+            .ok_or_else(|| Error::new(ErrorKind::TimedOut, "none of the drag's motion came out"))
+    }
+}
+
+
+/// How a backend did.
+#[derive(Debug, Clone)]
+pub struct BenchStats {
+    pub updates: usize,
+    pub lost: usize,
+    pub median: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration
+}
+
+impl BenchStats {
+    fn from_latencies(mut latencies: Vec<Duration>, lost: usize) -> Option<BenchStats> {
+        latencies.sort();
+        let max = *latencies.last()?;
+        let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+
+        Some(
+            BenchStats {
+                updates: latencies.len() + lost,
+                lost,
+                median: percentile(50),
+                p95: percentile(95),
+                p99: percentile(99),
+                max
+            }
+        )
+    }
+}
+
+impl fmt::Display for BenchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f, "{}/{} updates came through; median {:.2?}, 95th percentile {:.2?}, 99th {:.2?}, max {:.2?}",
+            self.updates - self.lost, self.updates, self.median, self.p95, self.p99, self.max
+        )
+    }
+}
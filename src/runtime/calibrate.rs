@@ -0,0 +1,252 @@
+// The `calibrate` wizard: instead of guessing at `acceleration` and
+// `dragEndDelay` and restarting to try each guess, the user performs a
+// few gestures, and the values are worked out from what the trackpad
+// actually reports.
+//
+// - `acceleration` comes from full-width swipes: it's whatever makes
+//   one swipe across the whole trackpad drag across the whole screen.
+//   The swipes are measured as drags would be (through the configured
+//   `accelerationProfile`), and the middle one counts, so one swipe
+//   that stops short doesn't throw the suggestion off.
+// - `dragEndDelay` comes from how long the user takes to lift and
+//   re-place their fingers mid-drag, with some headroom on top.
+
+use std::{
+    io::{Error, Write},
+    time::{Duration, Instant}
+};
+
+use serde_json::json;
+
+use super::{
+    accel_curve::AccelCurve,
+    desktop_speed,
+    event_handler::{Gesture, MM_PER_UNIT},
+    gesture_reader::GestureReader,
+    output_scale
+};
+use crate::init::config::{self, Configuration, SensitivityUnits};
+
+
+pub const DEFAULT_SWIPES: usize = 3;
+const REPOSITIONS: usize = 3;
+
+// a swipe this much shorter than the middle one so far likely didn't go
+// from edge to edge, so it's done again
+const SHORT_SWIPE: f64 = 0.7;
+// and swipes further apart than this (from the middle one, either way)
+// are worth a warning that the suggestion may be off
+const SPREAD_WARNING: f64 = 0.15;
+
+// if the fingers are gone longer than this, that wasn't a reposition,
+// just a pause between attempts
+const MAX_REPOSITION_GAP: Duration = Duration::from_secs(3);
+
+// on top of the slowest reposition, since nobody repositions
+// at exactly the same speed every time
+const DELAY_HEADROOM: f64 = 1.25;
+const DELAY_ROUNDING_MS: u64 = 50;
+
+const FALLBACK_SCREEN_WIDTH: f64 = 1920.0;
+
+
+/// Runs the whole wizard, reading gestures from `reader` (with `swipes`
+/// full-width swipes for the speed) and asking questions on the terminal,
+/// then saves the results to the config file if the user agrees.
+pub async fn run_calibration(reader: &mut GestureReader, cfg: &Configuration, swipes: usize) -> Result<(), Error> {
+
+    println!("This will work out an `acceleration` and `dragEndDelay` that suit you \
+        and your trackpad, from a few gestures. Nothing is dragged while calibrating.\n");
+
+    let screen_width = ask_screen_width()?;
+
+    println!("\nStep 1 of 2: speed.\n\
+        Swipe three fingers across the whole width of your trackpad, from the left edge \
+        to the right edge, {swipes} times.");
+    reader.discard_pending()?;
+
+    let mut swipe_widths = Vec::with_capacity(swipes);
+    while swipe_widths.len() < swipes {
+        let width = measure_swipe(reader, cfg).await?;
+        if median(&swipe_widths).is_some_and(|middle| width < middle * SHORT_SWIPE) {
+            println!("  that one was a lot shorter than the others ({:.0} units); \
+                make sure to go from edge to edge, and try again", width);
+            continue;
+        }
+        println!("  swipe {}: {:.0} units", swipe_widths.len() + 1, width);
+        swipe_widths.push(width);
+    }
+    let spread = spread(&swipe_widths);
+    if spread > SPREAD_WARNING {
+        println!("  (your swipes were up to {:.0}% off from the middle one, so the suggestion may \
+            be a little off too; running calibrate again with more swipes, like \
+            `calibrate --swipes 6`, evens that out)", spread * 100.0);
+    }
+    let acceleration = recommend_acceleration(&swipe_widths, screen_width, cfg);
+
+    println!("\nStep 2 of 2: drag end delay.\n\
+        Do a long three-finger drag, the way you'd move a file across the screen: swipe, \
+        lift your fingers, put them back down, and keep going. Lift and re-place your \
+        fingers {REPOSITIONS} times.");
+    reader.discard_pending()?;
+
+    let mut gaps = Vec::with_capacity(REPOSITIONS);
+    while gaps.len() < REPOSITIONS {
+        let gap = measure_reposition(reader).await?;
+        println!("  reposition {}: {}ms", gaps.len() + 1, gap.as_millis());
+        gaps.push(gap);
+    }
+    let drag_end_delay = recommend_drag_end_delay(&gaps);
+
+    println!("\nRecommended settings:\n\
+        \x20 acceleration: {:.2} (currently {:.2})\n\
+        \x20 dragEndDelay: {} (currently {})",
+        acceleration, cfg.acceleration,
+        drag_end_delay.as_millis(), cfg.drag_end_delay.as_millis()
+    );
+
+    let config_path = config::get_config_file_path()?;
+    let answer = prompt(&format!("\nSave these to {}? [y/N] ", config_path.display()))?;
+    if !answer.eq_ignore_ascii_case("y") && !answer.eq_ignore_ascii_case("yes") {
+        println!("Nothing was saved.");
+        return Ok(());
+    }
+
+    config::persist_value("acceleration", json!(acceleration))?;
+    config::persist_value("dragEndDelay", json!(drag_end_delay.as_millis() as u64))?;
+    println!("Saved! Start linux-3-finger-drag again to use them.");
+
+    Ok(())
+}
+
+
+fn prompt(question: &str) -> Result<String, Error> {
+    print!("{question}");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+
+/// Asks for the screen width in (logical) pixels, suggesting the
+/// focused output's width where the compositor can tell us.
+fn ask_screen_width() -> Result<f64, Error> {
+
+    let detected = output_scale::focused_output_width();
+    let suggestion = detected.unwrap_or(FALLBACK_SCREEN_WIDTH);
+
+    loop {
+        let answer = prompt(&format!("How wide is your screen, in pixels? [{suggestion:.0}] "))?;
+        if answer.is_empty() {
+            return Ok(suggestion);
+        }
+        match answer.parse::<f64>() {
+            Ok(width) if width.is_finite() && width > 0.0 => return Ok(width),
+            _ => println!("That doesn't look like a width; try a number like 1920.")
+        }
+    }
+}
+
+
+/// Waits for a whole three-finger swipe, returning how far it went
+/// sideways, with `accelerationProfile` applied as it is for drags.
+async fn measure_swipe(reader: &mut GestureReader, cfg: &Configuration) -> Result<f64, Error> {
+
+    let mut curve = AccelCurve::default();
+    let mut total_dx = None;
+    loop {
+        let (_, event) = reader.next_event().await?;
+        let gesture = curve.apply(cfg.acceleration_profile, &event, Gesture::from_event(&event, false));
+        match (gesture, total_dx.as_mut()) {
+            (Gesture::SwipeBegin, _) => total_dx = Some(0.0),
+            (Gesture::SwipeUpdate { dx, .. }, Some(total)) => *total += dx,
+            (Gesture::SwipeEnd { .. }, Some(total)) => return Ok(total.abs()),
+            _ => {}
+        }
+    }
+}
+
+
+/// Waits for the fingers to lift at the end of a swipe and land again,
+/// returning how long they were gone.
+async fn measure_reposition(reader: &mut GestureReader) -> Result<Duration, Error> {
+
+    let mut lifted_at: Option<Instant> = None;
+    loop {
+        let (at, gesture) = reader.next().await?;
+        match gesture {
+            Gesture::SwipeEnd { .. } => lifted_at = Some(at),
+            Gesture::HoldBegin | Gesture::SwipeBegin => {
+                if let Some(gap) = lifted_at.take().map(|lifted| at - lifted) {
+                    if gap <= MAX_REPOSITION_GAP {
+                        return Ok(gap);
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+
+/// The middle of `values`, if there are any.
+fn median(values: &[f64]) -> Option<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let middle = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        n if n % 2 == 0 => Some((sorted[middle - 1] + sorted[middle]) / 2.0),
+        _ => Some(sorted[middle])
+    }
+}
+
+
+/// How far the furthest of `values` is from their middle, as a fraction of it.
+fn spread(values: &[f64]) -> f64 {
+    match median(values) {
+        Some(middle) if middle > 0.0 => values.iter()
+            .map(|value| (value - middle).abs() / middle)
+            .fold(0.0, f64::max),
+        _ => 0.0
+    }
+}
+
+
+/// The acceleration that makes the middle full-width swipe cover the
+/// screen's width. The desktop's pointer speed is multiplied in on top
+/// of `acceleration` when `syncDesktopSpeed` is on, so that's divided
+/// back out here. With `sensitivityUnits` in millimeters, the swipes
+/// are measured in those too.
+fn recommend_acceleration(swipe_widths: &[f64], screen_width: f64, cfg: &Configuration) -> f64 {
+
+    let Some(mut width) = median(swipe_widths) else { return cfg.acceleration };
+    if cfg.sensitivity_units == SensitivityUnits::Mm {
+        width *= MM_PER_UNIT;
+    }
+    if width <= 0.0 {
+        return cfg.acceleration;
+    }
+
+    let desktop_factor = if cfg.sync_desktop_speed {
+        desktop_speed::current_factor().unwrap_or(1.0)
+    } else {
+        1.0
+    };
+
+    let acceleration = screen_width / width / desktop_factor;
+    (acceleration * 100.0).round() / 100.0
+}
+
+
+/// The slowest reposition, plus some headroom, rounded up.
+fn recommend_drag_end_delay(gaps: &[Duration]) -> Duration {
+
+    let slowest = gaps.iter().max().copied().unwrap_or_default();
+    let millis = (slowest.as_millis() as f64 * DELAY_HEADROOM).ceil() as u64;
+    let rounded = millis.div_ceil(DELAY_ROUNDING_MS) * DELAY_ROUNDING_MS;
+
+    Duration::from_millis(rounded)
+}
@@ -0,0 +1,51 @@
+// What a running instance can do, and what it's bound to, for settings
+// front-ends to adapt to (e.g. greying out options that need a backend
+// that isn't there). This is the `capabilities` request on the control
+// socket, answered with a JSON object, and the same goes out as D-Bus
+// properties (see `dbus_properties`).
+
+use serde::Serialize;
+
+use crate::init::{config::LIVE_OPTIONS, sandbox::{self, Sandbox}};
+
+
+/// Which ways of injecting drags there are. Only uinput is implemented
+/// so far; the others are listed so front-ends can check for them once
+/// they exist, without having to handle the fields appearing.
+#[derive(Serialize, Debug, Clone)]
+pub struct Backends {
+    pub uinput: bool,
+    pub libei: bool,
+    pub portal: bool
+}
+
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    pub version: &'static str,
+    pub backends: Backends,
+    pub devices: Vec<String>,               // trackpads being read, by name
+    pub live_options: &'static [&'static str],  // config options that take effect without a restart
+    pub sandbox: Option<String>             // Flatpak, Snap, or the kind of container, if in one
+}
+
+impl Capabilities {
+
+    /// The running instance's capabilities, with `devices` as the
+    /// trackpads it has open.
+    pub fn current(devices: &[String]) -> Capabilities {
+        Capabilities {
+            version: env!("CARGO_PKG_VERSION"),
+            // if this is running, the virtual trackpad was made
+            backends: Backends { uinput: true, libei: false, portal: false },
+            devices: devices.to_vec(),
+            live_options: LIVE_OPTIONS,
+            sandbox: sandbox::detect_sandbox().map(|sandbox| match sandbox {
+                Sandbox::Flatpak => "flatpak".to_string(),
+                Sandbox::Snap => "snap".to_string(),
+                Sandbox::Container(kind) => kind
+            })
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// The clock the translator's gesture timing goes by (the drag end delay,
+// debouncing, holds, `clickSuppressTime`, and so on), so it can be swapped
+// for one that tests move by hand, jumps included.
+//
+// The real one is `CLOCK_MONOTONIC` (by way of tokio's `Instant`, which is
+// std's, unless a test has paused tokio's time, in which case the two move
+// together with the drag end timer). It never follows the wall clock, so
+// NTP corrections or the time being set by hand can't stretch or skip a
+// delay, and it stands still during suspend, so a delay running when the
+// laptop is suspended picks up where it left off on resume.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant}
+};
+
+
+#[derive(Debug, Clone, Default)]
+pub struct Clock(Option<Arc<Mutex<Instant>>>);   // None for the real clock
+
+impl Clock {
+
+    /// The real, monotonic clock.
+    pub fn monotonic() -> Clock {
+        Clock(None)
+    }
+
+
+    /// A clock that stays at `start` until it's `set()` (or `advance()`d).
+    /// Clones share the time, so one can be handed to the translator and
+    /// still be moved from outside.
+    pub fn manual(start: Instant) -> Clock {
+        Clock(Some(Arc::new(Mutex::new(start))))
+    }
+
+
+    pub fn now(&self) -> Instant {
+        match &self.0 {
+            Some(time) => *time.lock().unwrap_or_else(|e| e.into_inner()),
+            None => tokio::time::Instant::now().into_std()
+        }
+    }
+
+
+    /// How long it's been since `earlier`, or nothing if the clock has
+    /// been set back past it.
+    pub fn since(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+
+
+    /// Moves a manual clock to `to`, forward or back. The real clock
+    /// can't be moved, so it's left alone.
+    pub fn set(&self, to: Instant) {
+        if let Some(time) = &self.0 {
+            *time.lock().unwrap_or_else(|e| e.into_inner()) = to;
+        }
+    }
+
+
+    pub fn advance(&self, by: Duration) {
+        self.set(self.now() + by);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock_only_moves_when_told() {
+        let start = Instant::now();
+        let clock = Clock::manual(start);
+        let shared = clock.clone();
+
+        assert_eq!(clock.now(), start);
+        shared.advance(Duration::from_millis(250));
+        assert_eq!(clock.since(start), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn setting_it_back_never_goes_negative() {
+        let start = Instant::now() + Duration::from_secs(60);
+        let clock = Clock::manual(start);
+
+        clock.set(start - Duration::from_secs(30));
+        assert_eq!(clock.since(start), Duration::ZERO);
+    }
+}
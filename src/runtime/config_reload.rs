@@ -0,0 +1,177 @@
+// Reloading the config file into a running instance: whenever it's saved
+// (watched with inotify), on SIGHUP (for `kill -HUP`, and for when
+// watching isn't possible), and on the `reload` command. The new config goes through the same channel
+// as a session change's does (see `sessions`), so the main loop swaps it
+// in with `apply_config()`, keeping the virtual trackpad and any drag in
+// progress.
+//
+// The config file's folder is watched, rather than the file itself, since
+// most editors save by writing a new file and moving it over the old one.
+// So is its folder of drop-ins, if it has one when this starts.
+//
+// A config that doesn't load is logged and ignored, keeping the one
+// that's running, rather than falling back on the defaults like at startup.
+
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration
+};
+
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor};
+use signal_hook::{consts::SIGHUP, iterator::Signals};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::init::config::{self, Configuration, Preset};
+use super::sessions;
+
+
+// how long to let an editor finish saving before reading what it saved
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+
+/// Loads the config file again: the user's own, or for a system-wide
+/// instance, the active user's (if they have one) or else the system's.
+pub fn reload_config(system: bool, preset_override: Option<Preset>) -> Result<Configuration, std::io::Error> {
+
+    if !system {
+        return config::parse_config_file_with_preset(preset_override);
+    }
+
+    let system_cfg = config::parse_config_file_at(&config::system_config_path(), preset_override)?;
+    Ok(
+        match sessions::active_session().filter(sessions::ActiveSession::is_user_session) {
+            Some(session) => sessions::config_for(&session, &system_cfg, preset_override),
+            None => system_cfg
+        }
+    )
+}
+
+
+/// Where a running instance's config comes from, for the main loop to
+/// load it again when `reload` asks it to.
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigSource {
+    pub system: bool,
+    pub preset_override: Option<Preset>
+}
+
+impl ConfigSource {
+    pub fn reload(&self) -> Result<Configuration, std::io::Error> {
+        reload_config(self.system, self.preset_override)
+    }
+}
+
+
+/// Reloads the config every time the process gets a SIGHUP, sending it to
+/// the main loop through `config_tx`.
+pub fn reload_on_sighup(
+    config_tx: mpsc::Sender<Configuration>,
+    system: bool,
+    preset_override: Option<Preset>
+) -> Result<(), std::io::Error> {
+
+    let mut signals = Signals::new([SIGHUP])?;
+
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            info!("Got SIGHUP; reloading the config file");
+            match reload_config(system, preset_override) {
+                Ok(cfg) => if config_tx.blocking_send(cfg).is_err() {
+                    return;
+                },
+                Err(e) => warn!("Keeping the current config, since the config file could not be reloaded: {}", e)
+            }
+        }
+    });
+
+    Ok(())
+}
+
+
+/// The config file a running instance was loaded from.
+fn config_file(system: bool) -> Result<PathBuf, std::io::Error> {
+    if system {
+        Ok(config::system_config_path())
+    } else {
+        config::get_config_file_path()
+    }
+}
+
+
+/// What's in the config file and its drop-ins right now, for telling
+/// whether anything actually changed.
+fn config_contents(system: bool) -> Vec<Option<String>> {
+    let Ok(path) = config_file(system) else { return Vec::new() };
+    [path.clone()].into_iter()
+        .chain(config::drop_in_files(&path))
+        .map(|path| read_to_string(path).ok())
+        .collect()
+}
+
+
+/// Reloads the config every time the config file (or a drop-in) is saved, sending it to
+/// the main loop through `config_tx`. For a system-wide instance, that's
+/// the system's config file; a user's own is only loaded again when their
+/// session comes to the front, or on SIGHUP.
+pub fn reload_on_change(
+    config_tx: mpsc::Sender<Configuration>,
+    system: bool,
+    preset_override: Option<Preset>
+) -> Result<(), std::io::Error> {
+
+    let dir = config_file(system)?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let changes = AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_TO | AddWatchFlags::IN_DELETE;
+    let inotify = Inotify::init(InitFlags::IN_CLOEXEC)?;
+    inotify.add_watch(&dir, changes)?;
+    let drop_ins = inotify.add_watch(&dir.join(config::DROP_IN_DIR_NAME), changes).ok();
+    info!("Watching {:?} for changes to the config file", dir);
+
+    thread::spawn(move || {
+        // saving without changing anything (or several events for one
+        // save) doesn't need to reload anything
+        let mut last_contents = config_contents(system);
+
+        loop {
+            let events = match inotify.read_events() {
+                Ok(events) => events,
+                Err(e) => {
+                    warn!("Stopped watching the config file for changes: {}", e);
+                    return;
+                }
+            };
+            if !events.iter().any(|event| is_config_event(event, drop_ins)) {
+                continue;
+            }
+
+            thread::sleep(SETTLE_TIME);
+            let contents = config_contents(system);
+            if contents == last_contents {
+                continue;
+            }
+            last_contents = contents;
+
+            info!("The config file changed; reloading it");
+            match reload_config(system, preset_override) {
+                Ok(cfg) => if config_tx.blocking_send(cfg).is_err() {
+                    return;
+                },
+                Err(e) => warn!("Keeping the current config, since the config file could not be reloaded: {}", e)
+            }
+        }
+    });
+
+    Ok(())
+}
+
+
+fn is_config_event(event: &InotifyEvent, drop_ins: Option<WatchDescriptor>) -> bool {
+    Some(event.wd) == drop_ins || event.name.as_ref()
+        .and_then(|name| name.to_str())
+        .is_some_and(config::is_config_file_name)
+}
@@ -0,0 +1,141 @@
+// The running instance's capabilities (see `capabilities`), as read-only
+// properties on the session bus, for settings front-ends that would
+// rather introspect D-Bus than talk to the control socket. They go on the
+// same object and interface as `dbusDragEvents`' signals, under the name
+// `io.github.lmr97.LinuxThreeFingerDrag`:
+//
+//     busctl --user introspect io.github.lmr97.LinuxThreeFingerDrag /io/github/lmr97/LinuxThreeFingerDrag
+//
+// The trackpads come from the main loop, by way of the same `capabilities`
+// request the control socket sends, so the two never disagree; they can
+// change while running, so they're marked as not to be cached. The rest
+// is fixed for as long as the instance runs.
+
+use std::{collections::HashMap, io::Error};
+
+use tokio::sync::mpsc::Sender;
+use tracing::{debug, info, warn};
+use zbus::{connection, fdo, interface};
+
+use super::{
+    capabilities::Capabilities,
+    dbus_signals::{INTERFACE, OBJECT_PATH},
+    ipc::{self, IpcMessage, IpcRequest}
+};
+
+
+pub const BUS_NAME: &str = "io.github.lmr97.LinuxThreeFingerDrag";
+
+
+struct CapabilityProperties {
+    fixed: Capabilities,            // everything but the devices
+    requests: Sender<IpcMessage>    // for asking the main loop for those
+}
+
+#[interface(name = "io.github.lmr97.LinuxThreeFingerDrag")]
+impl CapabilityProperties {
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn version(&self) -> String {
+        self.fixed.version.to_string()
+    }
+
+    /// Which ways of injecting drags there are, by name.
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn backends(&self) -> HashMap<String, bool> {
+        HashMap::from([
+            ("uinput".to_string(), self.fixed.backends.uinput),
+            ("libei".to_string(), self.fixed.backends.libei),
+            ("portal".to_string(), self.fixed.backends.portal)
+        ])
+    }
+
+    /// The trackpads being read, by name.
+    #[zbus(property(emits_changed_signal = "false"))]
+    async fn devices(&self) -> fdo::Result<Vec<String>> {
+        let response = ipc::forward_request(IpcRequest::Capabilities, &self.requests).await;
+        if !response.ok {
+            return Err(fdo::Error::Failed(response.message));
+        }
+        let current: serde_json::Value = serde_json::from_str(&response.message)
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+        serde_json::from_value(current["devices"].clone()).map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Config options that take effect without a restart.
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn live_options(&self) -> Vec<String> {
+        self.fixed.live_options.iter().map(|option| option.to_string()).collect()
+    }
+
+    /// Flatpak, Snap, or the kind of container, if in one, or else empty
+    /// (D-Bus has no null).
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn sandbox(&self) -> String {
+        self.fixed.sandbox.clone().unwrap_or_default()
+    }
+}
+
+
+/// Serves the properties on the session bus for as long as the instance
+/// runs, answering for the devices by asking the main loop through
+/// `requests`. Without a session bus, this only warns (and with the name
+/// already taken, only says so in the debug log), since nothing else
+/// depends on it.
+pub async fn serve_capabilities(requests: Sender<IpcMessage>) {
+
+    let properties = CapabilityProperties { fixed: Capabilities::current(&[]), requests };
+    let served = async {
+        connection::Builder::session()?
+            .name(BUS_NAME)?
+            .serve_at(OBJECT_PATH, properties)?
+            .build()
+            .await
+    };
+
+    match served.await {
+        Ok(connection) => {
+            info!("Serving capabilities on the session bus as {} ({})", BUS_NAME, INTERFACE);
+            // the properties are only served while the connection is open
+            std::future::pending::<()>().await;
+            drop(connection);
+        },
+        Err(zbus::Error::NameTaken) => debug!("{} is already taken on the session bus; not serving capabilities there", BUS_NAME),
+        Err(e) => warn!("Could not serve capabilities on the session bus: {}", Error::other(e))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::runtime::ipc::IpcResponse;
+
+    #[tokio::test]
+    async fn devices_come_from_the_main_loop() {
+        let (requests, mut main_loop) = mpsc::channel::<IpcMessage>(3);
+        tokio::spawn(async move {
+            while let Some(message) = main_loop.recv().await {
+                assert!(matches!(message.request, IpcRequest::Capabilities));
+                let capabilities = Capabilities::current(&["ELAN0670:00 04F3:3150 Touchpad".to_string()]);
+                let _ = message.reply.send(IpcResponse::ok(serde_json::to_string(&capabilities).unwrap()));
+            }
+        });
+
+        let properties = CapabilityProperties { fixed: Capabilities::current(&[]), requests };
+        assert_eq!(properties.devices().await.unwrap(), ["ELAN0670:00 04F3:3150 Touchpad"]);
+        assert_eq!(properties.version(), env!("CARGO_PKG_VERSION"));
+        assert_eq!(properties.backends().get("uinput"), Some(&true));
+    }
+
+    #[tokio::test]
+    async fn devices_fail_once_the_main_loop_is_gone() {
+        let (requests, main_loop) = mpsc::channel::<IpcMessage>(3);
+        drop(main_loop);
+
+        let properties = CapabilityProperties { fixed: Capabilities::current(&[]), requests };
+        assert!(properties.devices().await.is_err());
+    }
+}
@@ -0,0 +1,68 @@
+// `DragStarted` and `DragEnded` D-Bus signals, with `dbusDragEvents`, for
+// status bars, screen recorders, accessibility helpers and the like that
+// want to know when a drag is holding the button, on any desktop (unlike
+// `hyprlandDragEvents`). They're sent on the session bus (or the system
+// bus, for `--system`) with `busctl`, which comes with systemd, so this
+// doesn't need a D-Bus library for two signals without arguments. There's
+// no service to call methods on; anything listening just matches them:
+//
+//     dbus-monitor "type='signal',interface='io.github.lmr97.LinuxThreeFingerDrag'"
+
+use std::{io::Error, process::Command};
+
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tracing::{debug, warn};
+
+
+pub const OBJECT_PATH: &str = "/io/github/lmr97/LinuxThreeFingerDrag";
+pub const INTERFACE: &str   = "io.github.lmr97.LinuxThreeFingerDrag";
+
+const DRAG_STARTED: &str = "DragStarted";
+const DRAG_ENDED: &str   = "DragEnded";
+
+
+/// Sends a signal whenever a drag starts or ends, for as long as the
+/// virtual trackpad is around, on the system bus with `system`.
+pub async fn forward_drag_events(mut button_events: Receiver<bool>, system: bool) {
+
+    let mut last_held = false;
+    let mut warned = false;
+    loop {
+        let held = match button_events.recv().await {
+            Ok(held) => held,
+            // as with Hyprland's, only the latest one matters
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return
+        };
+
+        if held == last_held { continue; }
+        last_held = held;
+
+        let signal = if held { DRAG_STARTED } else { DRAG_ENDED };
+        let sent = tokio::task::spawn_blocking(move || emit(signal, system)).await;
+
+        match sent {
+            Ok(Err(e)) if !warned => {
+                warn!("Could not send the {} D-Bus signal (for dbusDragEvents): {}", signal, e);
+                warned = true;
+            },
+            Ok(Err(e)) => debug!("Could not send the {} D-Bus signal: {}", signal, e),
+            _ => ()
+        }
+    }
+}
+
+
+fn emit(signal: &str, system: bool) -> Result<(), Error> {
+
+    let output = Command::new("busctl")
+        .arg(if system { "--system" } else { "--user" })
+        .args(["emit", OBJECT_PATH, INTERFACE, signal])
+        .output()?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+    }
+}
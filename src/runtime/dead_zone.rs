@@ -0,0 +1,68 @@
+// `deadZone`: holding back the small, back-and-forth motion of fingers
+// trembling on the trackpad, so a drag held still (like at the end of a
+// text selection) doesn't wobble by a pixel or two. Motion is summed
+// while the dead zone is armed, and only let through once the sum gets
+// past it, so tremor, which mostly cancels itself out, never does. After
+// that, motion goes through as it comes, until the fingers have stayed
+// within the dead zone for a while again, which arms it once more.
+
+use std::time::{Duration, Instant};
+
+
+// how long the fingers have to stay within the dead zone for it to be
+// armed again, mid-drag
+const REARM_TIME: Duration = Duration::from_millis(200);
+
+
+#[derive(Debug)]
+pub struct DeadZone {
+    armed: bool,
+    held: (f64, f64),               // motion summed while armed
+    window_start: Option<Instant>,  // while not, for checking whether to arm it again
+    window_motion: (f64, f64)
+}
+
+impl Default for DeadZone {
+    fn default() -> Self {
+        DeadZone { armed: true, held: (0.0, 0.0), window_start: None, window_motion: (0.0, 0.0) }
+    }
+}
+
+impl DeadZone {
+
+    /// Arms the dead zone for a new swipe.
+    pub fn begin(&mut self) {
+        *self = DeadZone::default();
+    }
+
+
+    /// The motion to go through for (`dx`, `dy`), coming in at `now`, with
+    /// a dead zone of `size`, or `None` if it's held back.
+    pub fn filter(&mut self, now: Instant, size: f64, dx: f64, dy: f64) -> Option<(f64, f64)> {
+
+        if size <= 0.0 {
+            return Some((dx, dy));
+        }
+
+        if self.armed {
+            self.held.0 += dx;
+            self.held.1 += dy;
+            if self.held.0.hypot(self.held.1) < size {
+                return None;
+            }
+            self.armed = false;
+            self.window_start = Some(now);
+            self.window_motion = (0.0, 0.0);
+            return Some(std::mem::take(&mut self.held));
+        }
+
+        self.window_motion.0 += dx;
+        self.window_motion.1 += dy;
+        if self.window_start.is_some_and(|start| now.saturating_duration_since(start) >= REARM_TIME) {
+            self.armed = self.window_motion.0.hypot(self.window_motion.1) < size;
+            self.window_start = Some(now);
+            self.window_motion = (0.0, 0.0);
+        }
+        Some((dx, dy))
+    }
+}
@@ -0,0 +1,167 @@
+// Keeps the drag speed in line with the pointer speed set in the desktop's
+// own touchpad settings. Desktops keep this setting on libinput's scale,
+// -1.0 (slowest) to 1.0 (fastest), with 0.0 as libinput's default, so
+// that's the scale everything here works in.
+//
+// There's no one place to read the setting from, so each desktop
+// gets its own reader:
+//
+//   - GNOME: the `org.gnome.desktop.peripherals.touchpad speed` gsetting
+//   - KDE:   the `PointerAcceleration` key for the touchpad in `kcminputrc`
+//   - Sway:  the touchpad's `accel_speed`, as reported by `swaymsg`
+
+use std::{
+    fs::read_to_string,
+    path::PathBuf,
+    process::Command,
+    time::Duration
+};
+
+use tokio::sync::watch::Sender;
+use tracing::{debug, info, warn};
+
+
+// Settings changes are rare, and users won't notice a few seconds
+// of lag on one, so there's no need to check very often.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+
+#[derive(Debug, Clone, Copy)]
+enum Desktop { Gnome, Kde, Sway }
+
+
+fn detect_desktop() -> Option<Desktop> {
+
+    if std::env::var_os("SWAYSOCK").is_some() {
+        return Some(Desktop::Sway);
+    }
+
+    let current_desktop = std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_uppercase();
+
+    if current_desktop.contains("GNOME") {
+        Some(Desktop::Gnome)
+    } else if current_desktop.contains("KDE") {
+        Some(Desktop::Kde)
+    } else {
+        None
+    }
+}
+
+
+/// Converts libinput's speed setting into a multiplier for the drag
+/// motion: 0.5x at the slowest setting, 2x at the fastest, and 1x
+/// (no change) at the default.
+pub fn speed_to_factor(speed: f64) -> f64 {
+    2f64.powf(speed.clamp(-1.0, 1.0))
+}
+
+
+/// The multiplier for the desktop's current pointer speed, if 
+/// it's a desktop we know how to ask.
+pub fn current_factor() -> Option<f64> {
+    read_pointer_speed(detect_desktop()?).map(speed_to_factor)
+}
+
+
+fn read_pointer_speed(desktop: Desktop) -> Option<f64> {
+    match desktop {
+        Desktop::Gnome => read_gnome_speed(),
+        Desktop::Kde   => read_kde_speed(),
+        Desktop::Sway  => read_sway_speed()
+    }
+}
+
+
+fn read_gnome_speed() -> Option<f64> {
+    let output = Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.peripherals.touchpad", "speed"])
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<f64>()
+        .ok()
+}
+
+
+fn read_kde_speed() -> Option<f64> {
+    let config_folder = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(config_dir) => PathBuf::from(config_dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config")
+    };
+    let kcminputrc = read_to_string(config_folder.join("kcminputrc")).ok()?;
+
+    // KDE keeps one section per device, with headers like
+    // `[Libinput][1267][12624][ELAN0412:00 04F3:3240 Touchpad]`.
+    // The first touchpad section with a speed set is the one we use.
+    let mut in_touchpad_section = false;
+    for line in kcminputrc.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_touchpad_section = line.starts_with("[Libinput]")
+                && line.to_lowercase().contains("touchpad");
+            continue;
+        }
+
+        if !in_touchpad_section { continue; }
+
+        if let Some(value) = line.strip_prefix("PointerAcceleration=") {
+            return value.trim().parse::<f64>().ok();
+        }
+    }
+
+    None
+}
+
+
+fn read_sway_speed() -> Option<f64> {
+    let output = Command::new("swaymsg")
+        .args(["-t", "get_inputs", "--raw"])
+        .output()
+        .ok()?;
+    let inputs: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+
+    inputs.as_array()?
+        .iter()
+        .find(|input| input["type"] == "touchpad")?
+        ["libinput"]["accel_speed"]
+        .as_f64()
+}
+
+
+/// Checks the desktop's pointer speed on an interval, and sends the
+/// corresponding motion multiplier through `tx` whenever it changes.
+/// Returns right away if the desktop isn't one we know how to read.
+pub async fn watch_desktop_speed(tx: Sender<f64>) {
+
+    let Some(desktop) = detect_desktop() else {
+        warn!("Could not tell which desktop is running, so the desktop's pointer \
+            speed will not be applied to drags.");
+        return;
+    };
+    debug!("Following pointer speed settings for {:?}", desktop);
+
+    let mut last_speed = None;
+    loop {
+        // these shell out or read files, so keep them off the async workers
+        let speed = tokio::task::spawn_blocking(move || read_pointer_speed(desktop))
+            .await
+            .ok()
+            .flatten();
+
+        if let Some(new_speed) = speed.filter(|s| Some(*s) != last_speed) {
+            let factor = speed_to_factor(new_speed);
+            info!("Desktop pointer speed is {:.2}; scaling drag motion by {:.2}",
+                new_speed, factor
+            );
+
+            // the only receiver is the translator, so if it's gone, so are we
+            if tx.send(factor).is_err() { return; }
+            last_speed = speed;
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
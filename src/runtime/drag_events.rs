@@ -0,0 +1,79 @@
+// High-level gesture events (a drag starting, moving, and ending, or a
+// tap), for programs embedding this crate that want interpreted gestures
+// rather than libinput's events. The translator sends these as it handles
+// gestures (see `GestureTranslator::events()`), but they don't depend on
+// it: anything with a stream of `Gesture`s (like a `GestureReader`) can
+// feed its own `DragEvents`, with no virtual trackpad at all.
+//
+// These follow the fingers, not the mouse button: each three-finger swipe
+// is a drag of its own, even one that carries on a held drag within
+// `dragEndDelay`, and a drag ends when the fingers lift, not when the
+// button is released after the delay.
+
+use tokio::sync::broadcast;
+
+use super::event_handler::Gesture;
+
+
+// a subscriber that falls this far behind misses events (and finds out
+// from `recv()`), rather than holding up the translator
+const EVENT_CAPACITY: usize = 256;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DragEvent {
+    DragStarted,
+    DragMoved { dx: f64, dy: f64 },     // in trackpad units, before acceleration
+    DragEnded { cancelled: bool },      // cancelled if something else (like a pinch) cut it off
+    TapDetected                         // three fingers down and up, without moving
+}
+
+
+/// Turns gestures into `DragEvent`s, for anyone subscribed.
+#[derive(Debug)]
+pub struct DragEvents {
+    sender: broadcast::Sender<DragEvent>,
+    dragging: bool
+}
+
+impl Default for DragEvents {
+    fn default() -> Self {
+        DragEvents { sender: broadcast::channel(EVENT_CAPACITY).0, dragging: false }
+    }
+}
+
+impl DragEvents {
+
+    /// A new receiver for the events from here on.
+    pub fn subscribe(&self) -> broadcast::Receiver<DragEvent> {
+        self.sender.subscribe()
+    }
+
+
+    /// Follows along with `gesture`, sending whatever event it makes.
+    pub fn follow(&mut self, gesture: Gesture) {
+
+        let event = match gesture {
+            Gesture::SwipeBegin => {
+                self.dragging = true;
+                Some(DragEvent::DragStarted)
+            },
+            Gesture::SwipeUpdate { dx, dy } if self.dragging => Some(DragEvent::DragMoved { dx, dy }),
+            Gesture::SwipeEnd { cancelled } if self.dragging => {
+                self.dragging = false;
+                Some(DragEvent::DragEnded { cancelled })
+            },
+            Gesture::Interrupted if self.dragging => {
+                self.dragging = false;
+                Some(DragEvent::DragEnded { cancelled: true })
+            },
+            Gesture::HoldEnd { cancelled: false } => Some(DragEvent::TapDetected),
+            _ => None
+        };
+
+        // only fails if nobody's subscribed, which is fine
+        if let Some(event) = event {
+            let _ = self.sender.send(event);
+        }
+    }
+}
@@ -1,24 +1,61 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::{Duration, Instant}};
 
 //use smol::{channel::{RecvError, SendError, Sender}};
-use tokio::sync::mpsc::{error::SendError, Sender};
-use input::{
-    event::{
-        gesture::{
-            GestureEvent, 
-            GestureEventCoordinates, 
-            GestureEventTrait, 
-            GestureHoldEvent, 
-            GestureSwipeEvent
-        }
-    }, Event
+use tokio::sync::{broadcast, mpsc::{self, error::SendError, Receiver, Sender}, watch};
+use input::{Device, Event};
+
+
+use input_linux::Key;
+use tracing::{debug, trace, warn};
+
+use super::{
+    accel_curve::AccelCurve,
+    clock::Clock,
+    dead_zone::DeadZone,
+    drag_events::{DragEvent, DragEvents},
+    focused_window::FocusedWindow, 
+    gesture_macros::{self, MacroRecognizer}, 
+    gesture_matcher::{self, GestureMatcher},
+    overload::UpdateBudget,
+    report_rate::ReportRate,
+    resting_thumb::RestingThumbs,
+    stats,
+    trace::{TraceEvent, TraceKind},
+    virtual_trackpad::VirtualTrackpad
 };
+use super::super::init::{
+    config::{AppProfile, Configuration, DeviceProfile, DragButton, MacroStep, SensitivityUnits, ThreeFingerTap},
+    gesture_definitions::GestureDefinition,
+    log_level
+};
+
+/// Some trackpads briefly report 2 fingers instead of 3 (or the other way
+/// around) as fingers land or lift. For this long after a three-finger
+/// gesture begins or ends, events for other finger counts won't end the
+/// drag, and the drag end is held off at least this long, so that flicker
+/// doesn't turn into a rapid mouse up/down pair.
+pub const FINGER_COUNT_DEBOUNCE: Duration = Duration::from_millis(30);
+
+/// libinput normalizes trackpad motion to what a 1000 DPI device would
+/// report, so each unit is a thousandth of an inch of finger travel,
+/// as long as it knows the trackpad's resolution.
+pub const MM_PER_UNIT: f64 = 0.0254;
 
+// fingers resting on the trackpad longer than this before lifting (without
+// moving) weren't tapping, so a deferred tap doesn't click
+const MAX_TAP_TIME: Duration = Duration::from_secs(1);
 
-use tracing::{debug, trace};
+// for `responseTime: "auto"`: what to use until the trackpad's report
+// rate is known, and how far it can go either way after that
+const DEFAULT_RESPONSE_TIME: Duration = Duration::from_millis(5);
+const MIN_AUTO_RESPONSE_TIME: Duration = Duration::from_millis(1);
+const MAX_AUTO_RESPONSE_TIME: Duration = Duration::from_millis(20);
+
+/// With `oneFingerContinue` on, the drag end delay is at least this long,
+/// so there's time for the one finger left on the trackpad to start moving
+/// before the drag ends, and for it to pause without ending the drag.
+pub const ONE_FINGER_GRACE: Duration = Duration::from_millis(250);
 
-use super::virtual_trackpad::VirtualTrackpad;
-use super::super::init::config::Configuration;
 
 /// A signal to send into channel to control the behavior
 /// of the listener on the separate thread that controls
@@ -31,15 +68,72 @@ use super::super::init::config::Configuration;
 /// 
 /// `RestartTimer`: Restart timer by restarting the loop in the fork that starts with a timer
 /// 
+/// `UpdateDelay`: Use a new delay for the timer (restarting it, if running)
+/// 
 /// `TerminateThread`: Terminate function running in fork
 #[derive(Debug)]
 pub enum ControlSignal {
     CancelTimer,      // currently not sent in practice, but could be without issue
     CancelMouseUp,
     RestartTimer,     // these two end up being treated the same in practice,
+    UpdateDelay(Duration),
     TerminateThread
 }
 
+/// A libinput event, boiled down to what the translator acts on. Besides
+/// making the translator's logic easier to follow, this means gestures 
+/// don't have to come from libinput: they can also be made up (see 
+/// `simulate`), since libinput's own events can't be.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    HoldBegin,
+    HoldEnd { cancelled: bool },  // cancelled when the fingers start moving
+    SwipeBegin,
+    SwipeUpdate { dx: f64, dy: f64 },
+    SwipeEnd { cancelled: bool },
+    Interrupted,    // some other three-finger gesture event (like a pinch), which ends a drag right away
+    PointerMotion,  // a single finger moving the cursor (which the compositor does itself)
+    OtherInput      // any other input, like gestures with other finger counts
+}
+
+impl From<&Event> for Gesture {
+
+    fn from(event: &Event) -> Self {
+        Gesture::from_event(&TraceEvent::from_event(event), false)
+    }
+}
+
+impl Gesture {
+
+    /// Like `Gesture::from`, but with `thumb_resting` saying whether a
+    /// thumb is resting on the trackpad (see `restingThumbZone`), in which
+    /// case four fingers count as three, if libinput didn't already leave
+    /// the thumb out.
+    pub fn from_event(event: &TraceEvent, thumb_resting: bool) -> Self {
+
+        if event.kind == TraceKind::PointerMotion {
+            return Gesture::PointerMotion;
+        }
+
+        // we don't care about gestures with other finger-counts, 
+        // except that they end any drag still going
+        let fingers = event.fingers;
+        if fingers != 3 && !(fingers == 4 && thumb_resting) {
+            return Gesture::OtherInput;
+        }
+
+        match event.kind {
+            TraceKind::HoldBegin => Gesture::HoldBegin,
+            TraceKind::HoldEnd => Gesture::HoldEnd { cancelled: event.cancelled },
+            TraceKind::SwipeBegin => Gesture::SwipeBegin,
+            TraceKind::SwipeUpdate => Gesture::SwipeUpdate { dx: event.dx, dy: event.dy },
+            TraceKind::SwipeEnd => Gesture::SwipeEnd { cancelled: event.cancelled },
+            _ => Gesture::Interrupted // just in case, so the drag isn't locked
+        }
+    }
+}
+
+
 // (G)esture (T)ranslation Error
 #[derive(Debug)]
 pub enum GtError {
@@ -74,6 +168,36 @@ pub struct GestureTranslator {
     pub vtp: VirtualTrackpad,
     pub cfg: Configuration,
     tx: Sender<ControlSignal>,
+    desktop_speed: watch::Receiver<f64>,  // multiplier following the desktop's pointer speed
+    output_scale: watch::Receiver<f64>,   // multiplier following the focused output (from `outputScales`)
+    paused: watch::Receiver<bool>,        // whether the focused window likely has the pointer grabbed
+    paused_on_request: bool,              // whether `pause` was sent over the control socket (or SIGUSR1)
+    focused: watch::Receiver<Option<FocusedWindow>>,  // for picking the app profile
+    profile: AppProfile,                  // overrides for the focused app, picked as each drag starts
+    device_profile: DeviceProfile,        // overrides for the trackpad the gestures are coming from
+    last_gesture_end: Option<Instant>,    // when the last three-finger gesture ended, for debouncing
+    last_gesture_begin: Option<Instant>,  // likewise, for when it began
+    motion_remainder: (f64, f64),         // sub-pixel motion not yet sent to the virtual trackpad
+    hold_began: Option<Instant>,          // when a hold began, if its button press is being held off
+    drag_button: Key,                     // the button held for the current drag
+    right_drag_next: bool,                // whether the next drag should hold the right button
+    last_motion: Instant,                 // when the cursor last moved, for jiggling during drags
+    macros: MacroRecognizer,              // recent gestures, for recognizing gesture macros
+    swipe_motion: (f64, f64),             // how far the current swipe has gone, for gesture macros
+    update_budget: UpdateBudget,          // for holding back swipe updates in an event storm
+    drag_events: DragEvents,              // high-level gesture events, for embedders
+    raw_units_only: bool,                 // whether a trackpad has no known resolution, so no millimeters
+    resting_thumbs: RestingThumbs,        // for counting three fingers plus a resting thumb as three
+    settling_until: Option<Instant>,      // when a finished drag stops swallowing taps (`clickSuppressTime`)
+    pointer_lock_hint: Option<bool>,      // whether the pointer is locked, if told so over the control socket
+    named_gestures: GestureMatcher,       // the gestures in gestures.d, matched ahead of the drag
+    accel_curve: AccelCurve,              // for accelerationProfile
+    pending_start: Option<(f64, f64)>,    // motion of a swipe that hasn't reached startThreshold yet
+    dead_zone: DeadZone,                  // for deadZone
+    report_rate: ReportRate,              // for responseTime "auto"
+    cancel_delay_sent: bool,              // whether the timer's been given dragCancelDelay instead of the usual delay
+    device_ids: HashMap<String, (u32, u32)>,  // vendor and product of each trackpad, by name, for `devices`
+    clock: Clock,                         // what gesture timing goes by (see `clock`)
 }
 
 impl GestureTranslator {
@@ -81,17 +205,60 @@ impl GestureTranslator {
     pub fn new(
         vtp: VirtualTrackpad, 
         cfg: Configuration, 
-        tx: Sender<ControlSignal>
+        tx: Sender<ControlSignal>,
+        desktop_speed: watch::Receiver<f64>,
+        output_scale: watch::Receiver<f64>,
+        paused: watch::Receiver<bool>,
+        focused: watch::Receiver<Option<FocusedWindow>>
     ) -> GestureTranslator {
 
+        let clock = Clock::monotonic();
         GestureTranslator {
             vtp,
             cfg,
-            tx
+            tx,
+            desktop_speed,
+            output_scale,
+            paused,
+            paused_on_request: false,
+            focused,
+            profile: AppProfile::default(),
+            device_profile: DeviceProfile::default(),
+            last_gesture_end: None,
+            last_gesture_begin: None,
+            motion_remainder: (0.0, 0.0),
+            hold_began: None,
+            drag_button: Key::ButtonLeft,
+            right_drag_next: false,
+            last_motion: clock.now(),
+            macros: MacroRecognizer::default(),
+            swipe_motion: (0.0, 0.0),
+            update_budget: UpdateBudget::default(),
+            drag_events: DragEvents::default(),
+            raw_units_only: false,
+            resting_thumbs: RestingThumbs::default(),
+            settling_until: None,
+            pointer_lock_hint: None,
+            named_gestures: GestureMatcher::default(),
+            accel_curve: AccelCurve::default(),
+            pending_start: None,
+            dead_zone: DeadZone::default(),
+            report_rate: ReportRate::default(),
+            cancel_delay_sent: false,
+            device_ids: HashMap::new(),
+            clock
         }
     }
 
 
+    /// Times gestures by `clock` from here on, instead of the monotonic
+    /// clock, for tests that need to move time along (or jump it).
+    pub fn use_clock(&mut self, clock: Clock) {
+        self.last_motion = clock.now();
+        self.clock = clock;
+    }
+
+
     async fn update_cursor_position(&mut self, dx: f64, dy: f64) -> Result<(), GtError> {
 
         trace!("Moving cursor...");
@@ -99,73 +266,759 @@ impl GestureTranslator {
         // the drag hold being randomly released
         self.send_signal(ControlSignal::CancelMouseUp).await?;
 
-        self.vtp.mouse_move_relative(
-            dx * self.cfg.acceleration, 
-            dy * self.cfg.acceleration
-        )?;
+        let (accel_x, accel_y) = self.acceleration();
+        let speed = self.unit_scale()
+            * *self.desktop_speed.borrow() 
+            * *self.output_scale.borrow();
+
+        // The virtual trackpad can only move in whole units, so whatever 
+        // fraction is left over gets carried into the next update, instead
+        // of being dropped. Otherwise, slow drags (where most updates are 
+        // under a unit) would barely move at all.
+        let direction = |inverted| if inverted { -1.0 } else { 1.0 };
+        let (x_move, y_move) = self.clamp_speed(
+            dx * accel_x * speed * direction(self.cfg.invert_x),
+            dy * accel_y * speed * direction(self.cfg.invert_y)
+        );
+        let x_total = x_move + self.motion_remainder.0;
+        let y_total = y_move + self.motion_remainder.1;
+
+        // With `maxEventRate`, motion coming in faster than that is held
+        // back (all of it, not just the fraction) until it's time for the 
+        // next event, so it's sent all at once instead of lost.
+        if self.too_soon_to_move() {
+            trace!("Over maxEventRate, accumulating");
+            self.motion_remainder = (x_total, y_total);
+            return Ok(());
+        }
+
+        let (x_whole, y_whole) = (x_total.trunc(), y_total.trunc());
+        self.motion_remainder = (x_total - x_whole, y_total - y_whole);
+
+        // nothing to move yet, so don't bother writing an empty event
+        if x_whole == 0.0 && y_whole == 0.0 {
+            trace!("Motion under a unit, accumulating");
+            return Ok(());
+        }
+
+        self.vtp.mouse_move_relative(x_whole, y_whole)?;
+        self.last_motion = self.clock.now();
+
+        Ok(())
+    }
+
+
+    /// How long the main loop waits between fetching batches of events:
+    /// `responseTime`, or with it on "auto", about as long as the
+    /// trackpad takes between reports.
+    pub fn response_time(&self) -> Duration {
+        self.cfg.response_time.unwrap_or_else(||
+            self.report_rate.interval()
+                .unwrap_or(DEFAULT_RESPONSE_TIME)
+                .clamp(MIN_AUTO_RESPONSE_TIME, MAX_AUTO_RESPONSE_TIME)
+        )
+    }
+
+
+    /// Shortens a single update's motion to `maxSpeed`, if it's over it,
+    /// keeping its direction.
+    fn clamp_speed(&self, dx: f64, dy: f64) -> (f64, f64) {
+        let distance = dx.hypot(dy);
+        if self.cfg.max_speed <= 0.0 || distance <= self.cfg.max_speed {
+            return (dx, dy);
+        }
+        trace!("Motion over maxSpeed, clamping");
+        let scale = self.cfg.max_speed / distance;
+        (dx * scale, dy * scale)
+    }
+
+
+    /// Converts trackpad units into what `acceleration` is per, following
+    /// `sensitivityUnits`.
+    fn unit_scale(&self) -> f64 {
+        match self.cfg.sensitivity_units {
+            SensitivityUnits::Mm if !self.raw_units_only => MM_PER_UNIT,
+            _ => 1.0
+        }
+    }
+
+
+    /// Notes a trackpad being read, and whether its physical size (from
+    /// the resolution and range of its ABS_X and ABS_Y axes) is known.
+    /// Without it, libinput can only guess at how far a unit is, so
+    /// millimeters are out, and raw units are used instead. This also
+    /// starts watching it for resting thumbs, if `restingThumbZone` is set.
+    pub fn trackpad_added(&mut self, trackpad: &Device) {
+
+        if self.cfg.resting_thumb_zone > 0.0 {
+            self.resting_thumbs.watch(trackpad.sysname(), self.cfg.resting_thumb_zone);
+        }
+        self.trackpad_size_is(trackpad.size());
+    }
+
+
+    /// The part of `trackpad_added()` that goes by the trackpad's size
+    /// alone, for when there's no trackpad to watch (like in a replay).
+    pub fn trackpad_size_is(&mut self, size: Option<(f64, f64)>) {
+
+        match size {
+            Some((width, height)) => debug!("Trackpad is {:.0}x{:.0}mm", width, height),
+            None => {
+                if self.cfg.sensitivity_units == SensitivityUnits::Mm && !self.raw_units_only {
+                    warn!("A trackpad doesn't report its resolution, so sensitivityUnits \"mm\" \
+                        can't be used; acceleration is per raw trackpad unit instead");
+                }
+                self.raw_units_only = true;
+            }
+        }
+    }
+
+
+    /// Whether writing a motion event now would go over `maxEventRate`.
+    fn too_soon_to_move(&self) -> bool {
+        self.cfg.max_event_rate > 0
+            && self.clock.since(self.last_motion) < Duration::from_secs(1) / self.cfg.max_event_rate
+    }
+
+
+    /// Sends off any motion `maxEventRate` held back, so the end of a 
+    /// swipe doesn't come up short.
+    fn flush_motion(&mut self) -> Result<(), GtError> {
+        let (x_whole, y_whole) = (self.motion_remainder.0.trunc(), self.motion_remainder.1.trunc());
+        if x_whole == 0.0 && y_whole == 0.0 {
+            return Ok(());
+        }
+
+        self.motion_remainder = (self.motion_remainder.0 - x_whole, self.motion_remainder.1 - y_whole);
+        self.vtp.mouse_move_relative(x_whole, y_whole)?;
+        self.last_motion = self.clock.now();
+        Ok(())
+    }
+
+
+    /// Jiggles the cursor back and forth if a drag has been held without
+    /// moving for `jiggleInterval`, so drag-and-drop targets that only 
+    /// react while the pointer moves (like spring-loaded folders, or 
+    /// auto-scrolling near a window's edge) don't give up on the hover.
+    /// Meant to be called periodically while no events are coming in.
+    pub fn keep_drag_alive(&mut self) -> Result<(), GtError> {
+
+        if self.cfg.jiggle_interval.is_zero() 
+            || !self.drag_is_held() 
+            || self.clock.since(self.last_motion) < self.cfg.jiggle_interval 
+        {
+            return Ok(());
+        }
+
+        // there and back again, so the cursor ends up where it was
+        trace!("Jiggling cursor to keep the drag alive");
+        let amplitude = self.cfg.jiggle_amplitude as f64;
+        self.vtp.mouse_move_relative(amplitude, 0.0)?;
+        self.vtp.mouse_move_relative(-amplitude, 0.0)?;
+        self.last_motion = self.clock.now();
+
+        Ok(())
+    }
+
+
+    /// Whether the button is still held for a drag. `vtp.mouse_is_down` 
+    /// alone can't say, since the timer thread releases the button on 
+    /// its own copy of the virtual trackpad, so this also checks whether 
+    /// the drag end delay has run out since the gesture ended.
+    pub fn drag_is_held(&self) -> bool {
+        self.vtp.mouse_is_down 
+            && self.last_gesture_end.is_none_or(|end| self.clock.since(end) < self.timer_delay())
+    }
+
+
+    /// The acceleration for horizontal and vertical motion. An app's or
+    /// device's `acceleration` goes for both, over `accelerationX`/`Y`.
+    fn acceleration(&self) -> (f64, f64) {
+        match self.profile.acceleration.or(self.device_profile.acceleration) {
+            Some(accel) => (accel, accel),
+            None => (
+                self.cfg.acceleration_x.unwrap_or(self.cfg.acceleration),
+                self.cfg.acceleration_y.unwrap_or(self.cfg.acceleration)
+            )
+        }
+    }
+
+
+    /// Sets whether the pointer is locked (say, by a game), for
+    /// `pointerLockPassthrough`, or with `None`, goes back to guessing
+    /// from whether the focused window is fullscreen. Takes effect from
+    /// the next drag.
+    pub fn hint_pointer_lock(&mut self, locked: Option<bool>) {
+        self.pointer_lock_hint = locked;
+    }
+
+
+    /// Stops (or with `false`, starts again) translating gestures, for
+    /// the `pause` and `resume` commands (and SIGUSR1), letting go of any drag that's
+    /// held first. This is on top of the pausing the config asks for, so
+    /// resuming doesn't undo that.
+    pub async fn pause_on_request(&mut self, pause: bool) -> Result<(), GtError> {
+        self.paused_on_request = pause;
+        if pause && self.drag_is_held() {
+            debug!("Paused on request, releasing drag");
+            self.mouse_up_now().await?;
+        }
+        Ok(())
+    }
+
 
+    /// Whether `pause_on_request()` has gestures left alone.
+    pub fn is_paused_on_request(&self) -> bool {
+        self.paused_on_request
+    }
+
+
+    /// Whether gestures are being left alone right now, for whatever reason.
+    pub fn is_paused(&self) -> bool {
+        self.paused_on_request || *self.paused.borrow()
+    }
+
+
+    /// Everything the translator's in the middle of, as readable lines, for
+    /// SIGUSR2 (and `dump-state`). A button that's stuck down can be
+    /// looked into this way without having run at the trace level.
+    pub fn dump_state(&self, devices: &[String]) -> String {
+
+        let phase = if let Some((dx, dy)) = self.pending_start {
+            format!("swiping, short of startThreshold ({dx:.1}, {dy:.1} so far)")
+        } else if let Some(began) = self.hold_began {
+            format!("holding, with the press held off for {}ms", self.clock.since(began).as_millis())
+        } else if self.vtp.mouse_is_down {
+            match self.last_gesture_end {
+                None => "dragging".to_string(),
+                Some(end) => match self.timer_delay().checked_sub(self.clock.since(end)) {
+                    Some(left) => format!("ending, with the button let go in {}ms", left.as_millis()),
+                    // the timer thread has its own copy of the trackpad
+                    None => "ended, with the button let go by the timer".to_string()
+                }
+            }
+        } else {
+            "idle".to_string()
+        };
+
+        let paused = match (self.paused_on_request, *self.paused.borrow()) {
+            (true, _) => "yes, on request",
+            (false, true) => "yes, by the config (like pauseForApps)",
+            (false, false) => "no"
+        };
+        let settling = self.settling_until
+            .and_then(|until| until.checked_duration_since(self.clock.now()))
+            .map_or("no".to_string(), |left| format!("for {}ms more", left.as_millis()));
+
+        format!(
+            "Phase:            {phase}\n\
+            Button down:      {} ({:?})\n\
+            Drag end delay:   {}ms{}\n\
+            Right drag next:  {}\n\
+            Paused:           {paused}\n\
+            Pointer lock:     {}\n\
+            Swallowing taps:  {settling}\n\
+            Swipe motion:     ({:.1}, {:.1}), with ({:.2}, {:.2}) left over\n\
+            App profile:      {}\n\
+            Device profile:   {}\n\
+            Trackpads:        {}\n\
+            Config:           {}",
+            self.vtp.mouse_is_down, self.drag_button,
+            self.timer_delay().as_millis(), if self.cancel_delay_sent { " (dragCancelDelay in use)" } else { "" },
+            self.right_drag_next,
+            match self.pointer_lock_hint { Some(true) => "locked", Some(false) => "unlocked", None => "guessing" },
+            self.swipe_motion.0, self.swipe_motion.1, self.motion_remainder.0, self.motion_remainder.1,
+            serde_json::to_string(&self.profile).unwrap_or_default(),
+            serde_json::to_string(&self.device_profile).unwrap_or_default(),
+            if devices.is_empty() { "none".to_string() } else { devices.join(", ") },
+            serde_json::to_string(&self.cfg).unwrap_or_default()
+        )
+    }
+
+
+    /// Whether drags should only move the pointer, without holding a
+    /// button: the app profile asks for it, and the pointer is locked (or
+    /// likely is, with the focused window fullscreen). Games with the
+    /// pointer locked want the motion, but a held button would fire.
+    fn passes_pointer_through(&self) -> bool {
+        self.profile.pointer_lock_passthrough == Some(true)
+            && self.pointer_lock_hint.unwrap_or_else(||
+                self.focused.borrow().as_ref().is_some_and(|window| window.fullscreen)
+            )
+    }
+
+
+    /// How long the timer in the fork waits before ending a drag. It never 
+    /// runs shorter than the debounce window (see `FINGER_COUNT_DEBOUNCE`),
+    /// or with `oneFingerContinue`, than `ONE_FINGER_GRACE`.
+    pub fn timer_delay(&self) -> Duration {
+        self.profile.drag_end_delay
+            .or(self.device_profile.drag_end_delay)
+            .unwrap_or(self.cfg.drag_end_delay)
+            .max(self.shortest_delay())
+    }
+
+
+    fn shortest_delay(&self) -> Duration {
+        if self.cfg.one_finger_continue { ONE_FINGER_GRACE } else { FINGER_COUNT_DEBOUNCE }
+    }
+
+
+    /// Gives the timer `dragCancelDelay` in place of the usual delay, for a
+    /// swipe libinput cancelled (rather than one the fingers lifted from).
+    /// The usual delay goes back as the next drag starts.
+    async fn use_cancel_delay(&mut self) -> Result<(), GtError> {
+        let Some(delay) = self.cfg.drag_cancel_delay else { return Ok(()) };
+        let delay = delay.max(self.shortest_delay());
+        if delay != self.timer_delay() {
+            trace!("Swipe was cancelled; ending the drag after {}ms", delay.as_millis());
+            self.cancel_delay_sent = true;
+            self.send_signal(ControlSignal::UpdateDelay(delay)).await?;
+        }
         Ok(())
     }
 
+
+    /// Picks the profile for whichever app is focused (or no profile at
+    /// all), and lets the timer know if that changes the drag end delay.
+    /// Only called between drags, so a drag never changes speed (or 
+    /// button) halfway through.
+    async fn switch_profile(&mut self) -> Result<(), GtError> {
+
+        let profile = self.focused.borrow()
+            .as_ref()
+            .and_then(|window| self.cfg.profile_for(&window.app_id))
+            .cloned()
+            .unwrap_or_default();
+
+        if profile != self.profile {
+            debug!("Switching to app profile {:?}", profile);
+            let old_delay = self.timer_delay();
+            self.profile = profile;
+            self.sync_timer_delay(old_delay).await?;
+        }
+
+        Ok(())
+    }
+
+
+    /// Picks the settings for the trackpad `event` came from (see
+    /// `devices`), letting the timer know if that changes the drag end
+    /// delay.
+    async fn follow_device(&mut self, event: &TraceEvent) -> Result<(), GtError> {
+        if let (TraceKind::DeviceAdded, Some(vendor), Some(product)) = (event.kind, event.vendor, event.product) {
+            self.device_ids.insert(event.device.clone(), (vendor, product));
+        }
+        if self.cfg.devices.is_empty() && self.device_profile == DeviceProfile::default() {
+            return Ok(());
+        }
+
+        let (vendor, product) = self.device_ids.get(&event.device).copied().unwrap_or_default();
+        let profile = self.cfg
+            .device_profile_for(&event.device, vendor, product)
+            .cloned()
+            .unwrap_or_default();
+
+        if profile != self.device_profile {
+            debug!("Switching to the settings for the device '{}': {:?}", event.device, profile);
+            let old_delay = self.timer_delay();
+            self.device_profile = profile;
+            self.sync_timer_delay(old_delay).await?;
+        }
+        Ok(())
+    }
+
+
+    async fn sync_timer_delay(&mut self, old_delay: Duration) -> Result<(), GtError> {
+        if self.timer_delay() != old_delay {
+            self.send_signal(ControlSignal::UpdateDelay(self.timer_delay())).await?;
+        }
+        Ok(())
+    }
+
+    
+    /// Matches gestures against `definitions` (from `gestures.d`) from
+    /// here on, before the built-in drag.
+    pub fn use_named_gestures(&mut self, definitions: Vec<GestureDefinition>) {
+        self.named_gestures = GestureMatcher::new(definitions);
+    }
+
     
-    pub async fn translate_gesture(&mut self, event: Event) -> Result<(), GtError> {
+    /// Handles one event from the trackpad, as a `TraceEvent` (so events
+    /// from a recorded trace go through exactly what live ones do).
+    pub async fn translate_gesture(&mut self, event: TraceEvent) -> Result<(), GtError> {
     
         debug!("Event received: {:?}", event);
+        self.follow_device(&event).await?;
+        if !self.device_profile.is_enabled() {
+            trace!("Gestures from this trackpad are turned off in `devices`; ignoring");
+            return Ok(());
+        }
 
-        match event {
-            Event::Gesture(gest_ev) => {
+        if event.kind == TraceKind::SwipeUpdate {
+            self.report_rate.note(event.time_usec);
+        }
+
+        let thumb_resting = self.cfg.resting_thumb_zone > 0.0 && self.resting_thumbs.is_resting();
+        let gesture = Gesture::from_event(&event, thumb_resting);
+        let gesture = self.accel_curve.apply(self.cfg.acceleration_profile, &event, gesture);
+
+        let paused = self.is_paused();
+        if let Some(definition) = self.named_gestures.follow(&event) {
+            if !paused {
+                gesture_matcher::run_definition(definition);
+            }
+            // a three-finger hold a named gesture took over isn't a tap too,
+            // as long as its press was held off (see `handle_hold`)
+            if gesture == (Gesture::HoldEnd { cancelled: false }) && self.hold_began.take().is_some() {
+                return Ok(());
+            }
+        }
+
+        self.handle_gesture(gesture).await
+    }
 
-                // we don't care about gestures with other finger-counts
-                if gest_ev.finger_count() != 3 {
-                    debug!("Gesture not three-fingered, releasing drag");
-                    return self.mouse_up_now().await;
-                }
-            
-                match gest_ev {
 
-                    GestureEvent::Hold(gest_hold_ev) => self.handle_hold(gest_hold_ev).await,
-                    GestureEvent::Swipe(swipe_ev) => self.handle_swipe(swipe_ev).await,
-                    _ => self.mouse_up_now().await // just in case, so the drag isn't locked
+    pub async fn handle_gesture(&mut self, gesture: Gesture) -> Result<(), GtError> {
+
+        match gesture {
+            Gesture::SwipeBegin => stats::count_gesture_begun(),
+            Gesture::SwipeUpdate { .. } => stats::count_gesture_updated(),
+            Gesture::SwipeEnd { cancelled: false } => stats::count_gesture_ended(),
+            Gesture::SwipeEnd { cancelled: true } => stats::count_gesture_cancelled(),
+            _ => ()
+        }
+
+        // while paused, gestures are left entirely to the focused app,
+        // after letting go of any drag that was still held
+        if self.is_paused() {
+            if self.drag_is_held() {
+                debug!("Drags are paused, releasing drag");
+                return self.mouse_up_now().await;
+            }
+            trace!("Drags are paused; ignoring");
+            return Ok(());
+        }
+
+        if matches!(gesture, Gesture::SwipeBegin | Gesture::HoldBegin) {
+            self.last_gesture_begin = Some(self.clock.now());
+        }
+        if !self.cfg.gesture_macros.is_empty() {
+            self.track_macro_step(gesture);
+        }
+        self.drag_events.follow(gesture);
+
+        match gesture {
+            Gesture::HoldBegin | Gesture::HoldEnd { .. } => self.handle_hold(gesture).await,
+            Gesture::SwipeUpdate { dx, dy } if self.pending_start.is_some() => self.approach_start(dx, dy).await,
+            Gesture::SwipeUpdate { dx, dy } => match self.dead_zone.filter(self.clock.now(), self.cfg.dead_zone, dx, dy)
+                .and_then(|(dx, dy)| self.update_budget.admit(self.clock.now(), dx, dy))
+            {
+                Some((dx, dy)) => self.update_cursor_position(dx, dy).await,
+                None => Ok(())
+            },
+            Gesture::SwipeBegin => {
+                self.update_budget.begin();
+                self.dead_zone.begin();
+                // a drag being continued is already past the threshold
+                if self.cfg.start_threshold > 0.0 && !self.drag_is_held() {
+                    self.pending_start = Some((0.0, 0.0));
+                    return Ok(());
                 }
+                self.mouse_down().await
             },
-            _ => self.mouse_up_now().await
+            Gesture::SwipeEnd { .. } if self.pending_start.take().is_some() => {
+                trace!("Swipe ended before reaching startThreshold; no drag");
+                self.hold_began = None;
+                self.right_drag_next = false;
+                Ok(())
+            },
+            Gesture::SwipeEnd { cancelled } => {
+                self.start_settling();
+                if let Some((dx, dy)) = self.update_budget.finish() {
+                    self.update_cursor_position(dx, dy).await?;
+                }
+                self.flush_motion()?;
+                if cancelled {
+                    self.use_cancel_delay().await?;
+                }
+                self.handle_mouse_up().await
+            },
+            Gesture::Interrupted     => {
+                self.pending_start = None;
+                self.mouse_up_now().await
+            },
+            Gesture::PointerMotion   => self.handle_pointer_motion().await,
+            Gesture::OtherInput      => {
+                self.pending_start = None;
+                self.end_drag_for_other_input().await
+            }
+        }
+    }
+
+
+    /// Adds motion to a swipe that hasn't gone `startThreshold` yet,
+    /// starting the drag once it has, with all the motion so far.
+    async fn approach_start(&mut self, dx: f64, dy: f64) -> Result<(), GtError> {
+
+        let Some((total_x, total_y)) = &mut self.pending_start else { return Ok(()) };
+        *total_x += dx;
+        *total_y += dy;
+        let (total_x, total_y) = (*total_x, *total_y);
+
+        if total_x.hypot(total_y) < self.cfg.start_threshold {
+            trace!("Swipe hasn't reached startThreshold yet; holding off the press");
+            return Ok(());
         }
+
+        debug!("Swipe reached startThreshold, starting drag");
+        self.pending_start = None;
+        self.mouse_down().await?;
+        self.update_cursor_position(total_x, total_y).await
     }
 
 
-    async fn handle_hold(&mut self, hold_ev: GestureHoldEvent) -> Result<(), GtError> {
-        match hold_ev {
-            GestureHoldEvent::Begin(_) => self.mouse_down().await,
-            GestureHoldEvent::End(_)   => self.handle_mouse_up().await,
+    /// Follows along with three-finger gestures for `gestureMacros`, 
+    /// running a macro's command once its last step is done. This only
+    /// watches; the gesture is handled as usual either way.
+    fn track_macro_step(&mut self, gesture: Gesture) {
+
+        let step = match gesture {
+            Gesture::HoldEnd { cancelled: false } => Some(MacroStep::Tap),
+            Gesture::SwipeBegin => {
+                self.swipe_motion = (0.0, 0.0);
+                None
+            },
+            Gesture::SwipeUpdate { dx, dy } => {
+                self.swipe_motion.0 += dx;
+                self.swipe_motion.1 += dy;
+                None
+            },
+            Gesture::SwipeEnd { .. } => gesture_macros::swipe_step(self.swipe_motion.0, self.swipe_motion.1),
+            _ => None
+        };
+
+        if let Some(gesture_macro) = step.and_then(|step| self.macros.push(self.clock.now(), step, &self.cfg.gesture_macros)) {
+            gesture_macros::run_macro(gesture_macro);
+        }
+    }
+
+
+    /// Handles a single finger moving the cursor. With `oneFingerContinue`
+    /// on, lifting two of the three fingers mid-drag keeps the button held
+    /// while the last one carries on (the compositor moves the cursor, like
+    /// for any one-finger motion). Trackpads don't tell other programs when
+    /// that last finger lifts, only that it stopped moving, so each motion
+    /// restarts the drag end timer instead, and the drag ends once the 
+    /// finger has been still (or gone) for the drag end delay.
+    async fn handle_pointer_motion(&mut self) -> Result<(), GtError> {
+
+        if !self.cfg.one_finger_continue || !self.drag_is_held() {
+            return self.end_drag_for_other_input().await;
+        }
+
+        trace!("One finger moving mid-drag, keeping the drag held");
+        self.last_motion = self.clock.now();
+        self.handle_mouse_up().await
+    }
+
+
+    /// Handles input that isn't a three-finger gesture. This only does 
+    /// anything if there's a drag to end; otherwise, the event is left 
+    /// alone entirely (no signals, no writes), so things like four-finger
+    /// compositor gestures go on exactly as if this program weren't running.
+    async fn end_drag_for_other_input(&mut self) -> Result<(), GtError> {
+
+        if !self.drag_is_held() {
+            trace!("Not a three-finger gesture, and no drag to end; ignoring");
+            // the timer already let go of a drag that ran out its delay,
+            // on its own copy of the virtual trackpad
+            self.vtp.mouse_is_down = false;
+            // but a long press that never turned into anything is over
+            self.hold_began = None;
+            self.right_drag_next = false;
+            return Ok(());
+        }
+
+        if self.in_debounce_window() {
+            debug!("Not a three-finger gesture, but too soon after the last one began or ended to release drag");
+            return Ok(());
+        }
+
+        debug!("Not a three-finger gesture, releasing drag");
+        self.mouse_up_now().await
+    }
+
+
+    /// Whether a three-finger gesture began or ended recently enough that
+    /// events with other finger counts may just be the trackpad flickering.
+    fn in_debounce_window(&self) -> bool {
+        [self.last_gesture_begin, self.last_gesture_end]
+            .into_iter()
+            .flatten()
+            .any(|time| self.clock.since(time) < FINGER_COUNT_DEBOUNCE)
+    }
+
+
+    async fn handle_hold(&mut self, hold: Gesture) -> Result<(), GtError> {
+
+        // With hold-to-right-click on (or taps clicking anything but the
+        // left button, or a named gesture for three-finger holds), a hold
+        // that starts a new drag can't press anything until we know how 
+        // long it lasted and whether it moved, so the press is held off 
+        // until it ends. Holds that continue a drag (the button is already
+        // down, during dragEndDelay) work the same as ever.
+        let defer_press = (self.cfg.hold_to_right_click 
+            || self.cfg.three_finger_tap != ThreeFingerTap::Left
+            || self.named_gestures.takes_three_finger_holds())
+            && !self.drag_is_held();
+
+        match hold {
+            Gesture::HoldBegin if defer_press => {
+                trace!("Hold began, holding off the button press");
+                self.hold_began = Some(self.clock.now());
+                Ok(())
+            },
+            Gesture::HoldBegin => self.mouse_down().await,
+            Gesture::HoldEnd { cancelled } => match self.hold_began.take() {
+                Some(began) => self.handle_deferred_hold_end(began, cancelled).await,
+                None => self.handle_mouse_up().await
+            },
             _ => self.mouse_up_now().await
         }
     }
 
 
-    async fn handle_swipe(&mut self, swipe_ev: GestureSwipeEvent) -> Result<(), GtError> {
-                    
-        match swipe_ev {
-            GestureSwipeEvent::Update(swipe_update) => {            
-                self.update_cursor_position(
-                    swipe_update.dx(), 
-                    swipe_update.dy()
-                ).await
+    /// Decides what a hold was, once it's over. A hold is cancelled when the
+    /// fingers start moving (and a swipe begins), or ends normally when they
+    /// lift without moving. With hold-to-right-click on, a long enough hold 
+    /// means the right button: a right-click if the fingers lifted, or a 
+    /// right-drag if they moved. Otherwise, it's the usual left-drag, or a
+    /// tap if the fingers didn't move.
+    async fn handle_deferred_hold_end(&mut self, began: Instant, cancelled: bool) -> Result<(), GtError> {
+
+        let long_press = self.cfg.hold_to_right_click 
+            && self.clock.since(began) >= self.cfg.right_click_hold_time;
+
+        match (long_press, cancelled) {
+            (true, true) => {
+                debug!("Moved after a long press, starting a right-drag");
+                self.right_drag_next = true;
+                Ok(())
+            },
+            (true, false) => {
+                debug!("Long press, right-clicking");
+                self.click_now(Key::ButtonRight).await
+            },
+            // the swipe that's about to begin does the pressing
+            (false, true) => Ok(()),
+            (false, false) if self.clock.since(began) > MAX_TAP_TIME => {
+                trace!("Three fingers rested and lifted without moving; too long for a tap");
+                Ok(())
+            },
+            (false, false) => self.handle_tap().await
+        }
+    }
+
+
+    /// Clicks whatever `threeFingerTap` says to. A left tap goes through the
+    /// drag end timer like any other drag, so tapping and then dragging 
+    /// within `dragEndDelay` carries on as one drag. Other buttons click 
+    /// right away, since a right- or middle-drag by tapping first isn't
+    /// something anyone would expect.
+    async fn handle_tap(&mut self) -> Result<(), GtError> {
+
+        if self.is_settling() {
+            debug!("Three-finger tap right after a drag, ignoring it as a leftover of the drag");
+            return Ok(());
+        }
+
+        match self.cfg.three_finger_tap {
+            ThreeFingerTap::Left => {
+                debug!("Three-finger tap, left-clicking");
+                self.mouse_down().await?;
+                self.handle_mouse_up().await
+            },
+            ThreeFingerTap::Middle => {
+                debug!("Three-finger tap, middle-clicking");
+                self.click_now(Key::ButtonMiddle).await
+            },
+            ThreeFingerTap::Right => {
+                debug!("Three-finger tap, right-clicking");
+                self.click_now(Key::ButtonRight).await
+            },
+            ThreeFingerTap::None => {
+                trace!("Three-finger tap, but taps are set to do nothing");
+                Ok(())
             }
-            GestureSwipeEvent::Begin(_) => self.mouse_down().await,
-            GestureSwipeEvent::End(_)   => self.handle_mouse_up().await,
-            _ => self.mouse_up_now().await
         }
     }
 
 
+    /// Puts a drag that just ended into its last state, settling, where
+    /// taps for `clickSuppressTime` afterwards are taken to be fingers
+    /// lifting unevenly, not meant as clicks, and ignored. Settling ends
+    /// by itself once the time is up.
+    fn start_settling(&mut self) {
+        if !self.cfg.click_suppress_time.is_zero() {
+            self.settling_until = Some(self.clock.now() + self.cfg.click_suppress_time);
+        }
+    }
+
+
+    fn is_settling(&self) -> bool {
+        self.settling_until.is_some_and(|until| self.clock.now() < until)
+    }
+
+
+    /// Presses and immediately releases `button`, outside of any drag.
+    async fn click_now(&mut self, button: Key) -> Result<(), GtError> {
+        // an earlier drag's end has nothing to do with this press, and
+        // would have `mouse_up_now` think it was already let go
+        self.last_gesture_end = None;
+        self.vtp.button_down(button)?;
+        self.mouse_up_now().await
+    }
+
+
     /// Sets mouse to down immediately, and cancels background
     /// `mouse_up_delay` timer.
     async fn mouse_down(&mut self) -> Result<(), GtError> {
+
+        // has to be checked before the gesture end is forgotten below
+        let starting_new_drag = !self.drag_is_held();
         
+        self.last_gesture_end = None;
+        self.motion_remainder = (0.0, 0.0);
+        self.last_motion = self.clock.now();
         self.send_signal(ControlSignal::CancelMouseUp).await?;
+        if std::mem::take(&mut self.cancel_delay_sent) {
+            self.send_signal(ControlSignal::UpdateDelay(self.timer_delay())).await?;
+        }
+
+        // a drag being continued keeps the profile and button it started with
+        if starting_new_drag {
+            stats::count_drag_started();
+            self.switch_profile().await?;
+            self.drag_button = if std::mem::take(&mut self.right_drag_next) {
+                Key::ButtonRight
+            } else {
+                match self.profile.drag_button.unwrap_or(self.cfg.drag_button) {
+                    DragButton::Left => Key::ButtonLeft,
+                    DragButton::Middle => Key::ButtonMiddle,
+                    DragButton::Right => Key::ButtonRight
+                }
+            };
+
+            // with nothing pressed, the motion still goes through as usual
+            if self.passes_pointer_through() {
+                trace!("Pointer is locked; moving without pressing anything");
+                return Ok(());
+            }
+        }
         
         self.vtp
-            .mouse_down()
+            .button_down(self.drag_button)
             .map_err(GtError::from)
     }
 
@@ -175,28 +1028,75 @@ impl GestureTranslator {
     /// further up the call stack.
     async fn handle_mouse_up(&mut self) -> Result<(), GtError> {
 
-        // don't bother with forking and all that if there is
-        // no delay to begin with
-        if self.cfg.drag_end_delay == Duration::ZERO {
-            
-            return self.mouse_up_now().await;
-        }
-
-        // default case
+        // Even with no delay configured, the release still goes through 
+        // the timer in the fork, which always waits at least 
+        // FINGER_COUNT_DEBOUNCE. That way, if the gesture only ended 
+        // because the finger count flickered, the next three-finger 
+        // gesture's mouse_down cancels the release before it happens.
+        self.last_gesture_end = Some(self.clock.now());
         self.send_signal(ControlSignal::RestartTimer).await
     }
 
 
     /// Cancels the drag, cutting off any currently running delay.
     /// The left click is released here, not in the fork when the 
-    /// timer is running to cut down on latency.
+    /// timer is running to cut down on latency. If the timer already
+    /// let go (on its own copy of the virtual trackpad), nothing is
+    /// written, and it doesn't count as a forced release.
     async fn mouse_up_now(&mut self) -> Result<(), GtError> {
         trace!("Cancelling timer, ending drag immediately");
+        let held = self.drag_is_held();
+        self.last_gesture_end = None;
+        self.hold_began = None;
+        self.right_drag_next = false;
         self.send_signal(ControlSignal::CancelMouseUp).await?;
+
+        if !held {
+            self.vtp.mouse_is_down = false;
+            return Ok(());
+        }
+        stats::count_forced_release();
         Ok(self.vtp.mouse_up()?)
     }
 
 
+    /// Swaps in a new configuration, without interrupting a drag in progress.
+    /// Settings only read at startup (like the log file) aren't affected.
+    pub async fn apply_config(&mut self, cfg: Configuration) -> Result<(), GtError> {
+
+        log_level::set(cfg.log_level);
+        let old_delay = self.timer_delay();
+        self.cfg = cfg;
+
+        // a drag in progress keeps its profile until it's over, even if
+        // the profile changed; the next drag picks up the new one
+        if self.drag_is_held() {
+            self.sync_timer_delay(old_delay).await
+        } else {
+            self.profile = AppProfile::default();
+            self.sync_timer_delay(old_delay).await?;
+            self.switch_profile().await
+        }
+    }
+
+
+    /// High-level gesture events (drags starting, moving, and ending, and
+    /// taps) from here on, for programs embedding this crate. Nothing is
+    /// sent while drags are paused.
+    pub fn events(&self) -> broadcast::Receiver<DragEvent> {
+        self.drag_events.subscribe()
+    }
+
+
+    /// Connects to a new drag end timer, for when the old one is gone (or
+    /// about to be), and returns the end the new timer should listen on.
+    pub fn reconnect_timer(&mut self) -> Receiver<ControlSignal> {
+        let (tx, rx) = mpsc::channel(3);
+        self.tx = tx;
+        rx
+    }
+
+
     /// Wrapper to send signal into channel.
     pub async fn send_signal(&mut self, sig: ControlSignal) -> Result<(), GtError> {
         
@@ -208,4 +1108,371 @@ impl GestureTranslator {
         trace!("Signal sent!");
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        sync::MutexGuard,
+        time::{Duration, Instant}
+    };
+
+    use tokio::sync::{broadcast, mpsc, watch};
+
+    use super::*;
+    use crate::{
+        init::config::{Configuration, OutputMode},
+        runtime::stats::Stats
+    };
+
+    /// A translator on a dry run, with a drag end timer running on its
+    /// own clone of the virtual trackpad (like in `main`), and the button
+    /// presses and releases either of them makes. Only one is around at
+    /// a time, so the stats it counts are its own.
+    pub(crate) struct Harness {
+        pub translator: GestureTranslator,
+        buttons: broadcast::Receiver<bool>,
+        _stats: MutexGuard<'static, ()>
+    }
+
+    impl Harness {
+
+        pub(crate) fn new(cfg: Configuration) -> Harness {
+            let stats = stats::TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let vtp = VirtualTrackpad::dry_run(OutputMode::Pointer);
+            let buttons = vtp.subscribe_button_events();
+            let (tx, rx) = mpsc::channel(3);
+            let translator = GestureTranslator::new(
+                vtp,
+                cfg,
+                tx,
+                watch::channel(1.0).1,
+                watch::channel(1.0).1,
+                watch::channel(false).1,
+                watch::channel(None).1
+            );
+
+            let mut timer_vtp = translator.vtp.clone();
+            let delay = translator.timer_delay();
+            tokio::spawn(async move { timer_vtp.handle_mouse_up_timeout(delay, rx).await });
+
+            Harness { translator, buttons, _stats: stats }
+        }
+
+
+        pub(crate) async fn send(&mut self, kind: TraceKind, fingers: i32) {
+            self.send_event(event(kind, fingers)).await;
+        }
+
+
+        pub(crate) async fn send_event(&mut self, event: TraceEvent) {
+            self.translator.translate_gesture(event).await.expect("a dry run can't fail to write");
+            settle().await;
+        }
+
+
+        /// A three-finger swipe, from beginning to end.
+        pub(crate) async fn swipe(&mut self) {
+            self.send(TraceKind::SwipeBegin, 3).await;
+            self.send_event(motion(10.0, 0.0)).await;
+            self.send(TraceKind::SwipeEnd, 3).await;
+        }
+
+
+        /// Presses (`true`) and releases (`false`) since this was last called.
+        pub(crate) fn button_changes(&mut self) -> Vec<bool> {
+            std::iter::from_fn(|| self.buttons.try_recv().ok()).collect()
+        }
+    }
+
+
+    pub(crate) fn event(kind: TraceKind, fingers: i32) -> TraceEvent {
+        TraceEvent { kind, fingers, device: "Test trackpad".to_string(), ..Default::default() }
+    }
+
+
+    pub(crate) fn motion(dx: f64, dy: f64) -> TraceEvent {
+        TraceEvent { dx, dy, ..event(TraceKind::SwipeUpdate, 3) }
+    }
+
+
+    /// Lets the timer task catch up on what it's been sent.
+    pub(crate) async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+
+    /// Moves tokio's (paused) clock, which the translator's monotonic
+    /// clock and the drag end timer both go by.
+    pub(crate) async fn wait(time: Duration) {
+        tokio::time::advance(time).await;
+        settle().await;
+    }
+
+
+    pub(crate) fn with_delay(ms: u64) -> Configuration {
+        Configuration { drag_end_delay: Duration::from_millis(ms), ..Default::default() }
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn drag_ends_after_the_delay_and_not_before() {
+        let mut harness = Harness::new(with_delay(200));
+
+        harness.swipe().await;
+        assert_eq!(harness.button_changes(), [true]);
+
+        wait(Duration::from_millis(199)).await;
+        assert!(harness.translator.drag_is_held());
+        assert!(harness.button_changes().is_empty());
+
+        wait(Duration::from_millis(2)).await;
+        assert!(!harness.translator.drag_is_held());
+        assert_eq!(harness.button_changes(), [false]);
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn jump_forward_during_drag_end_releases_once() {
+        let mut harness = Harness::new(with_delay(200));
+        harness.swipe().await;
+        harness.button_changes();
+
+        // like a resume, if the clock didn't stand still during suspend
+        wait(Duration::from_secs(60 * 60 * 24)).await;
+        assert!(!harness.translator.drag_is_held());
+        assert_eq!(harness.button_changes(), [false]);
+
+        wait(Duration::from_secs(1)).await;
+        assert!(harness.button_changes().is_empty());
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn manual_clock_jumping_forward_ends_the_drag() {
+        let mut harness = Harness::new(with_delay(200));
+        let clock = Clock::manual(Instant::now());
+        harness.translator.use_clock(clock.clone());
+        harness.swipe().await;
+
+        clock.advance(Duration::from_secs(60 * 60 * 24 * 365));
+        assert!(!harness.translator.drag_is_held());
+        assert!(harness.translator.dump_state(&[]).contains("let go by the timer"));
+
+        // so the next swipe is a new drag, pressing again
+        harness.button_changes();
+        harness.send(TraceKind::SwipeBegin, 3).await;
+        assert_eq!(harness.button_changes(), [true]);
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn manual_clock_jumping_back_keeps_the_drag() {
+        let mut harness = Harness::new(with_delay(200));
+        let clock = Clock::manual(Instant::now() + Duration::from_secs(60));
+        harness.translator.use_clock(clock.clone());
+        harness.swipe().await;
+        harness.button_changes();
+
+        // a clock set back doesn't end the drag early, or panic on the math
+        clock.advance(Duration::from_millis(100));
+        clock.set(clock.now() - Duration::from_secs(30));
+        assert!(harness.translator.drag_is_held());
+        assert!(harness.translator.dump_state(&[]).contains("ending"));
+
+        // and a swipe now still carries on the same drag, with no release
+        harness.send(TraceKind::SwipeBegin, 3).await;
+        assert!(!harness.button_changes().contains(&false));
+        assert!(harness.translator.drag_is_held());
+    }
+
+
+    // The flicker tests are made up, not recorded: they're the events
+    // libinput sends when a trackpad drops a finger from its count for
+    // a few milliseconds, as fingers land or lift.
+
+    #[tokio::test(start_paused = true)]
+    async fn flicker_as_fingers_land_keeps_the_drag() {
+        let mut harness = Harness::new(with_delay(0));
+
+        harness.send(TraceKind::SwipeBegin, 3).await;
+        wait(Duration::from_millis(5)).await;
+        harness.send(TraceKind::SwipeBegin, 2).await;
+        harness.send(TraceKind::SwipeUpdate, 2).await;
+        wait(Duration::from_millis(5)).await;
+        harness.send(TraceKind::SwipeBegin, 3).await;
+        harness.send_event(motion(10.0, 0.0)).await;
+
+        assert!(!harness.button_changes().contains(&false));
+        assert!(harness.translator.drag_is_held());
+
+        harness.send(TraceKind::SwipeEnd, 3).await;
+        wait(FINGER_COUNT_DEBOUNCE + Duration::from_millis(1)).await;
+        assert_eq!(harness.button_changes(), [false]);
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn flicker_as_fingers_lift_keeps_the_drag() {
+        let mut harness = Harness::new(with_delay(0));
+
+        harness.swipe().await;
+        wait(Duration::from_millis(5)).await;
+        harness.send(TraceKind::SwipeBegin, 2).await;
+        harness.send(TraceKind::SwipeEnd, 2).await;
+        wait(Duration::from_millis(5)).await;
+        harness.send(TraceKind::SwipeBegin, 3).await;
+
+        assert!(!harness.button_changes().contains(&false));
+        assert!(harness.translator.drag_is_held());
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn other_input_past_the_debounce_still_ends_the_drag() {
+        let mut harness = Harness::new(with_delay(500));
+
+        harness.send(TraceKind::SwipeBegin, 3).await;
+        wait(FINGER_COUNT_DEBOUNCE + Duration::from_millis(10)).await;
+        harness.send(TraceKind::SwipeBegin, 2).await;
+        assert_eq!(harness.button_changes(), [true, false]);
+
+        harness.swipe().await;
+        wait(FINGER_COUNT_DEBOUNCE + Duration::from_millis(10)).await;
+        harness.send(TraceKind::SwipeBegin, 2).await;
+        assert_eq!(harness.button_changes(), [true, false]);
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn four_finger_gestures_alone_are_left_alone() {
+        let mut harness = Harness::new(with_delay(0));
+
+        for kind in [TraceKind::SwipeBegin, TraceKind::SwipeUpdate, TraceKind::SwipeEnd, TraceKind::PinchBegin, TraceKind::PinchEnd] {
+            harness.send(kind, 4).await;
+        }
+        assert!(harness.button_changes().is_empty());
+        assert!(!harness.translator.vtp.mouse_is_down);
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn four_finger_gesture_after_a_drag_ended_by_the_timer_writes_nothing() {
+        let mut harness = Harness::new(with_delay(100));
+
+        harness.swipe().await;
+        wait(Duration::from_millis(101)).await;
+        assert_eq!(harness.button_changes(), [true, false]);
+
+        harness.send(TraceKind::SwipeBegin, 4).await;
+        harness.send(TraceKind::SwipeUpdate, 4).await;
+        harness.send(TraceKind::SwipeEnd, 4).await;
+        assert!(harness.button_changes().is_empty());
+        assert!(!harness.translator.vtp.mouse_is_down);
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn four_finger_gesture_during_a_drag_ends_it_once() {
+        let mut harness = Harness::new(with_delay(500));
+
+        harness.send(TraceKind::SwipeBegin, 3).await;
+        harness.send_event(motion(5.0, 5.0)).await;
+        wait(FINGER_COUNT_DEBOUNCE + Duration::from_millis(10)).await;
+
+        harness.send(TraceKind::SwipeBegin, 4).await;
+        harness.send(TraceKind::SwipeUpdate, 4).await;
+        harness.send(TraceKind::SwipeEnd, 4).await;
+        assert_eq!(harness.button_changes(), [true, false]);
+
+        // and the timer has nothing left to let go of
+        wait(Duration::from_millis(600)).await;
+        assert!(harness.button_changes().is_empty());
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn four_finger_gesture_during_drag_end_delay_ends_it() {
+        let mut harness = Harness::new(with_delay(500));
+
+        harness.swipe().await;
+        wait(FINGER_COUNT_DEBOUNCE + Duration::from_millis(10)).await;
+        harness.send(TraceKind::SwipeBegin, 4).await;
+        assert_eq!(harness.button_changes(), [true, false]);
+        assert!(!harness.translator.drag_is_held());
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn interleaved_three_and_four_finger_gestures() {
+        let mut harness = Harness::new(with_delay(0));
+
+        // a drag, a workspace swipe, then another drag
+        harness.swipe().await;
+        wait(Duration::from_millis(50)).await;
+        harness.send(TraceKind::SwipeBegin, 4).await;
+        harness.send(TraceKind::SwipeUpdate, 4).await;
+        harness.send(TraceKind::SwipeEnd, 4).await;
+        wait(Duration::from_millis(50)).await;
+        harness.swipe().await;
+        wait(Duration::from_millis(50)).await;
+
+        assert_eq!(harness.button_changes(), [true, false, true, false]);
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn other_input_after_the_timer_ended_a_drag_is_not_a_forced_release() {
+        let mut harness = Harness::new(with_delay(100));
+
+        harness.swipe().await;
+        wait(Duration::from_millis(101)).await;
+        assert_eq!(harness.button_changes(), [true, false]);
+        let before = Stats::current().forced_releases;
+
+        harness.send(TraceKind::SwipeBegin, 4).await;
+        harness.send(TraceKind::PinchBegin, 3).await;
+        harness.translator.pause_on_request(true).await.unwrap();
+        harness.send(TraceKind::SwipeBegin, 3).await;
+        settle().await;
+
+        assert_eq!(Stats::current().forced_releases, before);
+        assert!(harness.button_changes().is_empty());
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn other_input_during_a_drag_is_one_forced_release() {
+        let mut harness = Harness::new(with_delay(500));
+        let before = Stats::current().forced_releases;
+
+        harness.swipe().await;
+        wait(FINGER_COUNT_DEBOUNCE + Duration::from_millis(10)).await;
+        harness.send(TraceKind::SwipeBegin, 4).await;
+        harness.send(TraceKind::SwipeBegin, 4).await;
+
+        assert_eq!(Stats::current().forced_releases, before + 1);
+        assert_eq!(harness.button_changes(), [true, false]);
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn right_tap_after_a_drag_ended_by_the_timer_still_lets_go() {
+        let mut harness = Harness::new(Configuration {
+            three_finger_tap: ThreeFingerTap::Right,
+            ..with_delay(100)
+        });
+
+        harness.swipe().await;
+        wait(Duration::from_secs(1)).await;
+        assert_eq!(harness.button_changes(), [true, false]);
+
+        harness.send(TraceKind::HoldBegin, 3).await;
+        wait(Duration::from_millis(50)).await;
+        harness.send(TraceKind::HoldEnd, 3).await;
+        assert_eq!(harness.button_changes(), [true, false]);
+        assert!(!harness.translator.vtp.mouse_is_down);
+    }
+}
@@ -0,0 +1,216 @@
+// Finding out which window is focused, for pausing drags in certain apps
+// (see `pointer_grab`) and per-app profiles. There's no one way to ask for this on Linux, so
+// this goes through whatever the session has:
+//
+// - Sway and Hyprland, through their own IPC tools
+// - other wlroots-based compositors (river, labwc, Wayfire, ...), through
+//   `lswt`, which lists windows with the foreign-toplevel protocol
+// - X11 sessions, through `xprop` and the EWMH properties on the root window
+//
+// GNOME and KDE Plasma on Wayland don't let other programs see the focused
+// window at all, so there's nothing to be done there.
+
+use std::{process::Command, time::Duration};
+
+use tokio::sync::watch::Sender;
+use tracing::{debug, warn};
+
+use super::output_scale::{detect_compositor, Compositor};
+
+
+// Same reasoning as for the output scale
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+
+/// Where the focused window is read from.
+#[derive(Debug, Clone, Copy)]
+enum WindowSource {
+    Compositor(Compositor),
+    ForeignToplevel,
+    X11
+}
+
+
+/// What we need to know about the focused window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FocusedWindow {
+    pub app_id: String,     // Wayland app ID, or X11 window class
+    pub fullscreen: bool
+}
+
+impl FocusedWindow {
+
+    /// Whether this window belongs to `app`, an app ID or window class
+    /// as written in the config (case doesn't matter).
+    pub fn is_app(&self, app: &str) -> bool {
+        app.eq_ignore_ascii_case(&self.app_id)
+    }
+}
+
+
+fn detect_source() -> Option<WindowSource> {
+
+    if let Some(compositor) = detect_compositor() {
+        return Some(WindowSource::Compositor(compositor));
+    }
+
+    let on_wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let on_x11 = std::env::var_os("DISPLAY").is_some();
+
+    if on_wayland {
+        // lswt being installed isn't enough, the compositor has to support
+        // the protocol too, which a successful run shows
+        let lswt_works = Command::new("lswt").arg("-j").output()
+            .is_ok_and(|output| output.status.success());
+        return lswt_works.then_some(WindowSource::ForeignToplevel);
+    }
+
+    // if it's XWayland, only X11 windows would show up, so only plain X11 counts
+    on_x11.then_some(WindowSource::X11)
+}
+
+
+fn read_focused_window(source: WindowSource) -> Option<FocusedWindow> {
+    match source {
+        WindowSource::Compositor(Compositor::Sway)     => read_from_sway(),
+        WindowSource::Compositor(Compositor::Hyprland) => read_from_hyprland(),
+        WindowSource::ForeignToplevel => read_from_lswt(),
+        WindowSource::X11 => read_from_x11()
+    }
+}
+
+
+fn run_json(program: &str, args: &[&str]) -> Option<serde_json::Value> {
+    let output = Command::new(program).args(args).output().ok()?;
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+
+/// Sway nests windows in workspaces in outputs, so the focused one
+/// has to be searched for through the whole tree.
+fn find_focused_node(node: &serde_json::Value) -> Option<&serde_json::Value> {
+
+    if node["focused"] == true {
+        return Some(node);
+    }
+
+    ["nodes", "floating_nodes"].iter()
+        .filter_map(|children| node[children].as_array())
+        .flatten()
+        .find_map(find_focused_node)
+}
+
+
+fn read_from_sway() -> Option<FocusedWindow> {
+
+    let tree = run_json("swaymsg", &["-t", "get_tree", "--raw"])?;
+    let focused = find_focused_node(&tree)?;
+
+    // XWayland windows don't have an app ID, only a class
+    let app_id = focused["app_id"].as_str()
+        .or_else(|| focused["window_properties"]["class"].as_str())?;
+
+    Some(
+        FocusedWindow {
+            app_id: app_id.to_string(),
+            fullscreen: focused["fullscreen_mode"].as_u64().is_some_and(|mode| mode > 0)
+        }
+    )
+}
+
+
+fn read_from_hyprland() -> Option<FocusedWindow> {
+
+    let window = run_json("hyprctl", &["activewindow", "-j"])?;
+
+    // older versions of Hyprland give a bool here, newer ones a mode number
+    let fullscreen = &window["fullscreen"];
+
+    Some(
+        FocusedWindow {
+            app_id: window["class"].as_str()?.to_string(),
+            fullscreen: fullscreen.as_bool()
+                .unwrap_or_else(|| fullscreen.as_u64().is_some_and(|mode| mode > 0))
+        }
+    )
+}
+
+
+fn read_from_lswt() -> Option<FocusedWindow> {
+
+    let listing = run_json("lswt", &["-j"])?;
+    let focused = listing["toplevels"].as_array()?
+        .iter()
+        .find(|toplevel| toplevel["activated"] == true)?;
+
+    Some(
+        FocusedWindow {
+            app_id: focused["app-id"].as_str()?.to_string(),
+            fullscreen: focused["fullscreen"] == true
+        }
+    )
+}
+
+
+fn read_from_x11() -> Option<FocusedWindow> {
+
+    // looks like `_NET_ACTIVE_WINDOW: window id # 0x3e00007`
+    let active = Command::new("xprop").args(["-root", "-notype", "_NET_ACTIVE_WINDOW"]).output().ok()?;
+    let active = String::from_utf8_lossy(&active.stdout);
+    let window_id = active.split_whitespace().last()?;
+    if !window_id.starts_with("0x") || window_id == "0x0" {
+        return None;
+    }
+
+    // and this like
+    //   WM_CLASS = "navigator", "firefox"
+    //   _NET_WM_STATE = _NET_WM_STATE_FULLSCREEN
+    let props = Command::new("xprop")
+        .args(["-id", window_id, "-notype", "WM_CLASS", "_NET_WM_STATE"])
+        .output()
+        .ok()?;
+    let props = String::from_utf8_lossy(&props.stdout);
+
+    let mut app_id = None;
+    let mut fullscreen = false;
+    for line in props.lines() {
+        if let Some(classes) = line.strip_prefix("WM_CLASS = ") {
+            // the second string is the class, which is what other tools go by
+            app_id = classes.split(", ")
+                .last()
+                .map(|class| class.trim_matches('"').to_string());
+        } else if line.starts_with("_NET_WM_STATE = ") {
+            fullscreen = line.contains("_NET_WM_STATE_FULLSCREEN");
+        }
+    }
+
+    Some(FocusedWindow { app_id: app_id?, fullscreen })
+}
+
+
+/// Keeps track of the focused window, sending it through `tx` whenever
+/// it changes (or `None`, if no window has focus).
+pub async fn watch_focused_window(tx: Sender<Option<FocusedWindow>>) {
+
+    let Some(source) = tokio::task::spawn_blocking(detect_source).await.ok().flatten() else {
+        warn!("Could not find a way to see which window is focused (supported are Sway, \
+            Hyprland, other wlroots compositors with `lswt` installed, and X11 with `xprop` \
+            installed), so drags will not be paused or use per-app profiles.");
+        return;
+    };
+    debug!("Following the focused window with {:?}", source);
+
+    loop {
+        let focused = tokio::task::spawn_blocking(move || read_focused_window(source))
+            .await
+            .ok()
+            .flatten();
+
+        if *tx.borrow() != focused {
+            debug!("Focused window is now {:?}", focused);
+            if tx.send(focused).is_err() { return; }
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
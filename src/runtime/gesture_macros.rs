@@ -0,0 +1,103 @@
+// Recognizing short sequences of three-finger gestures (like a swipe down,
+// then up) and running a command for them, as set up in `gestureMacros`.
+//
+// The gestures themselves are handled as usual while this watches, so a
+// swipe in a macro is still a drag. Macros made of taps, or of quick
+// flicks over an empty part of the screen, work best for that reason.
+
+use std::{
+    collections::VecDeque,
+    process::{Command, Stdio},
+    time::Instant
+};
+
+use tracing::{debug, info, warn};
+
+use crate::init::config::{GestureMacro, MacroStep};
+
+
+// anything shorter than this (in trackpad units) is a wobble, not a swipe
+const MIN_SWIPE_DISTANCE: f64 = 100.0;
+
+// no macro is going to be longer than this many steps
+const MAX_REMEMBERED_STEPS: usize = 8;
+
+
+/// The step a whole swipe counts as, going by which way it went furthest,
+/// if it went far enough to count at all.
+pub fn swipe_step(dx: f64, dy: f64) -> Option<MacroStep> {
+    if dx.hypot(dy) < MIN_SWIPE_DISTANCE {
+        return None;
+    }
+
+    Some(match (dx.abs() > dy.abs(), dx > 0.0, dy > 0.0) {
+        (true, true, _)   => MacroStep::SwipeRight,
+        (true, false, _)  => MacroStep::SwipeLeft,
+        (false, _, true)  => MacroStep::SwipeDown,
+        (false, _, false) => MacroStep::SwipeUp
+    })
+}
+
+
+/// Remembers the last few steps, and checks them against the macros
+/// as each new one comes in.
+#[derive(Debug, Default)]
+pub struct MacroRecognizer {
+    recent: VecDeque<(Instant, MacroStep)>
+}
+
+impl MacroRecognizer {
+
+    /// Adds a step, done at `now`, and returns the macro it completes, if
+    /// any. Steps used up by a macro are forgotten, so they can't start another.
+    pub fn push<'a>(&mut self, now: Instant, step: MacroStep, macros: &'a [GestureMacro]) -> Option<&'a GestureMacro> {
+
+        self.recent.push_back((now, step));
+        if self.recent.len() > MAX_REMEMBERED_STEPS {
+            self.recent.pop_front();
+        }
+        debug!("Macro step: {:?}", step);
+
+        let matched = macros.iter().find(|m| {
+            let len = m.sequence.len();
+            len > 0 && len <= self.recent.len() && {
+                let tail = self.recent.range(self.recent.len() - len..);
+                let first_at = self.recent[self.recent.len() - len].0;
+
+                now.saturating_duration_since(first_at) <= m.within
+                    && tail.map(|(_, step)| step).eq(m.sequence.iter())
+            }
+        });
+
+        if matched.is_some() {
+            self.recent.clear();
+        }
+        matched
+    }
+}
+
+
+/// Runs a macro's command with `sh -c`, without waiting on it.
+pub fn run_macro(gesture_macro: &GestureMacro) {
+    run_command(&format!("Gesture macro {:?}", gesture_macro.sequence), &gesture_macro.command);
+}
+
+
+/// Runs `command` with `sh -c` for whatever `recognized` describes,
+/// without waiting on it.
+pub fn run_command(recognized: &str, command: &str) {
+
+    info!("{} recognized, running `{}`", recognized, command);
+
+    let spawned = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .spawn();
+
+    match spawned {
+        // reaped on another thread, so it doesn't hang around as a zombie
+        Ok(mut child) => { std::thread::spawn(move || child.wait()); },
+        Err(e) => warn!("Could not run `{}`: {}", command, e)
+    }
+}
@@ -0,0 +1,163 @@
+// Matching libinput's gestures against the named gestures in `gestures.d`
+// (see `gesture_definitions`), and running the command of whichever fits
+// best. This follows every gesture libinput sends, whatever the finger
+// count, and decides once it's over: a swipe by the way it went furthest,
+// a pinch by whether it ended smaller or bigger than it started, and a
+// hold if the fingers lifted without moving. Of the definitions that
+// match, the one with the highest priority wins (the first loaded, on a
+// tie), and only that one runs.
+//
+// This goes before the built-in drag, but mostly stays out of its way:
+// three-finger swipes can't be defined at all, so a definition only takes
+// over a three-finger gesture for holds (which are otherwise taps).
+
+use tracing::debug;
+
+use crate::init::gesture_definitions::{GestureDefinition, GestureDirection, GestureKind};
+use super::{
+    gesture_macros,
+    trace::{TraceEvent, TraceKind}
+};
+
+
+// how far a pinch's scale has to get from 1 to count as going in or out
+const MIN_PINCH_CHANGE: f64 = 0.2;
+
+
+/// A gesture in progress, as far as matching it goes.
+#[derive(Debug, Clone, Copy)]
+struct Ongoing {
+    kind: GestureKind,
+    fingers: i32,
+    motion: (f64, f64),     // for swipes
+    scale: f64              // for pinches
+}
+
+
+/// Follows gestures, and says which definition one matches once it's over.
+#[derive(Debug, Default)]
+pub struct GestureMatcher {
+    definitions: Vec<GestureDefinition>,
+    ongoing: Option<Ongoing>
+}
+
+impl GestureMatcher {
+
+    pub fn new(definitions: Vec<GestureDefinition>) -> GestureMatcher {
+        GestureMatcher { definitions, ongoing: None }
+    }
+
+
+    /// Whether any definition takes over three-finger holds, in which case
+    /// a three-finger tap's click has to wait to see if one matches.
+    pub fn takes_three_finger_holds(&self) -> bool {
+        self.definitions.iter().any(|d| d.fingers == 3 && d.kind == GestureKind::Hold)
+    }
+
+
+    /// Follows along with `event`, returning the definition the gesture
+    /// matches if this event ended it.
+    pub fn follow(&mut self, event: &TraceEvent) -> Option<&GestureDefinition> {
+
+        if self.definitions.is_empty() {
+            return None;
+        }
+        let fingers = event.fingers;
+
+        let begin = |kind| Ongoing { kind, fingers, motion: (0.0, 0.0), scale: 1.0 };
+        let ended = match event.kind {
+            TraceKind::SwipeBegin => {
+                self.ongoing = Some(begin(GestureKind::Swipe));
+                None
+            },
+            TraceKind::SwipeUpdate => {
+                if let Some(ongoing) = &mut self.ongoing {
+                    ongoing.motion.0 += event.dx;
+                    ongoing.motion.1 += event.dy;
+                }
+                None
+            },
+            TraceKind::PinchBegin => {
+                self.ongoing = Some(begin(GestureKind::Pinch));
+                None
+            },
+            TraceKind::PinchUpdate => {
+                if let (Some(ongoing), Some(scale)) = (&mut self.ongoing, event.scale) {
+                    ongoing.scale = scale;
+                }
+                None
+            },
+            TraceKind::HoldBegin => {
+                self.ongoing = Some(begin(GestureKind::Hold));
+                None
+            },
+            // a cancelled hold is one that turned into a swipe or pinch
+            TraceKind::SwipeEnd | TraceKind::PinchEnd | TraceKind::HoldEnd => self.finish(event.cancelled),
+            _ => None
+        }?;
+
+        self.best_match(&ended)
+    }
+
+
+    fn finish(&mut self, cancelled: bool) -> Option<Ongoing> {
+        let ongoing = self.ongoing.take()?;
+        (!cancelled).then_some(ongoing)
+    }
+
+
+    fn best_match(&self, gesture: &Ongoing) -> Option<&GestureDefinition> {
+
+        let matched = self.definitions.iter()
+            .filter(|d| d.kind == gesture.kind && d.fingers == gesture.fingers)
+            .filter(|d| matches_direction(d, gesture))
+            // max_by_key keeps the last of equals, so go backwards to keep the first
+            .rev()
+            .max_by_key(|d| d.priority);
+
+        if let Some(definition) = matched {
+            debug!("{}-finger {:?} matched the named gesture '{}'", gesture.fingers, gesture.kind, definition.name);
+        }
+        matched
+    }
+}
+
+
+fn matches_direction(definition: &GestureDefinition, gesture: &Ongoing) -> bool {
+    match gesture.kind {
+        GestureKind::Swipe => {
+            let (dx, dy) = gesture.motion;
+            if dx.hypot(dy) < definition.min_distance {
+                return false;
+            }
+            definition.direction.is_none_or(|direction| direction == swipe_direction(dx, dy))
+        },
+        GestureKind::Pinch => {
+            let direction = if gesture.scale <= 1.0 - MIN_PINCH_CHANGE {
+                GestureDirection::In
+            } else if gesture.scale >= 1.0 + MIN_PINCH_CHANGE {
+                GestureDirection::Out
+            } else {
+                return false;
+            };
+            definition.direction.is_none_or(|wanted| wanted == direction)
+        },
+        GestureKind::Hold => true
+    }
+}
+
+
+fn swipe_direction(dx: f64, dy: f64) -> GestureDirection {
+    match (dx.abs() > dy.abs(), dx > 0.0, dy > 0.0) {
+        (true, true, _)   => GestureDirection::Right,
+        (true, false, _)  => GestureDirection::Left,
+        (false, _, true)  => GestureDirection::Down,
+        (false, _, false) => GestureDirection::Up
+    }
+}
+
+
+/// Runs a named gesture's command, the same way as a gesture macro's.
+pub fn run_definition(definition: &GestureDefinition) {
+    gesture_macros::run_command(&format!("Named gesture '{}'", definition.name), &definition.command);
+}
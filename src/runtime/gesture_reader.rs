@@ -0,0 +1,77 @@
+// Reading gestures from the real trackpad one at a time, for modes that
+// need to watch what the user does (like `calibrate`) rather than
+// translate it. The main loop doesn't use this, since it also has to
+// keep an eye on signals and the control socket while it waits.
+
+use std::{
+    collections::VecDeque,
+    io::Error,
+    os::fd::{AsRawFd, RawFd},
+    time::Instant
+};
+
+use input::Libinput;
+use tokio::io::unix::AsyncFd;
+use tracing::trace;
+
+use super::{event_handler::Gesture, trace::TraceEvent};
+
+
+pub struct GestureReader {
+    libinput: Libinput,
+    fd: AsyncFd<RawFd>,
+    pending: VecDeque<(Instant, TraceEvent)>
+}
+
+impl GestureReader {
+
+    pub fn new(libinput: Libinput) -> Result<GestureReader, Error> {
+        let fd = AsyncFd::new(libinput.as_raw_fd())?;
+        Ok(GestureReader { libinput, fd, pending: VecDeque::new() })
+    }
+
+
+    /// Waits for the next gesture, returning it along with when it arrived.
+    pub async fn next(&mut self) -> Result<(Instant, Gesture), Error> {
+        let (at, event) = self.next_event().await?;
+        Ok((at, Gesture::from_event(&event, false)))
+    }
+
+
+    /// Like `next()`, but with the event as libinput gave it, for when
+    /// more than the gesture it makes is needed (like its timestamp).
+    pub async fn next_event(&mut self) -> Result<(Instant, TraceEvent), Error> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
+            }
+
+            let mut guard = self.fd.readable().await?;
+            guard.clear_ready();
+            self.read_available()?;
+        }
+    }
+
+
+    /// Throws away anything that came in while nobody was reading,
+    /// e.g. while waiting for an answer at a prompt.
+    pub fn discard_pending(&mut self) -> Result<(), Error> {
+        self.read_available()?;
+        self.pending.clear();
+        Ok(())
+    }
+
+
+    fn read_available(&mut self) -> Result<(), Error> {
+        self.libinput.dispatch()?;
+
+        let now = Instant::now();
+        for event in &mut self.libinput {
+            let event = TraceEvent::from_event(&event);
+            trace!("Gesture read: {:?}", event);
+            self.pending.push_back((now, event));
+        }
+
+        Ok(())
+    }
+}
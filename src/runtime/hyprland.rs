@@ -0,0 +1,171 @@
+// Talking to Hyprland directly over its IPC socket, for two things:
+//
+// - Hyprland's own workspace swipe gesture, if set to three fingers, fights
+//   with three-finger drags. At startup, this checks for that, and (if
+//   `hyprlandDisableConflicts` is on) turns the swipe off until exit.
+// - Other programs listening on Hyprland's event socket can be told when
+//   drags start and end (with `hyprlandDragEvents`), as `custom>>l3fd-drag-start`
+//   and `custom>>l3fd-drag-end` events.
+
+use std::{
+    io::{Error, ErrorKind, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf
+};
+
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tracing::{debug, info, warn};
+
+
+const DRAG_START_EVENT: &str = "l3fd-drag-start";
+const DRAG_END_EVENT: &str   = "l3fd-drag-end";
+
+
+pub fn is_running() -> bool {
+    std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some()
+}
+
+
+/// Hyprland's request socket.
+fn socket_path() -> Option<PathBuf> {
+    find_socket(".socket.sock")
+}
+
+
+/// Hyprland's event socket, which sends a line for each event (like
+/// `focusedmon>>DP-1,2`) to anything connected.
+pub fn event_socket_path() -> Option<PathBuf> {
+    find_socket(".socket2.sock")
+}
+
+
+/// One of Hyprland's sockets. They moved from /tmp to the runtime
+/// directory in v0.40, so both places are checked.
+fn find_socket(name: &str) -> Option<PathBuf> {
+
+    let signature = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join("hypr"));
+
+    runtime_dir.into_iter()
+        .chain([PathBuf::from("/tmp/hypr")])
+        .map(|dir| dir.join(&signature).join(name))
+        .find(|path| path.exists())
+}
+
+
+/// Sends a single request (like `j/getoption gestures:workspace_swipe`),
+/// and returns Hyprland's reply. Hyprland closes the connection after
+/// replying, so the reply is everything up to that.
+fn request(command: &str) -> Result<String, Error> {
+
+    let path = socket_path()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "Hyprland's IPC socket could not be found"))?;
+
+    let mut stream = UnixStream::connect(path)?;
+    stream.write_all(command.as_bytes())?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply)
+}
+
+
+/// The value of an int (or bool) Hyprland option, if there is such an option.
+pub fn get_int_option(name: &str) -> Option<i64> {
+    let reply = request(&format!("j/getoption {name}")).ok()?;
+    let option: serde_json::Value = serde_json::from_str(&reply).ok()?;
+    option["int"].as_i64()
+}
+
+
+/// Sets an int (or bool) Hyprland option until Hyprland's config is reloaded.
+pub fn set_int_option(name: &str, value: i64) -> Result<(), Error> {
+    match request(&format!("keyword {name} {value}"))? {
+        reply if reply.trim() == "ok" => Ok(()),
+        reply => Err(Error::other(reply.trim().to_string()))
+    }
+}
+
+
+/// Options changed by `resolve_gesture_conflicts()`, to be put back on exit.
+#[derive(Debug, Default)]
+pub struct ChangedOptions(Vec<(String, i64)>);
+
+
+/// Checks whether Hyprland's workspace swipe is set to three fingers, and
+/// if so, either turns it off (if `disable` is set) or warns about it.
+pub fn resolve_gesture_conflicts(disable: bool) -> ChangedOptions {
+
+    let mut changed = ChangedOptions::default();
+
+    // Newer versions of Hyprland set up gestures with `gesture = ...` lines
+    // instead, which can't be queried, so there's nothing to check there
+    let Some(swipe_enabled) = get_int_option("gestures:workspace_swipe") else {
+        debug!("Hyprland has no workspace_swipe option, so there's no gesture conflict to check");
+        return changed;
+    };
+    let swipe_fingers = get_int_option("gestures:workspace_swipe_fingers").unwrap_or(3);
+
+    if swipe_enabled == 0 || swipe_fingers != 3 {
+        return changed;
+    }
+
+    if !disable {
+        warn!("Hyprland's workspace swipe is set to three fingers, which will conflict with \
+            three-finger drags. Set `gestures:workspace_swipe_fingers` to 4 in your Hyprland \
+            config, or set `hyprlandDisableConflicts` to true to have it turned off while \
+            this program runs.");
+        return changed;
+    }
+
+    match request("keyword gestures:workspace_swipe 0") {
+        Ok(reply) if reply.trim() == "ok" => {
+            info!("Turned off Hyprland's three-finger workspace swipe until exit");
+            changed.0.push(("gestures:workspace_swipe".to_string(), swipe_enabled));
+        },
+        Ok(reply) => warn!("Could not turn off Hyprland's workspace swipe: {}", reply.trim()),
+        Err(e) => warn!("Could not turn off Hyprland's workspace swipe: {}", e)
+    }
+
+    changed
+}
+
+
+/// Puts back the options `resolve_gesture_conflicts()` changed.
+pub fn restore_options(changed: ChangedOptions) {
+    for (name, value) in changed.0 {
+        match request(&format!("keyword {name} {value}")) {
+            Ok(_) => debug!("Restored Hyprland option {} to {}", name, value),
+            Err(e) => warn!("Could not restore Hyprland option {} to {}: {}", name, value, e)
+        }
+    }
+}
+
+
+/// Sends a custom event to Hyprland's event socket whenever a drag
+/// starts or ends, for as long as the virtual trackpad is around.
+pub async fn forward_drag_events(mut button_events: Receiver<bool>) {
+
+    let mut last_held = false;
+    loop {
+        let held = match button_events.recv().await {
+            Ok(held) => held,
+            // missing a few only matters if the last one is missed,
+            // and that's the one that comes next
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return
+        };
+
+        if held == last_held { continue; }
+        last_held = held;
+
+        let event = if held { DRAG_START_EVENT } else { DRAG_END_EVENT };
+        let sent = tokio::task::spawn_blocking(move || request(&format!("dispatch event {event}"))).await;
+
+        if let Ok(Err(e)) = sent {
+            debug!("Could not send {} to Hyprland: {}", event, e);
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    io::{BufRead, BufReader, Error, ErrorKind, Write},
+    os::{fd::{FromRawFd, RawFd}, unix::net::{UnixListener as StdUnixListener, UnixStream as StdUnixStream}},
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration
+};
+
+use serde::{Deserialize, Serialize};
+use signal_hook::{consts::{SIGUSR1, SIGUSR2}, iterator::Signals};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader},
+    net::{UnixListener, UnixStream},
+    sync::{mpsc::Sender, oneshot}
+};
+use tracing::{debug, error, info, warn};
+
+use super::reexec;
+use crate::init::run_mode;
+
+
+/// How long a client waits on the daemon before giving up. This
+/// is a little longer than the daemon's own shutdown bound, so a
+/// `stop` that runs right up to the limit still gets its reply.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(3);
+
+// where systemd puts the first socket it hands over (sd_listen_fds(3))
+pub const SD_LISTEN_FDS_START: RawFd = 3;
+
+// whether the control socket came from systemd, which then owns its file
+static SOCKET_ACTIVATED: AtomicBool = AtomicBool::new(false);
+
+
+/// A request sent over the control socket, one JSON object per line.
+/// For example, `{"cmd":"stop"}`, or 
+/// `{"cmd":"config-set","key":"acceleration","value":1.5,"persist":false}`.
+/// Scripts are what this is for as much as the CLI, so requests only
+/// ever gain names (like `set-option`), never lose them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "cmd", rename_all = "kebab-case")]
+pub enum IpcRequest {
+    Stop,
+    Reexec,
+    Capabilities,                         // version, backends, and devices, as JSON
+    Status,                               // uptime, devices, whether a drag is held, and the config, as JSON
+    Stats,                                // counts of gestures, drags, and errors since starting, as JSON
+    Reload,                               // read the config file again
+    ReopenLog,                            // for logrotate
+    Pause,                                // stop translating gestures, releasing any held drag
+    Resume,
+    TogglePause,                          // whichever of those it isn't; what SIGUSR1 sends
+    DumpState,                            // log the translator's state, and reply with it; what SIGUSR2 sends
+    PointerLock { locked: Option<bool> }, // a hint for `pointerLockPassthrough`; none goes back to guessing
+    #[serde(alias = "get-option")]
+    ConfigGet { key: Option<String> },    // no key gets the whole config
+    #[serde(alias = "set-option")]
+    ConfigSet { key: String, value: serde_json::Value, #[serde(default)] persist: bool }
+}
+
+/// The daemon's reply to an `IpcRequest`, also one JSON object per line.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IpcResponse {
+    pub ok: bool,
+    pub message: String
+}
+
+impl IpcResponse {
+
+    pub fn ok(message: impl Into<String>) -> Self {
+        IpcResponse { ok: true, message: message.into() }
+    }
+
+    pub fn err(message: impl Into<String>) -> Self {
+        IpcResponse { ok: false, message: message.into() }
+    }
+}
+
+/// A request as handed to the main loop, along with where
+/// to send the reply once the main loop has dealt with it.
+#[derive(Debug)]
+pub struct IpcMessage {
+    pub request: IpcRequest,
+    pub reply: oneshot::Sender<IpcResponse>
+}
+
+
+pub fn get_socket_path() -> Result<PathBuf, Error> {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) => Ok(PathBuf::from(runtime_dir).join("l3fd.sock")),
+        None => Err(
+            Error::new(
+                ErrorKind::NotFound,
+                "$XDG_RUNTIME_DIR not defined in environment"
+            )
+        )
+    }
+}
+
+
+/// Binds the control socket, or uses `activated`, the one systemd opened
+/// (from `take_activated_socket()`), if it started this instance through
+/// a socket unit. Otherwise, any socket file left over from a previous
+/// run is removed first, since nothing can be listening on it anymore if
+/// we got this far.
+pub fn bind_listener(activated: Option<Result<StdUnixListener, Error>>) -> Result<UnixListener, Error> {
+
+    if let Some(listener) = activated {
+        let listener = listener?;
+        listener.set_nonblocking(true)?;
+        SOCKET_ACTIVATED.store(true, Ordering::Relaxed);
+        info!("Listening for control commands on the socket systemd handed over");
+        return UnixListener::from_std(listener);
+    }
+
+    let socket_path = get_socket_path()?;
+
+    if socket_path.exists() {
+        debug!("Removing stale control socket at {:?}", socket_path);
+        std::fs::remove_file(&socket_path)?;
+    }
+
+    let listener = UnixListener::bind(&socket_path)?;
+    info!("Listening for control commands on {:?}", socket_path);
+
+    Ok(listener)
+}
+
+
+/// The socket systemd passed in, if it started this process for a
+/// connection to it (`LISTEN_FDS`, with `LISTEN_PID` as this process, so
+/// it wasn't meant for a parent). The variables are cleared either way,
+/// and the socket closed on exec, so nothing this starts thinks it's
+/// been handed one too. Clearing them isn't safe once other threads
+/// could be reading the environment, so this has to be called before
+/// the runtime starts, and before the logger's up.
+pub fn take_activated_socket() -> Option<Result<StdUnixListener, Error>> {
+
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    if pid?.parse::<u32>().ok()? != std::process::id() {
+        return None;
+    }
+    match fds?.parse::<u32>() {
+        Ok(0) | Err(_) => return None,
+        Ok(1) => (),
+        Ok(_) => run_mode::pre_warn("systemd handed over more than one socket; only the first is used")
+    }
+
+    if let Err(e) = reexec::set_cloexec(SD_LISTEN_FDS_START, true) {
+        return Some(Err(e));
+    }
+    // SAFETY: systemd opened this for this process, and said so above,
+    // and nothing else here knows about it
+    Some(Ok(unsafe { StdUnixListener::from_raw_fd(SD_LISTEN_FDS_START) }))
+}
+
+
+/// Whether the control socket was handed over by systemd.
+pub fn is_socket_activated() -> bool {
+    SOCKET_ACTIVATED.load(Ordering::Relaxed)
+}
+
+
+/// Removes the control socket file, if there is one (and it isn't
+/// systemd's, which it keeps listening on). Failing to do so is not
+/// worth more than a warning, since the next run cleans it up.
+pub fn remove_socket_file() {
+    if is_socket_activated() {
+        return;
+    }
+    if let Ok(socket_path) = get_socket_path() {
+        if let Err(e) = std::fs::remove_file(&socket_path) {
+            if e.kind() != ErrorKind::NotFound {
+                warn!("Could not remove control socket at {:?}: {}", socket_path, e);
+            }
+        }
+    }
+}
+
+
+/// Accepts connections on the control socket forever, forwarding each
+/// request to the main loop through `tx`. Each connection is handled
+/// on its own task, so a client that never finishes its line can't
+/// hold up anyone else.
+pub async fn listen(listener: UnixListener, tx: Sender<IpcMessage>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle_connection(stream, tx.clone()));
+            },
+            Err(e) => error!("Failed to accept control connection: {}", e)
+        }
+    }
+}
+
+
+async fn handle_connection(stream: UnixStream, tx: Sender<IpcMessage>) {
+
+    let (read_half, mut write_half) = stream.into_split();
+    let mut lines = AsyncBufReader::new(read_half).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+
+        let response = match serde_json::from_str::<IpcRequest>(&line) {
+            Ok(request) => {
+                debug!("Control request received: {:?}", request);
+                forward_request(request, &tx).await
+            },
+            Err(e) => IpcResponse::err(format!("malformed request: {e}"))
+        };
+
+        // serializing a struct of a bool and a String can't fail
+        let mut reply = serde_json::to_string(&response)
+            .expect("IpcResponse should always serialize");
+        reply.push('\n');
+
+        if let Err(e) = write_half.write_all(reply.as_bytes()).await {
+            warn!("Could not reply to control client: {}", e);
+            break;
+        }
+    }
+}
+
+
+/// Hands `request` to the main loop, as if it came over the control
+/// socket, and waits for the reply.
+pub async fn forward_request(request: IpcRequest, tx: &Sender<IpcMessage>) -> IpcResponse {
+
+    let (reply_tx, reply_rx) = oneshot::channel();
+
+    if tx.send(IpcMessage { request, reply: reply_tx }).await.is_err() {
+        return IpcResponse::err("daemon is shutting down");
+    }
+
+    reply_rx.await
+        .unwrap_or_else(|_| IpcResponse::err("daemon dropped the request"))
+}
+
+
+/// Turns SIGUSR1 into `toggle-pause` and SIGUSR2 into `dump-state`, as
+/// if they'd come over the control socket, so pausing can go on a hotkey
+/// with just `pkill -USR1 linux-3-finger-drag`, and the state can be got
+/// at without the socket (or from a bug report's steps).
+pub fn forward_signals(tx: Sender<IpcMessage>) -> Result<(), Error> {
+
+    let mut signals = Signals::new([SIGUSR1, SIGUSR2])?;
+
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            let request = if signal == SIGUSR1 { IpcRequest::TogglePause } else { IpcRequest::DumpState };
+            // no one's waiting on the reply; the main loop logs what happened
+            let (reply, _) = oneshot::channel();
+            if tx.blocking_send(IpcMessage { request, reply }).is_err() {
+                return;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+
+/// Client side: send a single request to the running daemon and wait
+/// for its reply. This is blocking, since the client has nothing
+/// else to do in the meantime.
+pub fn send_request(request: &IpcRequest) -> Result<IpcResponse, Error> {
+
+    let socket_path = get_socket_path()?;
+    let mut stream = StdUnixStream::connect(&socket_path)
+        .map_err(|e|
+            Error::new(
+                e.kind(),
+                format!("Could not connect to a running instance at {:?}: {}", socket_path, e)
+            )
+        )?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+
+    Ok(serde_json::from_str::<IpcResponse>(&reply)?)
+}
@@ -2,5 +2,39 @@
 // and VirtualTrackpad::clone are used
 // during initialization, but the rest
 // here is used in runtime only.
+pub mod accel_curve;
+pub mod bench;
+pub mod bench_backends;
+pub mod calibrate;
+pub mod capabilities;
+pub mod clock;
+pub mod config_reload;
+pub mod dbus_properties;
+pub mod dbus_signals;
+pub mod dead_zone;
+pub mod desktop_speed;
+pub mod drag_events;
 pub mod event_handler;
+pub mod focused_window;
+pub mod gesture_macros;
+pub mod gesture_matcher;
+pub mod gesture_reader;
+pub mod hyprland;
+pub mod ipc;
+pub mod output_scale;
+pub mod overload;
+pub mod pointer_grab;
+pub mod reexec;
+pub mod replay;
+pub mod report_rate;
+pub mod resting_thumb;
+pub mod self_test;
+pub mod sessions;
+pub mod simulate;
+pub mod stats;
+pub mod status;
+pub mod supervisor;
+pub mod tap_suppression;
+pub mod touch_output;
+pub mod trace;
 pub mod virtual_trackpad;
@@ -0,0 +1,200 @@
+// Scales drag motion by a multiplier set for each output (monitor) in
+// `outputScales`, following whichever output is in use, for mixed-DPI
+// setups where the same `acceleration` feels different on each screen.
+//
+// The compositor's own output scale isn't applied here: Sway and Hyprland
+// already scale relative pointer motion (which they handle in logical
+// pixels) by it, so multiplying by it again would make every drag on a
+// 2x output twice as fast.
+//
+// Only the compositor knows which output is in use, so this only works on
+// compositors we know how to ask (Sway and Hyprland, for now). Both send
+// an event when focus moves to another output, so that's followed,
+// instead of asking over and over.
+
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+    process::Command
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+    sync::watch::Sender
+};
+use tracing::{debug, info, warn};
+
+use super::hyprland;
+
+
+// Sway's IPC messages start with this, then the payload's length and
+// the message type, both as native-endian u32s
+const SWAY_IPC_MAGIC: &[u8] = b"i3-ipc";
+const SWAY_HEADER_LEN: usize = 14;
+const SWAY_SUBSCRIBE: u32 = 2;
+
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Compositor { Sway, Hyprland }
+
+
+/// The output the compositor says is focused.
+#[derive(Debug, Clone, PartialEq)]
+struct FocusedOutput {
+    name: String,
+    width: Option<f64>  // in logical pixels (i.e. after scaling)
+}
+
+
+pub(super) fn detect_compositor() -> Option<Compositor> {
+    if std::env::var_os("SWAYSOCK").is_some() {
+        Some(Compositor::Sway)
+    } else if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        Some(Compositor::Hyprland)
+    } else {
+        None
+    }
+}
+
+
+fn read_focused_output(compositor: Compositor) -> Option<FocusedOutput> {
+
+    // Conveniently, both of these give a JSON array of outputs
+    // with the same field names for what we need here
+    let output = match compositor {
+        Compositor::Sway     => Command::new("swaymsg").args(["-t", "get_outputs", "--raw"]).output(),
+        Compositor::Hyprland => Command::new("hyprctl").args(["monitors", "-j"]).output()
+    }.ok()?;
+
+    let outputs: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    let focused = outputs.as_array()?
+        .iter()
+        .find(|output| output["focused"] == true)?;
+
+    let scale = focused["scale"].as_f64().unwrap_or(1.0);
+
+    // ...except for the width, which Sway gives after scaling, and Hyprland before
+    let width = match compositor {
+        Compositor::Sway     => focused["rect"]["width"].as_f64(),
+        Compositor::Hyprland => focused["width"].as_f64().map(|width| width / scale)
+    };
+
+    Some(
+        FocusedOutput {
+            name: focused["name"].as_str()?.to_string(),
+            width
+        }
+    )
+}
+
+
+/// The width of the focused output in logical pixels, if the
+/// compositor is one we know how to ask.
+pub fn focused_output_width() -> Option<f64> {
+    read_focused_output(detect_compositor()?)?.width
+}
+
+
+/// Keeps track of the focused output, and sends its multiplier from
+/// `overrides` (or 1.0, for outputs not in it) through `tx` whenever
+/// focus moves to another output.
+pub async fn watch_output_scale(tx: Sender<f64>, overrides: HashMap<String, f64>) {
+
+    let Some(compositor) = detect_compositor() else {
+        warn!("Scaling drags by output is only supported on Sway and Hyprland, \
+            so drags will not be scaled by output.");
+        return;
+    };
+    if overrides.is_empty() {
+        warn!("scaleByOutput is on, but outputScales is empty, so drags will not be scaled by output.");
+        return;
+    }
+    debug!("Following the focused output on {:?}", compositor);
+
+    let mut last_output = None;
+    let mut follow = |output: &str| {
+        if last_output.as_deref() == Some(output) { return true; }
+
+        let factor = overrides.get(output).copied().unwrap_or(1.0);
+        info!("Output '{}' is focused; scaling drag motion by {:.2}", output, factor);
+        last_output = Some(output.to_string());
+
+        // the only receiver is the translator, so if it's gone, so are we
+        tx.send(factor).is_ok()
+    };
+
+    // the events only say when focus moves, so where it starts is asked for
+    let focused = tokio::task::spawn_blocking(move || read_focused_output(compositor))
+        .await
+        .ok()
+        .flatten();
+    if let Some(output) = focused {
+        if !follow(&output.name) { return; }
+    }
+
+    let followed = match compositor {
+        Compositor::Sway     => follow_sway(follow).await,
+        Compositor::Hyprland => follow_hyprland(follow).await
+    };
+    if let Err(e) = followed {
+        warn!("Lost track of the focused output ({}), so drags will keep \
+            the current output's multiplier.", e);
+    }
+}
+
+
+/// Calls `follow` with the output's name whenever Sway's focus moves to
+/// a workspace (and so maybe an output), until it returns false.
+async fn follow_sway(mut follow: impl FnMut(&str) -> bool) -> Result<(), Error> {
+
+    let path = std::env::var_os("SWAYSOCK")
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "SWAYSOCK is not set"))?;
+    let mut stream = UnixStream::connect(path).await?;
+
+    let payload = br#"["workspace"]"#;
+    let mut message = SWAY_IPC_MAGIC.to_vec();
+    message.extend((payload.len() as u32).to_ne_bytes());
+    message.extend(SWAY_SUBSCRIBE.to_ne_bytes());
+    message.extend(payload);
+    stream.write_all(&message).await?;
+
+    loop {
+        let mut header = [0u8; SWAY_HEADER_LEN];
+        stream.read_exact(&mut header).await?;
+        let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).await?;
+
+        // the first message is the reply to subscribing, which has no `change`
+        let event: serde_json::Value = serde_json::from_slice(&payload).unwrap_or_default();
+        if event["change"] != "focus" { continue; }
+
+        if let Some(output) = event["current"]["output"].as_str() {
+            if !follow(output) { return Ok(()); }
+        }
+    }
+}
+
+
+/// Calls `follow` with the output's name whenever Hyprland's focus moves
+/// to another output, until it returns false.
+async fn follow_hyprland(mut follow: impl FnMut(&str) -> bool) -> Result<(), Error> {
+
+    let path = hyprland::event_socket_path()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "Hyprland's event socket could not be found"))?;
+    let mut lines = BufReader::new(UnixStream::connect(path).await?).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        // like `focusedmon>>DP-1,2` (the output, then the workspace)
+        let output = line.strip_prefix("focusedmon>>")
+            .and_then(|rest| rest.split(',').next());
+
+        if let Some(output) = output {
+            if !follow(output) { return Ok(()); }
+        }
+    }
+
+    Err(Error::new(ErrorKind::UnexpectedEof, "Hyprland closed its event socket"))
+}
@@ -0,0 +1,154 @@
+// Protection against event storms: some trackpad firmware (or drivers)
+// can go haywire and report thousands of swipe updates a second, which
+// would otherwise have this program writing to the virtual trackpad (and
+// signalling the timer) for every one of them, pinning a CPU core.
+//
+// Each swipe gets a budget of updates per second. Past it, updates are
+// only added up, and the total is handed back with the next update the
+// budget allows (or at the end of the swipe), so the cursor still ends up
+// in the right place. The first time a swipe goes over, it's logged, and
+// how much was held back is logged when the swipe ends.
+//
+// `fixtures/event-storm.jsonl` is a made-up storm for `replay`'s test;
+// one recorded on affected hardware would be better, once someone with
+// some sends one in.
+
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+
+// no real trackpad reports anywhere near this many updates a second
+// (most do 100-250), so only a storm ever gets held back
+pub const MAX_UPDATES_PER_SECOND: u32 = 500;
+const BUDGET_WINDOW: Duration = Duration::from_secs(1);
+
+
+#[derive(Debug, Default)]
+pub struct UpdateBudget {
+    window_start: Option<Instant>,  // None until the swipe's first update
+    in_window: u32,           // updates let through in the current window
+    held_back: (f64, f64),    // motion from updates over budget, not yet let through
+    over_budget: u64,         // updates held back this swipe
+}
+
+impl UpdateBudget {
+
+    /// Starts over for a new swipe.
+    pub fn begin(&mut self) {
+        *self = UpdateBudget::default();
+    }
+
+
+    /// Returns the motion to act on for this update (coming in at `now`,
+    /// and including any held back before it), or `None` if it's over
+    /// budget and was held back.
+    pub fn admit(&mut self, now: Instant, dx: f64, dy: f64) -> Option<(f64, f64)> {
+
+        if self.window_start.is_none_or(|start| now.saturating_duration_since(start) >= BUDGET_WINDOW) {
+            self.window_start = Some(now);
+            self.in_window = 0;
+        }
+
+        let dx = dx + self.held_back.0;
+        let dy = dy + self.held_back.1;
+
+        if self.in_window >= MAX_UPDATES_PER_SECOND {
+            if self.over_budget == 0 {
+                warn!(
+                    "The trackpad is sending over {} swipe updates a second, which is far more than \
+                    usual (this is likely a firmware or driver bug); holding back the excess",
+                    MAX_UPDATES_PER_SECOND
+                );
+            }
+            self.over_budget += 1;
+            self.held_back = (dx, dy);
+            return None;
+        }
+
+        self.in_window += 1;
+        self.held_back = (0.0, 0.0);
+        Some((dx, dy))
+    }
+
+
+    /// Ends the swipe, returning any motion still held back.
+    pub fn finish(&mut self) -> Option<(f64, f64)> {
+
+        if self.over_budget > 0 {
+            warn!("{} swipe updates were held back during the last swipe", self.over_budget);
+        }
+
+        let held_back = self.held_back;
+        self.begin();
+        (held_back != (0.0, 0.0)).then_some(held_back)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends `count` updates of (1, -1), `gap` apart from `start`, returning
+    /// how many were let through and the motion they carried.
+    fn send(budget: &mut UpdateBudget, start: Instant, gap: Duration, count: u32) -> (u32, (f64, f64)) {
+        let mut admitted = 0;
+        let mut total = (0.0, 0.0);
+        for n in 0..count {
+            if let Some((dx, dy)) = budget.admit(start + gap * n, 1.0, -1.0) {
+                admitted += 1;
+                total = (total.0 + dx, total.1 + dy);
+            }
+        }
+        (admitted, total)
+    }
+
+    #[test]
+    fn ordinary_rates_go_through_untouched() {
+        let mut budget = UpdateBudget::default();
+        let (admitted, total) = send(&mut budget, Instant::now(), Duration::from_millis(5), 400);
+
+        assert_eq!(admitted, 400);
+        assert_eq!(total, (400.0, -400.0));
+        assert_eq!(budget.finish(), None);
+    }
+
+    #[test]
+    fn a_storm_is_held_to_the_budget_and_its_motion_kept() {
+        let mut budget = UpdateBudget::default();
+        let (admitted, total) = send(&mut budget, Instant::now(), Duration::from_micros(500), 600);
+
+        assert_eq!(admitted, MAX_UPDATES_PER_SECOND);
+        assert_eq!(total, (MAX_UPDATES_PER_SECOND as f64, -(MAX_UPDATES_PER_SECOND as f64)));
+        // the 100 held back all come out at the end
+        assert_eq!(budget.finish(), Some((100.0, -100.0)));
+        assert_eq!(budget.finish(), None);
+    }
+
+    #[test]
+    fn held_back_motion_goes_with_the_next_window() {
+        let start = Instant::now();
+        let mut budget = UpdateBudget::default();
+        let (admitted, _) = send(&mut budget, start, Duration::from_micros(100), MAX_UPDATES_PER_SECOND + 3);
+        assert_eq!(admitted, MAX_UPDATES_PER_SECOND);
+
+        // a second on, the window rolls over, and the first update in it
+        // carries the three held back as well as its own
+        assert_eq!(budget.admit(start + BUDGET_WINDOW, 1.0, -1.0), Some((4.0, -4.0)));
+        assert_eq!(budget.admit(start + BUDGET_WINDOW, 1.0, -1.0), Some((1.0, -1.0)));
+        assert_eq!(budget.finish(), None);
+    }
+
+    #[test]
+    fn each_swipe_starts_with_a_fresh_budget() {
+        let start = Instant::now();
+        let mut budget = UpdateBudget::default();
+        send(&mut budget, start, Duration::from_micros(100), MAX_UPDATES_PER_SECOND + 1);
+        assert_eq!(budget.finish(), Some((1.0, -1.0)));
+
+        budget.begin();
+        let (admitted, _) = send(&mut budget, start + Duration::from_millis(100), Duration::from_micros(100), 10);
+        assert_eq!(admitted, 10);
+    }
+}
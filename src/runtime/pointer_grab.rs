@@ -0,0 +1,52 @@
+// Pauses drag translation while the focused window is likely to have the
+// pointer grabbed or confined, like a fullscreen game or a VM console.
+// A three-finger drag there would fight whatever the app does with the
+// pointer (or leave the button held inside the VM).
+//
+// Wayland compositors don't tell other clients when a window has the
+// pointer locked or confined, so the closest thing we can go on is the
+// focused window itself: whether it's fullscreen (`pauseWhenFullscreen`),
+// or whether it's one of the apps listed in `pauseForApps`. See
+// `focused_window` for where that comes from.
+
+use tokio::sync::watch::{Receiver, Sender};
+use tracing::info;
+
+use super::focused_window::FocusedWindow;
+
+
+/// Follows the focused window from `focused`, and sends whether drags 
+/// should be paused through `tx` whenever that changes. A window pauses
+/// drags if it's one of `apps`, or if it's fullscreen and `when_fullscreen`
+/// is set.
+pub async fn watch_pointer_grabs(
+    mut focused: Receiver<Option<FocusedWindow>>, 
+    tx: Sender<bool>, 
+    apps: Vec<String>, 
+    when_fullscreen: bool
+) {
+
+    let mut last_paused = false;
+    while focused.changed().await.is_ok() {
+
+        let window = focused.borrow_and_update().clone();
+
+        // no focused window (e.g. an empty workspace) means nothing to pause for
+        let paused = window.as_ref().is_some_and(|window|
+            (when_fullscreen && window.fullscreen)
+                || apps.iter().any(|app| window.is_app(app))
+        );
+
+        if paused != last_paused {
+            match &window {
+                Some(window) if paused => info!("'{}' is focused{}; pausing three-finger drags",
+                    window.app_id, if window.fullscreen { " and fullscreen" } else { "" }
+                ),
+                _ => info!("Resuming three-finger drags")
+            }
+
+            if tx.send(paused).is_err() { return; }
+            last_paused = paused;
+        }
+    }
+}
@@ -0,0 +1,141 @@
+// Re-executing the program in place (e.g. to pick up an upgraded binary,
+// via `systemctl reload`), while keeping the same virtual trackpad.
+//
+// The uinput file descriptor is left open across the `exec()`, and its
+// number (along with whether the mouse button is down) is passed to the
+// new process in environment variables. The new process then picks up
+// the existing virtual device instead of creating a new one. Since
+// `exec()` keeps the PID, systemd doesn't even notice. A control socket
+// systemd handed over is passed on the same way it came in.
+
+use std::{
+    io::Error,
+    os::{fd::{AsRawFd, FromRawFd, OwnedFd, RawFd}, unix::process::CommandExt},
+    process::Command
+};
+
+use nix::libc::{fcntl, FD_CLOEXEC, F_GETFD, F_SETFD};
+use tracing::{info, warn};
+
+use super::{ipc, virtual_trackpad::VirtualTrackpad};
+
+
+const HANDOFF_FD_VAR: &str         = "L3FD_HANDOFF_UINPUT_FD";
+const HANDOFF_MOUSE_DOWN_VAR: &str = "L3FD_HANDOFF_MOUSE_DOWN";
+const HANDOFF_TOUCH_VAR: &str      = "L3FD_HANDOFF_TOUCH";
+
+
+/// Replaces the current process with a fresh run of the current executable,
+/// handing the virtual trackpad over to it. This only returns if something
+/// went wrong, in which case the virtual trackpad is handed back, so it
+/// can be cleaned up as usual.
+pub fn exec_with_handoff(vtp: VirtualTrackpad) -> (VirtualTrackpad, Error) {
+
+    // a dry run has nothing to hand over, so the new one starts from scratch
+    if vtp.is_dry_run() {
+        let err = match std::env::current_exe() {
+            Ok(exe) => {
+                info!("Re-executing {:?}", exe);
+                let mut command = Command::new(&exe);
+                command.args(std::env::args_os().skip(1));
+                pass_activated_socket(&mut command);
+                command.exec()
+            },
+            Err(e) => e
+        };
+        return (vtp, err);
+    }
+
+    let fd = vtp.as_raw_fd();
+    if let Err(e) = set_cloexec(fd, false) {
+        return (vtp, e);
+    }
+
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => return (vtp, e)
+    };
+
+    info!("Re-executing {:?}, handing off the virtual trackpad (fd {})", exe, fd);
+
+    let mut command = Command::new(&exe);
+    command
+        .args(std::env::args_os().skip(1))
+        .env(HANDOFF_FD_VAR, fd.to_string())
+        .env(HANDOFF_MOUSE_DOWN_VAR, if vtp.mouse_is_down { "1" } else { "0" })
+        .env(HANDOFF_TOUCH_VAR, if vtp.is_touchscreen() { "1" } else { "0" });
+    pass_activated_socket(&mut command);
+    let err = command.exec();
+
+    // still here, so the exec() failed; put things back how they were
+    let _ = set_cloexec(fd, true);
+    (vtp, err)
+}
+
+
+/// Leaves the control socket systemd handed over (if it did) open across
+/// the exec, and tells the new process about it as systemd did, so it
+/// keeps listening on systemd's socket instead of replacing its file.
+fn pass_activated_socket(command: &mut Command) {
+    if !ipc::is_socket_activated() {
+        return;
+    }
+    match set_cloexec(ipc::SD_LISTEN_FDS_START, false) {
+        // the pid stays the same across exec()
+        Ok(()) => { command.env("LISTEN_FDS", "1").env("LISTEN_PID", std::process::id().to_string()); },
+        Err(e) => warn!("Could not keep the control socket open across the exec: {}", e)
+    }
+}
+
+
+/// If this process was started by `exec_with_handoff()`, takes over the
+/// virtual trackpad it was handed, returning `None` otherwise. The
+/// environment variables are cleared either way, so they don't leak into
+/// anything this process starts.
+pub fn take_handoff() -> Option<VirtualTrackpad> {
+
+    let fd_var = std::env::var(HANDOFF_FD_VAR).ok();
+    let mouse_down_var = std::env::var(HANDOFF_MOUSE_DOWN_VAR).ok();
+    let touch_var = std::env::var(HANDOFF_TOUCH_VAR).ok();
+    std::env::remove_var(HANDOFF_FD_VAR);
+    std::env::remove_var(HANDOFF_MOUSE_DOWN_VAR);
+    std::env::remove_var(HANDOFF_TOUCH_VAR);
+
+    let fd: RawFd = fd_var?.parse().ok()?;
+
+    // make sure the number we were given is actually an open fd before
+    // taking ownership of it, since closing a random fd would be bad news
+    if unsafe { fcntl(fd, F_GETFD) } < 0 {
+        warn!("Handed-off uinput fd {} is not open; creating a new virtual trackpad instead", fd);
+        return None;
+    }
+    if let Err(e) = set_cloexec(fd, true) {
+        warn!("Could not reset close-on-exec for the handed-off uinput fd: {}", e);
+    }
+
+    let mouse_is_down = mouse_down_var.as_deref() == Some("1");
+    info!("Picked up the virtual trackpad from the previous process (mouse down: {})", mouse_is_down);
+
+    // SAFETY: the fd was checked to be open above, and nothing else in this
+    // process knows about it, since it was inherited across exec()
+    let owned_fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    Some(VirtualTrackpad::from_handoff(owned_fd, mouse_is_down, touch_var.as_deref() == Some("1")))
+}
+
+
+pub fn set_cloexec(fd: RawFd, cloexec: bool) -> Result<(), Error> {
+
+    // SAFETY: fcntl with F_GETFD/F_SETFD doesn't touch memory, and
+    // fails cleanly (with EBADF) if the fd isn't valid
+    let flags = unsafe { fcntl(fd, F_GETFD) };
+    if flags < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    let new_flags = if cloexec { flags | FD_CLOEXEC } else { flags & !FD_CLOEXEC };
+    if unsafe { fcntl(fd, F_SETFD, new_flags) } < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    Ok(())
+}
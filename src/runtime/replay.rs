@@ -0,0 +1,103 @@
+// `replay`: plays a trace from `record` back through the translator, with
+// the same gaps between events as when it was recorded, so a drag that
+// misbehaved on someone else's trackpad can be watched misbehaving here
+// (out of the virtual trackpad, or only in the log with `--dry-run`).
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tracing::info;
+
+use super::{
+    event_handler::{GestureTranslator, GtError},
+    trace::{TraceEvent, TraceKind}
+};
+
+
+// a little extra wait after the drag end delay, so the last release is in
+// before the caller moves on (to cleanup, say), like for `simulate`
+const RELEASE_MARGIN: Duration = Duration::from_millis(50);
+
+
+/// Feeds `events` to `translator` at their original timing, returning
+/// once the last of them (and any drag end delay after it) is through.
+pub async fn replay(translator: &mut GestureTranslator, events: &[TraceEvent]) -> Result<(), GtError> {
+
+    info!("Replaying {} events", events.len());
+    let mut first_usec = None;
+    let started = Instant::now();
+
+    for event in events {
+        // device events have no timestamp, so they don't count toward the gaps.
+        // Each event waits for its time since the start, rather than for the
+        // gap since the last one, since tokio's timer goes by whole
+        // milliseconds, and rounding every gap up would slow a storm of
+        // updates a fraction of a millisecond apart right down.
+        if event.time_usec > 0 {
+            let first = *first_usec.get_or_insert(event.time_usec);
+            tokio::time::sleep_until(started + Duration::from_micros(event.time_usec.saturating_sub(first))).await;
+        }
+
+        if event.kind == TraceKind::DeviceAdded {
+            translator.trackpad_size_is(event.size_mm);
+        }
+        translator.translate_gesture(event.clone()).await?;
+    }
+
+    tokio::time::sleep(translator.timer_delay() + RELEASE_MARGIN).await;
+    info!("Replay finished");
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::{
+        init::config::Configuration,
+        runtime::{
+            event_handler::tests::Harness, overload::MAX_UPDATES_PER_SECOND, trace::read_trace,
+            virtual_trackpad::DRY_RUN_MOVES
+        }
+    };
+
+    // made up rather than recorded (nobody with a trackpad that does this
+    // has sent one in yet), but shaped like a storm: a swipe with 600
+    // updates, half a millisecond apart and each enough to move the
+    // pointer, then an ordinary one with 5, continuing the drag
+    const EVENT_STORM: &str = include_str!("fixtures/event-storm.jsonl");
+
+
+    #[tokio::test(start_paused = true)]
+    async fn event_storm_replays_as_one_drag() {
+        let (header, events) = read_trace(EVENT_STORM.as_bytes()).expect("the fixture should read");
+        let cfg: Configuration = serde_json::from_value(header.config).expect("its config should load");
+        let mut harness = Harness::new(cfg);
+
+        let updates = events.iter().filter(|event| event.kind == TraceKind::SwipeUpdate).count() as u64;
+        assert!(updates > MAX_UPDATES_PER_SECOND as u64, "the fixture should be a storm");
+
+        let moves_before = DRY_RUN_MOVES.load(Ordering::Relaxed);
+        let started = Instant::now();
+        replay(&mut harness.translator, &events).await.unwrap();
+        let moves = DRY_RUN_MOVES.load(Ordering::Relaxed) - moves_before;
+
+        // as long as it took to record, give or take tokio's millisecond,
+        // then the drag end delay
+        let first = events.iter().find(|event| event.time_usec > 0).unwrap().time_usec;
+        let last = events.last().unwrap().time_usec;
+        let expected = Duration::from_micros(last - first) + Duration::from_millis(200) + RELEASE_MARGIN;
+        assert!(started.elapsed().abs_diff(expected) <= Duration::from_millis(1), "took {:?}", started.elapsed());
+
+        // the storm only moves the pointer as often as the budget allows,
+        // plus once at its end, for what was held back
+        assert_eq!(moves, MAX_UPDATES_PER_SECOND as u64 + 1 + 5);
+
+        // continuing the drag presses again, but the button only goes up once
+        let mut changes = harness.button_changes();
+        changes.dedup();
+        assert_eq!(changes, vec![true, false]);
+    }
+}
@@ -0,0 +1,47 @@
+// For `responseTime: "auto"`: how often the trackpad actually reports
+// motion, measured from the timestamps libinput puts on swipe updates, so
+// the main loop can fetch events about that often. A fixed 5ms is a batch
+// of several reports on a fast trackpad, but wakes up more often than
+// there's anything to read on a slow one.
+
+use std::time::Duration;
+
+
+// how much each new gap counts toward the average, so one late report
+// doesn't throw it off
+const SMOOTHING: f64 = 0.1;
+// gaps longer than this are the fingers resting, not the report rate
+const MAX_REPORT_GAP_USEC: u64 = 50_000;
+
+
+#[derive(Debug, Default)]
+pub struct ReportRate {
+    last_report_usec: Option<u64>,
+    average_gap_usec: Option<f64>
+}
+
+impl ReportRate {
+
+    /// Notes a swipe update from the trackpad, reported at `time_usec`.
+    pub fn note(&mut self, time_usec: u64) {
+
+        let last = self.last_report_usec.replace(time_usec);
+        let Some(gap) = last.map(|last| time_usec.saturating_sub(last)) else { return };
+        if gap == 0 || gap > MAX_REPORT_GAP_USEC {
+            return;
+        }
+
+        let gap = gap as f64;
+        self.average_gap_usec = Some(match self.average_gap_usec {
+            Some(average) => average + (gap - average) * SMOOTHING,
+            None => gap
+        });
+    }
+
+
+    /// The time between the trackpad's reports, once there's been enough
+    /// motion to tell.
+    pub fn interval(&self) -> Option<Duration> {
+        self.average_gap_usec.map(|gap| Duration::from_micros(gap as u64))
+    }
+}
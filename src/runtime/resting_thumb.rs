@@ -0,0 +1,188 @@
+// Noticing a thumb resting on the bottom of the trackpad, for
+// `restingThumbZone`. With a thumb resting there, three fingers dragging
+// make four contacts, which libinput reports as a four-finger swipe
+// (unless its own thumb detection catches it), so no drag happens.
+//
+// libinput doesn't share where each contact is, so this reads the
+// trackpad's evdev node alongside it (without grabbing it), following the
+// multitouch slots. A contact that landed in the bottom zone and has
+// barely moved since is taken to be a resting thumb.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError, atomic::{AtomicBool, Ordering}}
+};
+
+use input_linux::{
+    AbsoluteAxis, EventKind, EventTime, EvdevHandle, InputEvent,
+    SynchronizeEvent, SynchronizeKind
+};
+use nix::libc::O_RDONLY;
+use tracing::{debug, warn};
+
+use crate::init::open_helper::{is_permission_error, open_via_helper};
+
+
+// how far a thumb can drift and still count as resting, in millimeters,
+// or as a fraction of the trackpad's width if its resolution isn't known
+const THUMB_DRIFT_MM: i32 = 2;
+const THUMB_DRIFT_FRACTION: f64 = 0.02;
+
+
+#[derive(Debug, Clone, Copy)]
+struct Contact {
+    landed_at: (i32, i32)
+}
+
+
+/// The parts of the trackpad's geometry needed to tell a resting thumb.
+#[derive(Debug, Clone, Copy)]
+struct Geometry {
+    zone_top: i32,      // contacts landing at or below this y are in the thumb zone
+    max_drift: i32      // in trackpad units
+}
+
+
+/// Follows resting thumbs on any number of trackpads, each read on a
+/// thread of its own.
+#[derive(Debug, Clone, Default)]
+pub struct RestingThumbs {
+    resting: Arc<AtomicBool>,
+    watching: Arc<Mutex<HashSet<PathBuf>>>
+}
+
+impl RestingThumbs {
+
+    /// Whether a thumb is resting on a trackpad right now.
+    pub fn is_resting(&self) -> bool {
+        self.resting.load(Ordering::Acquire)
+    }
+
+
+    /// Starts watching the trackpad at `/dev/input/<sysname>` for resting
+    /// thumbs, in the bottom `zone` (as a fraction of its height), unless
+    /// it's already being watched.
+    pub fn watch(&self, sysname: &str, zone: f64) {
+
+        let path = Path::new("/dev/input").join(sysname);
+        if !self.watching.lock().unwrap_or_else(PoisonError::into_inner).insert(path.clone()) {
+            return;
+        }
+
+        let thumbs = self.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = thumbs.follow_contacts(&path, zone) {
+                warn!("Stopped watching {:?} for resting thumbs: {}", path, e);
+            }
+            thumbs.resting.store(false, Ordering::Release);
+            thumbs.watching.lock().unwrap_or_else(PoisonError::into_inner).remove(&path);
+        });
+    }
+
+
+    /// Reads the trackpad's contacts until it goes away (or can't be read).
+    fn follow_contacts(&self, path: &Path, zone: f64) -> Result<(), Error> {
+
+        let file = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .or_else(|err|
+                if is_permission_error(&err) {
+                    open_via_helper(path, O_RDONLY).map(File::from)
+                } else {
+                    Err(err)
+                }
+            )?;
+        let evdev = EvdevHandle::new(file);
+        let geometry = geometry_of(&evdev, zone)?;
+        debug!("Watching {:?} for resting thumbs ({:?})", path, geometry);
+
+        let (abs, syn) = (EventKind::Absolute as u16, EventKind::Synchronize as u16);
+        let (slot_code, x_code, y_code, id_code) = (
+            AbsoluteAxis::MultitouchSlot as u16,
+            AbsoluteAxis::MultitouchPositionX as u16,
+            AbsoluteAxis::MultitouchPositionY as u16,
+            AbsoluteAxis::MultitouchTrackingId as u16
+        );
+
+        // Positions are kept per slot, even once a contact lifts, since the
+        // kernel only sends the ones that changed: a new contact in a slot
+        // starts where the slot's last one was, unless it says otherwise
+        let mut positions: HashMap<i32, (i32, i32)> = HashMap::new();
+        let mut contacts: HashMap<i32, Contact> = HashMap::new();
+        let mut slot = 0;
+        let mut landing: HashSet<i32> = HashSet::new();   // slots that got a new contact this frame
+
+        // anything will do to fill the buffer with, since it's read over
+        let filler = InputEvent::from(SynchronizeEvent::new(EventTime::new(0, 0), SynchronizeKind::Report, 0)).into_raw();
+        let mut buffer = [filler; 64];
+
+        loop {
+            let read = evdev.read(&mut buffer)?;
+            if read == 0 {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "the trackpad went away"));
+            }
+
+            for event in &buffer[..read] {
+                match (event.type_, event.code) {
+                    (t, c) if t == abs && c == slot_code => slot = event.value,
+                    (t, c) if t == abs && c == id_code => {
+                        if event.value < 0 {
+                            contacts.remove(&slot);
+                        } else {
+                            landing.insert(slot);
+                        }
+                    },
+                    (t, c) if t == abs && (c == x_code || c == y_code) => {
+                        let position = positions.entry(slot).or_default();
+                        if c == x_code { position.0 = event.value } else { position.1 = event.value }
+                    },
+                    (t, _) if t == syn => {
+                        // a contact's position in its first frame is where it landed
+                        for slot in landing.drain() {
+                            let at = positions.get(&slot).copied().unwrap_or_default();
+                            contacts.insert(slot, Contact { landed_at: at });
+                        }
+                        let resting = contacts.iter().any(|(slot, contact)| {
+                            let at = positions.get(slot).copied().unwrap_or_default();
+                            is_resting_thumb(contact, at, &geometry)
+                        });
+                        self.resting.store(resting, Ordering::Release);
+                    },
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+
+fn geometry_of(evdev: &EvdevHandle<File>, zone: f64) -> Result<Geometry, Error> {
+
+    let x = evdev.absolute_info(AbsoluteAxis::MultitouchPositionX)?;
+    let y = evdev.absolute_info(AbsoluteAxis::MultitouchPositionY)?;
+
+    let height = (y.maximum - y.minimum) as f64;
+    let max_drift = if x.resolution > 0 {
+        THUMB_DRIFT_MM * x.resolution   // resolution is in units per millimeter
+    } else {
+        ((x.maximum - x.minimum) as f64 * THUMB_DRIFT_FRACTION) as i32
+    };
+
+    Ok(Geometry {
+        zone_top: y.maximum - (height * zone) as i32,
+        max_drift
+    })
+}
+
+
+/// Whether `contact`, now at `at`, landed in the thumb zone and has
+/// barely moved since.
+fn is_resting_thumb(contact: &Contact, at: (i32, i32), geometry: &Geometry) -> bool {
+    contact.landed_at.1 >= geometry.zone_top
+        && (at.0 - contact.landed_at.0).abs() <= geometry.max_drift
+        && (at.1 - contact.landed_at.1).abs() <= geometry.max_drift
+}
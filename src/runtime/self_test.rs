@@ -0,0 +1,150 @@
+// `--self-test`: checking the whole path out of this program works, by
+// creating a virtual trackpad, writing a known sequence of events to it,
+// and reading them back from the evdev node the kernel made for it. This
+// needs no trackpad and no desktop, so it works as a systemd
+// `ExecStartPre=` health check, and in CI on real hardware.
+//
+// The evdev node is grabbed before anything is written, so the events only
+// reach this test, not the desktop; otherwise the test would move the
+// cursor and click wherever it happens to be.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    time::{Duration, Instant}
+};
+
+use input_linux::{
+    sys::input_event,
+    EventTime, EvdevHandle, InputEvent,
+    Key, KeyEvent, KeyState,
+    RelativeAxis, RelativeEvent,
+    SynchronizeEvent, SynchronizeKind
+};
+use nix::libc::{O_NONBLOCK, O_RDONLY};
+use tracing::debug;
+
+use super::virtual_trackpad::{self, VirtualTrackpad};
+use crate::init::open_helper::{is_permission_error, open_via_helper};
+
+
+// nonzero, since the kernel drops relative motion of 0
+const TEST_MOTION: (i32, i32) = (5, -3);
+
+// how long to wait for the events to come back out
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+
+/// The events the test sequence should come back as, as (type, code, value).
+fn expected_events() -> Vec<(u16, u16, i32)> {
+    let zero = EventTime::new(0, 0);
+    let sync = || InputEvent::from(SynchronizeEvent::new(zero, SynchronizeKind::Report, 0));
+
+    [
+        // the move
+        InputEvent::from(RelativeEvent::new(zero, RelativeAxis::X, TEST_MOTION.0)),
+        InputEvent::from(RelativeEvent::new(zero, RelativeAxis::Y, TEST_MOTION.1)),
+        sync(),
+        // the press
+        InputEvent::from(KeyEvent::new(zero, Key::ButtonLeft, KeyState::pressed(true))),
+        sync(),
+        // the release (the right button's release is dropped by the kernel,
+        // since it was never pressed)
+        InputEvent::from(KeyEvent::new(zero, Key::ButtonLeft, KeyState::pressed(false))),
+        sync()
+    ]
+    .into_iter()
+    .map(|event| summarize(&event.into_raw()))
+    .collect()
+}
+
+
+fn summarize(event: &input_event) -> (u16, u16, i32) {
+    (event.type_, event.code, event.value)
+}
+
+
+/// Opens the virtual trackpad's evdev node for reading (without blocking),
+/// through the setgid helper if it has to.
+pub(super) fn open_evdev(path: &Path) -> Result<File, Error> {
+    OpenOptions::new()
+        .read(true)
+        .custom_flags(O_NONBLOCK)
+        .open(path)
+        .or_else(|err|
+            if is_permission_error(&err) {
+                open_via_helper(path, O_RDONLY | O_NONBLOCK).map(File::from)
+            } else {
+                Err(err)
+            }
+        )
+        .map_err(|e| Error::new(e.kind(), format!("Could not open {:?} to read the events back: {}", path, e)))
+}
+
+
+/// Reads events from `evdev` until there are `count` of them, or the
+/// timeout runs out.
+fn read_events(evdev: &EvdevHandle<File>, count: usize) -> Result<Vec<(u16, u16, i32)>, Error> {
+
+    let mut events = Vec::new();
+    // anything will do to fill the buffer with, since it's read over
+    let filler = InputEvent::from(SynchronizeEvent::new(EventTime::new(0, 0), SynchronizeKind::Report, 0)).into_raw();
+    let mut buffer = [filler; 16];
+    let deadline = Instant::now() + READ_TIMEOUT;
+
+    while events.len() < count && Instant::now() < deadline {
+        match evdev.read(&mut buffer) {
+            Ok(read) => events.extend(buffer[..read].iter().map(summarize)),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(10)),
+            Err(e) => return Err(e)
+        }
+    }
+
+    Ok(events)
+}
+
+
+/// Writes the test sequence to `vtp`, and checks it comes back out of
+/// its evdev node.
+fn check_round_trip(vtp: &mut VirtualTrackpad) -> Result<(), Error> {
+
+    let path = vtp.evdev_path()?;
+    debug!("Virtual trackpad's evdev node: {:?}", path);
+
+    let evdev = EvdevHandle::new(open_evdev(&path)?);
+    evdev.grab(true)
+        .map_err(|e| Error::new(e.kind(), format!("Could not grab {:?}, so the test events would \
+            reach the desktop: {}", path, e)))?;
+
+    vtp.mouse_move_relative(TEST_MOTION.0 as f64, TEST_MOTION.1 as f64)?;
+    vtp.button_down(Key::ButtonLeft)?;
+    vtp.mouse_up()?;
+
+    let expected = expected_events();
+    let received = read_events(&evdev, expected.len())?;
+    let _ = evdev.grab(false);
+
+    if received == expected {
+        Ok(())
+    } else {
+        Err(Error::new(ErrorKind::InvalidData, format!(
+            "the events read back were {:?}, not the {:?} written (as type, code, value)",
+            received, expected
+        )))
+    }
+}
+
+
+/// Runs the self-test, returning what went wrong if it failed. The virtual
+/// trackpad is removed afterwards either way.
+pub fn run_self_test() -> Result<(), Error> {
+
+    let mut vtp = virtual_trackpad::start_handler()?;
+    let result = check_round_trip(&mut vtp);
+    let destroyed = vtp.destruct();
+
+    result?;
+    destroyed
+}
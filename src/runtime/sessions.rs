@@ -0,0 +1,140 @@
+// Following logind's active session, for running as a single system-wide
+// instance (`--system`) rather than one per user. Every session on the
+// seat sees the same virtual trackpad, but logind only lets the active
+// one's compositor read input devices, so drags land in whichever session
+// is in front. What this adds is:
+//
+// - pausing drags while no user session is active (at the login screen,
+//   or on a switched-away VT), so nothing is injected there
+// - using the active user's own config, if they have one, falling back on
+//   the system-wide one
+//
+// There's no D-Bus crate here, so this asks `loginctl`, once a second.
+
+use std::{
+    path::Path,
+    process::Command,
+    time::Duration
+};
+
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, info, warn};
+use users::{get_user_by_uid, os::unix::UserExt};
+
+use crate::init::config::{self, Configuration, Preset};
+
+
+const SEAT: &str = "seat0";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveSession {
+    pub id: String,
+    pub uid: u32,
+    pub name: String,
+    pub class: String   // "user" for a login, "greeter" for the login screen, ...
+}
+
+impl ActiveSession {
+    pub fn is_user_session(&self) -> bool {
+        self.class == "user"
+    }
+}
+
+
+fn loginctl(args: &[&str]) -> Option<String> {
+    let output = Command::new("loginctl").args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+
+/// The session in front on the seat, if there is one.
+pub fn active_session() -> Option<ActiveSession> {
+
+    let id = loginctl(&["show-seat", SEAT, "--property=ActiveSession", "--value"])?
+        .trim()
+        .to_string();
+    if id.is_empty() {
+        return None;
+    }
+
+    let properties = loginctl(&["show-session", &id, "--property=User", "--property=Name", "--property=Class"])?;
+    let property = |key: &str| properties.lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        .map(str::to_string);
+
+    Some(ActiveSession {
+        uid: property("User")?.parse().ok()?,
+        name: property("Name").unwrap_or_default(),
+        class: property("Class").unwrap_or_default(),
+        id
+    })
+}
+
+
+/// The config to use for `session`: its user's own, if they have one that
+/// loads, or else `system_cfg`.
+pub(crate) fn config_for(session: &ActiveSession, system_cfg: &Configuration, preset_override: Option<Preset>) -> Configuration {
+
+    let Some(home) = get_user_by_uid(session.uid).map(|user| user.home_dir().to_path_buf()) else {
+        return system_cfg.clone();
+    };
+    let user_config = config::config_path_in(Path::new(&home).join(".config"));
+
+    match config::parse_config_file_at(&user_config, preset_override) {
+        Ok(cfg) => {
+            info!("Using {}'s config, from {:?}", session.name, user_config);
+            cfg
+        },
+        Err(e) => {
+            debug!("Using the system-wide config for {} ({})", session.name, e);
+            system_cfg.clone()
+        }
+    }
+}
+
+
+/// Follows the active session, pausing drags through `pause_tx` while it
+/// isn't a user's, and sending the config to use through `config_tx`
+/// whenever another user's session comes to the front.
+pub async fn follow_active_session(
+    pause_tx: watch::Sender<bool>,
+    config_tx: mpsc::Sender<Configuration>,
+    system_cfg: Configuration,
+    preset_override: Option<Preset>
+) {
+
+    let mut last_session: Option<ActiveSession> = None;
+    let mut last_uid = None;
+
+    loop {
+        let session = tokio::task::spawn_blocking(active_session).await.ok().flatten();
+
+        if session != last_session {
+            match &session {
+                Some(session) if session.is_user_session() => info!(
+                    "Session {} ({}) is now active", session.id, session.name
+                ),
+                _ => info!("No user session is active; pausing three-finger drags")
+            }
+
+            let paused = !session.as_ref().is_some_and(ActiveSession::is_user_session);
+            if pause_tx.send(paused).is_err() { return; }
+
+            let uid = session.as_ref().filter(|s| s.is_user_session()).map(|s| s.uid);
+            if let (Some(session), true) = (&session, uid.is_some() && uid != last_uid) {
+                let cfg = config_for(session, &system_cfg, preset_override);
+                if config_tx.send(cfg).await.is_err() {
+                    warn!("The main loop is gone, so session changes can no longer be followed");
+                    return;
+                }
+                last_uid = uid;
+            }
+
+            last_session = session;
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,104 @@
+// Made-up gestures, fed through the translator (and out the virtual
+// trackpad) as if they came from the trackpad. This is for checking that
+// drags actually come out the other end on a given desktop, without having
+// to perform them by hand, e.g. when helping someone debug remotely.
+// `test-gesture`'s square skips the translator too, so it's down to the
+// virtual trackpad alone.
+
+use std::time::{Duration, Instant};
+
+use tracing::info;
+
+use super::{
+    event_handler::{Gesture, GestureTranslator, GtError},
+    virtual_trackpad::VirtualTrackpad
+};
+
+
+// about as often as a real trackpad reports motion
+const UPDATE_INTERVAL: Duration = Duration::from_millis(10);
+
+// how long each side of `test-gesture`'s square takes
+const SQUARE_SIDE_TIME: Duration = Duration::from_millis(400);
+
+// a little extra wait after the drag end delay, so the release is in
+// before the caller moves on (to cleanup, say)
+const RELEASE_MARGIN: Duration = Duration::from_millis(50);
+
+
+/// Performs a three-finger drag of (`dx`, `dy`) in trackpad units (before
+/// acceleration), spread evenly over `duration`. The events come in the
+/// same order libinput sends them for a real drag: a hold, cancelled as
+/// the fingers start moving, then a swipe. This returns once the drag has
+/// ended, after any drag end delay.
+pub async fn simulate_drag(
+    translator: &mut GestureTranslator,
+    dx: f64,
+    dy: f64,
+    duration: Duration
+) -> Result<(), GtError> {
+    simulate_drag_observed(translator, dx, dy, duration, |_| {}).await
+}
+
+
+/// Like `simulate_drag()`, but calls `after_update` after each motion
+/// update has been handled, with when it was handed to the translator
+/// (e.g. to time how long it takes to come out the other end).
+pub async fn simulate_drag_observed(
+    translator: &mut GestureTranslator,
+    dx: f64,
+    dy: f64,
+    duration: Duration,
+    mut after_update: impl FnMut(Instant)
+) -> Result<(), GtError> {
+
+    let steps = (duration.as_millis() / UPDATE_INTERVAL.as_millis()).max(1) as u32;
+    info!("Simulating a drag of ({}, {}) over {:?}, in {} updates", dx, dy, duration, steps);
+
+    translator.handle_gesture(Gesture::HoldBegin).await?;
+    translator.handle_gesture(Gesture::HoldEnd { cancelled: true }).await?;
+    translator.handle_gesture(Gesture::SwipeBegin).await?;
+
+    let step = Gesture::SwipeUpdate { dx: dx / steps as f64, dy: dy / steps as f64 };
+    for _ in 0..steps {
+        tokio::time::sleep(UPDATE_INTERVAL).await;
+        let sent = Instant::now();
+        translator.handle_gesture(step).await?;
+        after_update(sent);
+    }
+
+    translator.handle_gesture(Gesture::SwipeEnd { cancelled: false }).await?;
+
+    // the release goes through the drag end timer, like a real one would
+    tokio::time::sleep(translator.timer_delay() + RELEASE_MARGIN).await;
+    info!("Simulated drag finished");
+
+    Ok(())
+}
+
+
+/// Presses the button, moves the pointer around a square `side` pixels
+/// across (right, down, left, then up, so it ends where it started), and
+/// releases it, writing straight to `vtp`. No settings come into it, and
+/// the button is released even if a move fails.
+pub async fn drag_square(vtp: &mut VirtualTrackpad, side: f64) -> Result<(), std::io::Error> {
+
+    let steps = (SQUARE_SIDE_TIME.as_millis() / UPDATE_INTERVAL.as_millis()) as u32;
+    let step = side / steps as f64;
+    info!("Dragging a square {} pixels across", side);
+
+    vtp.mouse_down()?;
+    let moved: Result<(), std::io::Error> = async {
+        for (dx, dy) in [(step, 0.0), (0.0, step), (-step, 0.0), (0.0, -step)] {
+            for _ in 0..steps {
+                tokio::time::sleep(UPDATE_INTERVAL).await;
+                vtp.mouse_move_relative(dx, dy)?;
+            }
+        }
+        Ok(())
+    }.await;
+    let released = vtp.mouse_up();
+
+    moved?;
+    released
+}
@@ -0,0 +1,85 @@
+// Counts of what a running instance has done since it started, for the
+// `stats` command and `status`, so "drags sometimes don't start" or "the
+// button gets stuck" can be told apart from the numbers (and watched by
+// monitoring) without turning the log up. They're kept as globals, like
+// the uptime in `status`, since the virtual trackpad's clones (the timer
+// thread's, say) write to it too, and they all count as one.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+
+static GESTURES_BEGUN: AtomicU64 = AtomicU64::new(0);
+static GESTURES_UPDATED: AtomicU64 = AtomicU64::new(0);
+static GESTURES_ENDED: AtomicU64 = AtomicU64::new(0);
+static GESTURES_CANCELLED: AtomicU64 = AtomicU64::new(0);
+static DRAGS_STARTED: AtomicU64 = AtomicU64::new(0);
+static FORCED_RELEASES: AtomicU64 = AtomicU64::new(0);
+static WRITE_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+// held by tests that drive a translator, so one test's counts don't land
+// in the middle of another's
+#[cfg(test)]
+pub static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+
+/// Three-finger swipes, as the translator gets them (paused or not).
+pub fn count_gesture_begun()     { GESTURES_BEGUN.fetch_add(1, Ordering::Relaxed); }
+pub fn count_gesture_updated()   { GESTURES_UPDATED.fetch_add(1, Ordering::Relaxed); }
+pub fn count_gesture_ended()     { GESTURES_ENDED.fetch_add(1, Ordering::Relaxed); }
+pub fn count_gesture_cancelled() { GESTURES_CANCELLED.fetch_add(1, Ordering::Relaxed); }
+
+/// A drag starting, rather than one being continued within `dragEndDelay`.
+pub fn count_drag_started()      { DRAGS_STARTED.fetch_add(1, Ordering::Relaxed); }
+
+/// A drag let go of right away instead of after `dragEndDelay`, like for
+/// other input, pausing, or an interrupted gesture.
+pub fn count_forced_release()    { FORCED_RELEASES.fetch_add(1, Ordering::Relaxed); }
+
+/// A write to the virtual trackpad that failed.
+pub fn count_write_error()       { WRITE_ERRORS.fetch_add(1, Ordering::Relaxed); }
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Stats {
+    pub gestures_begun: u64,
+    pub gestures_updated: u64,
+    pub gestures_ended: u64,        // not counting the cancelled ones
+    pub gestures_cancelled: u64,
+    pub drags_started: u64,
+    pub forced_releases: u64,
+    pub write_errors: u64
+}
+
+impl Stats {
+
+    /// The counts so far.
+    pub fn current() -> Stats {
+        Stats {
+            gestures_begun: GESTURES_BEGUN.load(Ordering::Relaxed),
+            gestures_updated: GESTURES_UPDATED.load(Ordering::Relaxed),
+            gestures_ended: GESTURES_ENDED.load(Ordering::Relaxed),
+            gestures_cancelled: GESTURES_CANCELLED.load(Ordering::Relaxed),
+            drags_started: DRAGS_STARTED.load(Ordering::Relaxed),
+            forced_releases: FORCED_RELEASES.load(Ordering::Relaxed),
+            write_errors: WRITE_ERRORS.load(Ordering::Relaxed)
+        }
+    }
+
+
+    /// The counts as `status` and `stats` print them.
+    pub fn describe(&self) -> String {
+        format!(
+            "Gestures:        {} begun, {} updates, {} ended, {} cancelled\n\
+            Drags started:   {}\n\
+            Forced releases: {}\n\
+            Write errors:    {}",
+            self.gestures_begun, self.gestures_updated, self.gestures_ended, self.gestures_cancelled,
+            self.drags_started,
+            self.forced_releases,
+            self.write_errors
+        )
+    }
+}
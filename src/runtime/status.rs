@@ -0,0 +1,98 @@
+// What a running instance is up to, for the `status` command, so there's
+// a way to tell whether the service is alive and has a trackpad without
+// digging through its logs. Like `capabilities`, this goes over the
+// control socket as a JSON object, and the command turns it into
+// something readable.
+
+use std::{sync::OnceLock, time::{Duration, Instant}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::init::config::Configuration;
+use super::stats::Stats;
+
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// Notes that the daemon has started, for its uptime.
+pub fn mark_started() {
+    STARTED.get_or_init(Instant::now);
+}
+
+fn uptime() -> Duration {
+    STARTED.get().map(Instant::elapsed).unwrap_or_default()
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub version: String,
+    pub pid: u32,
+    pub uptime_secs: u64,
+    pub devices: Vec<String>,       // trackpads being read, by name
+    pub drag_held: bool,            // including during dragEndDelay
+    #[serde(default)]
+    pub paused: bool,               // with `pause`, or by the config (like pauseForApps)
+    #[serde(default)]
+    pub stats: Stats,               // since it started
+    pub config: serde_json::Value   // as it's running, with anything set over the socket
+}
+
+impl Status {
+
+    /// The running instance's status, with `devices` as the trackpads it
+    /// has open.
+    pub fn current(devices: &[String], drag_held: bool, paused: bool, cfg: &Configuration) -> Status {
+        Status {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            pid: std::process::id(),
+            uptime_secs: uptime().as_secs(),
+            devices: devices.to_vec(),
+            drag_held,
+            paused,
+            stats: Stats::current(),
+            config: serde_json::to_value(cfg).unwrap_or_default()
+        }
+    }
+
+
+    /// The status as the `status` command prints it.
+    pub fn describe(&self) -> String {
+
+        let devices = match self.devices.as_slice() {
+            [] => "none (still looking)".to_string(),
+            devices => devices.join(", ")
+        };
+        let config = serde_json::to_string_pretty(&self.config).unwrap_or_default();
+
+        format!(
+            "linux-3-finger-drag {} is running (pid {})\n\
+            Up for:    {}\n\
+            Trackpads: {}\n\
+            Dragging:  {}\n\
+            Paused:    {}\n\
+            {}\n\
+            Config:\n{}",
+            self.version, self.pid,
+            describe_uptime(self.uptime_secs),
+            devices,
+            if self.drag_held { "yes" } else { "no" },
+            if self.paused { "yes" } else { "no" },
+            self.stats.describe(),
+            config
+        )
+    }
+}
+
+
+/// Like `2h 5m 3s`, leaving out the larger units while they're 0.
+fn describe_uptime(secs: u64) -> String {
+    let (days, hours, minutes, secs) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{secs}s"),
+        (0, 0, _) => format!("{minutes}m {secs}s"),
+        (0, _, _) => format!("{hours}h {minutes}m {secs}s"),
+        _ => format!("{days}d {hours}h {minutes}m {secs}s")
+    }
+}
@@ -0,0 +1,191 @@
+// Keeping the main loop going through failures that can be recovered
+// from, rather than exiting and leaving it to systemd to restart the
+// program (which loses anything only held in memory, like whether drags
+// are paused). Each kind of failure has the piece it affects rebuilt,
+// with an increasing wait between attempts:
+//
+// - the trackpad going away (unplugged, or the driver reloaded), or
+//   libinput failing to read it over and over: the trackpad is searched
+//   for again, until it's back
+// - writes to the virtual trackpad failing over and over: it's recreated
+// - the drag end timer crashing: it's restarted (by the caller)
+
+use std::{
+    sync::{Arc, atomic::{AtomicBool, Ordering}},
+    time::{Duration, Instant}
+};
+
+use input::Libinput;
+use tracing::{info, warn};
+
+use super::event_handler::{GestureTranslator, GtError};
+use crate::init::libinput_init;
+
+
+// this many libinput read errors within DISPATCH_ERROR_WINDOW is a storm,
+// not a hiccup
+const MAX_DISPATCH_ERRORS: usize = 20;
+const DISPATCH_ERROR_WINDOW: Duration = Duration::from_secs(1);
+
+// writes failing this many times in a row means the device is gone
+const MAX_WRITE_ERRORS: u32 = 5;
+
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+// a failure this long after the last one starts the waits over
+const BACKOFF_RESET: Duration = Duration::from_secs(60);
+
+
+#[derive(Debug)]
+pub enum Failure {
+    TrackpadLost,
+    DispatchErrors,
+    WriteErrors,
+    TimerCrashed(Option<GtError>)   // with its error, if it had one
+}
+
+
+/// Keeps an eye on one run of the main loop, for the failures above.
+/// The trackpads it's reading are kept across runs that reuse the same
+/// libinput context, since their `Added` events only come once.
+#[derive(Debug, Default)]
+pub struct LoopHealth {
+    devices: Vec<String>,   // by name
+    dispatch_errors: Vec<Instant>,
+    write_errors: u32
+}
+
+impl LoopHealth {
+
+    pub fn device_added(&mut self, name: &str) {
+        self.devices.push(name.to_string());
+    }
+
+    /// Returns a failure if that was the last trackpad.
+    pub fn device_removed(&mut self, name: &str) -> Option<Failure> {
+        if let Some(i) = self.devices.iter().position(|device| device == name) {
+            self.devices.remove(i);
+        }
+        self.devices.is_empty().then_some(Failure::TrackpadLost)
+    }
+
+    /// The trackpads being read, by name.
+    pub fn devices(&self) -> &[String] {
+        &self.devices
+    }
+
+    /// Starts over after `failure` was recovered from, keeping the
+    /// trackpads unless they were searched for again.
+    pub fn recovered_from(&mut self, failure: &Failure) {
+        match failure {
+            Failure::TrackpadLost | Failure::DispatchErrors => *self = LoopHealth::default(),
+            Failure::WriteErrors | Failure::TimerCrashed(_) => {
+                self.dispatch_errors.clear();
+                self.write_errors = 0;
+            }
+        }
+    }
+
+    /// Returns a failure if there have been too many errors lately.
+    pub fn dispatch_failed(&mut self) -> Option<Failure> {
+        let now = Instant::now();
+        self.dispatch_errors.retain(|at| now.duration_since(*at) < DISPATCH_ERROR_WINDOW);
+        self.dispatch_errors.push(now);
+
+        (self.dispatch_errors.len() >= MAX_DISPATCH_ERRORS).then_some(Failure::DispatchErrors)
+    }
+
+    /// Follows how handling each event went, and returns a failure if
+    /// writing to the virtual trackpad keeps failing.
+    pub fn event_handled(&mut self, result: &Result<(), GtError>) -> Option<Failure> {
+        match result {
+            Err(GtError::EventWriteError(_)) => self.write_errors += 1,
+            _ => self.write_errors = 0
+        }
+        (self.write_errors >= MAX_WRITE_ERRORS).then_some(Failure::WriteErrors)
+    }
+}
+
+
+/// The wait before each attempt at recovering, doubling each time, unless
+/// things have been fine for a while.
+#[derive(Debug)]
+pub struct Backoff {
+    delay: Duration,
+    last_attempt: Option<Instant>
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff { delay: FIRST_RETRY_DELAY, last_attempt: None }
+    }
+}
+
+impl Backoff {
+
+    fn next_delay(&mut self) -> Duration {
+        if self.last_attempt.is_some_and(|last| last.elapsed() > BACKOFF_RESET) {
+            self.delay = FIRST_RETRY_DELAY;
+        }
+        let delay = self.delay;
+        self.delay = (self.delay * 2).min(MAX_RETRY_DELAY);
+        self.last_attempt = Some(Instant::now());
+        delay
+    }
+
+    /// Waits for the next delay, returning early (with `false`) if the
+    /// program is asked to exit in the meantime.
+    async fn wait(&mut self, should_exit: &Arc<AtomicBool>) -> bool {
+        let until = Instant::now() + self.next_delay();
+        while Instant::now() < until {
+            if should_exit.load(Ordering::Acquire) {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        true
+    }
+}
+
+
+/// Rebuilds whatever `failure` affected, trying until it works or the
+/// program is asked to exit (in which case this returns `false`).
+/// The drag end timer is expected to be stopped while this runs, and
+/// restarted afterwards, so any drag is released here directly.
+pub async fn recover(
+    failure: &Failure,
+    translator: &mut GestureTranslator,
+    real_trackpad: &mut Libinput,
+    backoff: &mut Backoff,
+    should_exit: &Arc<AtomicBool>
+) -> bool {
+
+    warn!("Recovering from a failure in the main loop: {:?}", failure);
+
+    // whatever happened, a drag can't carry on through it
+    if let Err(e) = translator.vtp.mouse_up() {
+        warn!("Could not release the mouse button: {}", e);
+    }
+
+    loop {
+        if !backoff.wait(should_exit).await {
+            return false;
+        }
+
+        let attempt = match failure {
+            Failure::TrackpadLost | Failure::DispatchErrors => libinput_init::find_real_trackpads(&translator.cfg.disabled_devices)
+                .map(|libinput| *real_trackpad = libinput),
+            Failure::WriteErrors => translator.vtp.recreate(),
+            Failure::TimerCrashed(_) => Ok(())
+        };
+
+        match attempt {
+            Ok(()) => {
+                info!("Recovered from {:?}", failure);
+                return true;
+            },
+            Err(e) => warn!("Could not recover yet ({}), trying again", e)
+        }
+    }
+}
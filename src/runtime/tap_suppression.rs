@@ -0,0 +1,323 @@
+// Turning the desktop's own tap-to-click off while a drag holds the button,
+// with `suppressTapWhileDragging`. libinput (the desktop's copy, not ours)
+// maps a three-finger tap to a middle-click, and fingers lifting or
+// landing unevenly around a drag can read as one, which pastes on many
+// desktops. That click never goes through this program, so it can't be
+// filtered out here; the setting is changed instead, only if tapping was
+// on, and put back once the drag is over (and on exit).
+//
+// The change outlives the program (in GNOME, it's saved with the rest of
+// the user's settings), so whenever tapping is off because of us, a marker
+// file under `$XDG_STATE_HOME` says so. If the program panics, tapping is
+// put back from the panic hook; if it's killed outright, the marker is
+// still there at the next start, which puts it back then. This is never
+// done from a `--system` instance, which runs as root and would only be
+// changing root's settings.
+//
+// As with the desktop's pointer speed, each desktop is asked its own way:
+//
+//   - GNOME:    the `org.gnome.desktop.peripherals.touchpad tap-to-click` gsetting
+//   - Sway:     the touchpads' `tap`, with `swaymsg`
+//   - Hyprland: the `input:touchpad:tap-to-click` option
+
+use std::{
+    fs,
+    io::Error,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Mutex, TryLockError},
+    time::Duration
+};
+
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tracing::{debug, info, warn};
+
+use super::hyprland;
+
+
+// libinput decides whether a touch was a tap as the fingers lift, which
+// is around when a drag lets go, so tapping stays off for a bit longer
+const TAP_GRACE: Duration = Duration::from_millis(300);
+
+const HYPRLAND_TAP_OPTION: &str = "input:touchpad:tap-to-click";
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Desktop { Gnome, Hyprland, Sway }
+
+impl Desktop {
+    // how it's written in the marker file
+    fn name(self) -> &'static str {
+        match self {
+            Desktop::Gnome => "gnome",
+            Desktop::Hyprland => "hyprland",
+            Desktop::Sway => "sway"
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Desktop> {
+        [Desktop::Gnome, Desktop::Hyprland, Desktop::Sway]
+            .into_iter()
+            .find(|desktop| desktop.name() == name.trim())
+    }
+}
+
+// the desktop tapping was turned off on, so it can be put back on exit
+// (even with the task that turned it off stuck waiting on a drag)
+static TURNED_OFF: Mutex<Option<Desktop>> = Mutex::new(None);
+
+
+/// Where the marker goes: `$XDG_STATE_HOME`, or `~/.local/state` without
+/// it, since it has to outlast a logout (unlike `$XDG_RUNTIME_DIR`).
+fn marker_path() -> Option<PathBuf> {
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+    Some(marker_path_in(&state_home))
+}
+
+fn marker_path_in(state_home: &Path) -> PathBuf {
+    state_home.join("linux-3-finger-drag").join("tapping-off")
+}
+
+fn write_marker(path: &Path, desktop: Desktop) -> Result<(), Error> {
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder)?;
+    }
+    fs::write(path, desktop.name())
+}
+
+/// The desktop a marker says tapping was left off on, if there's one.
+fn read_marker(path: &Path) -> Option<Desktop> {
+    Desktop::from_name(&fs::read_to_string(path).ok()?)
+}
+
+fn remove_marker() {
+    if let Some(path) = marker_path() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+
+fn detect_desktop() -> Option<Desktop> {
+
+    if hyprland::is_running() {
+        return Some(Desktop::Hyprland);
+    }
+    if std::env::var_os("SWAYSOCK").is_some() {
+        return Some(Desktop::Sway);
+    }
+
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_uppercase()
+        .contains("GNOME")
+        .then_some(Desktop::Gnome)
+}
+
+
+/// Whether tap-to-click is on, if the desktop says.
+fn tap_enabled(desktop: Desktop) -> Option<bool> {
+    match desktop {
+        Desktop::Gnome => {
+            let output = Command::new("gsettings")
+                .args(["get", "org.gnome.desktop.peripherals.touchpad", "tap-to-click"])
+                .output()
+                .ok()?;
+            String::from_utf8_lossy(&output.stdout).trim().parse::<bool>().ok()
+        },
+        Desktop::Sway => {
+            let output = Command::new("swaymsg")
+                .args(["-t", "get_inputs", "--raw"])
+                .output()
+                .ok()?;
+            let inputs: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+            let tap = inputs.as_array()?
+                .iter()
+                .find(|input| input["type"] == "touchpad")?
+                ["libinput"]["tap"]
+                .as_str()?
+                .to_string();
+            Some(tap == "enabled")
+        },
+        Desktop::Hyprland => hyprland::get_int_option(HYPRLAND_TAP_OPTION).map(|tap| tap != 0)
+    }
+}
+
+
+fn set_tap(desktop: Desktop, enabled: bool) -> Result<(), Error> {
+
+    let output = match desktop {
+        Desktop::Gnome => Command::new("gsettings")
+            .args(["set", "org.gnome.desktop.peripherals.touchpad", "tap-to-click"])
+            .arg(enabled.to_string())
+            .output()?,
+        Desktop::Sway => Command::new("swaymsg")
+            .args(["input", "type:touchpad", "tap"])
+            .arg(if enabled { "enabled" } else { "disabled" })
+            .output()?,
+        Desktop::Hyprland => return hyprland::set_int_option(HYPRLAND_TAP_OPTION, enabled as i64)
+    };
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+    }
+}
+
+
+/// Turns tapping off, if it's on, and remembers to put it back.
+fn turn_off(desktop: Desktop) {
+
+    let mut turned_off = TURNED_OFF.lock().unwrap_or_else(|e| e.into_inner());
+    if turned_off.is_some() || tap_enabled(desktop) != Some(true) { return; }
+
+    match set_tap(desktop, false) {
+        Ok(()) => {
+            debug!("Turned off tap-to-click for the drag");
+            *turned_off = Some(desktop);
+            // without it, a crash now would leave tapping off for good
+            match marker_path() {
+                Some(path) => if let Err(e) = write_marker(&path, desktop) {
+                    debug!("Could not note that tap-to-click is off in {:?}: {}", path, e);
+                },
+                None => debug!("No $XDG_STATE_HOME or $HOME to note that tap-to-click is off in")
+            }
+        },
+        Err(e) => warn!("Could not turn off tap-to-click for the drag: {}", e)
+    }
+}
+
+
+/// Puts tapping back on, if it was turned off for a drag. Called on exit,
+/// too, since the program may be stopped mid-drag.
+pub fn restore_tapping() {
+    restore(&mut TURNED_OFF.lock().unwrap_or_else(|e| e.into_inner()));
+}
+
+fn restore(turned_off: &mut Option<Desktop>) {
+
+    let Some(desktop) = turned_off.take() else { return; };
+
+    match set_tap(desktop, true) {
+        Ok(()) => {
+            debug!("Turned tap-to-click back on");
+            remove_marker();
+        },
+        Err(e) => warn!("Could not turn tap-to-click back on (turn it on again in your \
+            desktop's touchpad settings): {}", e)
+    }
+}
+
+
+/// Puts tapping back on if a previous run left it off, by being killed
+/// (or crashing) mid-drag. Meant for startup, before any drags.
+pub fn restore_stale_tapping() {
+
+    let Some(path) = marker_path() else { return; };
+    if !path.exists() { return; }
+
+    match read_marker(&path) {
+        Some(desktop) => {
+            info!("Tap-to-click was left off by a previous run (stopped mid-drag); turning it back on");
+            restore(&mut Some(desktop));
+        },
+        None => {
+            debug!("Removing unreadable marker {:?}", path);
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+
+/// Puts tapping back on if the program panics, before going on to the
+/// usual panic hook.
+pub fn restore_tapping_on_panic() {
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // a panic while holding the lock can't wait on it; the marker
+        // covers that case at the next start instead
+        match TURNED_OFF.try_lock() {
+            Ok(mut turned_off) => restore(&mut turned_off),
+            Err(TryLockError::Poisoned(e)) => restore(&mut e.into_inner()),
+            Err(TryLockError::WouldBlock) => ()
+        }
+        previous(info);
+    }));
+}
+
+
+/// The next press of the button, or `None` once the virtual trackpad is gone.
+async fn next_press(button_events: &mut Receiver<bool>) -> Option<()> {
+    loop {
+        match button_events.recv().await {
+            Ok(true) => return Some(()),
+            Ok(false) | Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return None
+        }
+    }
+}
+
+
+/// Keeps tap-to-click off while drags hold the button, for as long as
+/// the virtual trackpad is around. Returns right away if the desktop
+/// isn't one we know how to change it for.
+pub async fn suppress_taps_while_dragging(mut button_events: Receiver<bool>) {
+
+    let Some(desktop) = detect_desktop() else {
+        warn!("Could not tell which desktop is running (or it's one whose tap-to-click \
+            can't be changed from here, like KDE Plasma), so tapping will not be turned \
+            off during drags.");
+        return;
+    };
+    info!("Turning off {:?}'s tap-to-click while dragging", desktop);
+
+    // these shell out, so keep them off the async workers
+    while next_press(&mut button_events).await.is_some() {
+        let _ = tokio::task::spawn_blocking(move || turn_off(desktop)).await;
+
+        // and it stays off until a release that isn't followed by another
+        // press (picking the drag back up) within the grace period
+        loop {
+            match button_events.recv().await {
+                Ok(true) | Err(RecvError::Lagged(_)) => continue,
+                Ok(false) => (),
+                Err(RecvError::Closed) => return
+            }
+            match tokio::time::timeout(TAP_GRACE, next_press(&mut button_events)).await {
+                Ok(Some(())) => continue,
+                Ok(None) => return,
+                Err(_) => break
+            }
+        }
+        let _ = tokio::task::spawn_blocking(restore_tapping).await;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markers_name_the_desktop_tapping_was_left_off_on() {
+        let state_home = std::env::temp_dir().join(format!("l3fd-tap-test-{}", std::process::id()));
+        let path = marker_path_in(&state_home);
+        assert_eq!(read_marker(&path), None);
+
+        for desktop in [Desktop::Gnome, Desktop::Hyprland, Desktop::Sway] {
+            write_marker(&path, desktop).unwrap();
+            assert_eq!(read_marker(&path), Some(desktop));
+        }
+
+        fs::write(&path, "plasma\n").unwrap();
+        assert_eq!(read_marker(&path), None);
+        fs::write(&path, "sway\n").unwrap();
+        assert_eq!(read_marker(&path), Some(Desktop::Sway));
+
+        fs::remove_dir_all(&state_home).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+// `outputMode: "touch"`: drags come out as a finger on a virtual
+// touchscreen instead of a held mouse button, for touch-first UIs (and
+// apps that treat touch drags differently from mouse drags, like kinetic
+// scrolling or long-press menus).
+//
+// A touchscreen works in absolute positions, so the touch has to land
+// somewhere on the screen. It lands where the pointer is, if the desktop
+// can be asked (Hyprland, or X11 with xdotool installed), and in the
+// middle of the screen otherwise; after that, it's moved by the drag's
+// motion, scaled from pixels to the touchscreen's range by the screen's
+// size. The compositor maps the touchscreen to an output on its own
+// (usually the built-in display), so on a multi-monitor setup, touches
+// may come out on a different screen than the pointer is on.
+
+use std::{fs::File, process::Command};
+
+use input_linux::{
+    sys::input_event,
+    AbsoluteAxis, AbsoluteEvent, AbsoluteInfo, AbsoluteInfoSetup,
+    EventKind, EventTime, InputEvent, InputId, InputProperty,
+    Key, KeyEvent, KeyState,
+    SynchronizeEvent, SynchronizeKind,
+    UInputHandle
+};
+use tracing::{debug, warn};
+
+use super::{
+    output_scale::{detect_compositor, Compositor},
+    virtual_trackpad::VIRTUAL_VENDOR_ID
+};
+
+
+// Like the virtual trackpad's, these mark the touchscreen as ours, so
+// device discovery skips it
+pub const VIRTUAL_TOUCH_DEVICE_NAME: &str = "Virtual touchscreen (created by linux-3-finger-drag)";
+pub const VIRTUAL_TOUCH_PRODUCT_ID: u16   = 0x5679;
+
+// Positions go from 0 to this on both axes, whatever the screen's size,
+// and the compositor scales them to the output
+const TOUCH_AXIS_MAX: i32 = 32767;
+
+// for when there's no asking the desktop how big the screen is
+const FALLBACK_SCREEN_SIZE: (f64, f64) = (1920.0, 1080.0);
+
+const ZERO: EventTime = EventTime::new(0, 0);
+
+
+/// A finger on the virtual touchscreen, from touch down to lift off.
+#[derive(Debug, Clone, Copy)]
+pub struct TouchContact {
+    at: (f64, f64),         // as a fraction of the way across and down the screen
+    screen: (f64, f64),     // the screen's size in pixels, for scaling motion
+    tracking_id: i32
+}
+
+
+/// Gives the virtual device at `uhandle` a touchscreen's capabilities,
+/// and creates it.
+pub fn create_touchscreen(uhandle: &UInputHandle<File>) -> Result<(), std::io::Error> {
+
+    uhandle.set_evbit(EventKind::Key)?;
+    uhandle.set_keybit(Key::ButtonTouch)?;
+    uhandle.set_evbit(EventKind::Absolute)?;
+    // marks it as a touchscreen rather than a (pointer-moving) tablet
+    uhandle.set_propbit(InputProperty::Direct)?;
+
+    let axis = |axis, maximum| AbsoluteInfoSetup {
+        axis,
+        info: AbsoluteInfo { value: 0, minimum: 0, maximum, fuzz: 0, flat: 0, resolution: 0 }
+    };
+    // only one finger, so only one slot
+    let axes = [
+        axis(AbsoluteAxis::X, TOUCH_AXIS_MAX),
+        axis(AbsoluteAxis::Y, TOUCH_AXIS_MAX),
+        axis(AbsoluteAxis::MultitouchSlot, 0),
+        axis(AbsoluteAxis::MultitouchPositionX, TOUCH_AXIS_MAX),
+        axis(AbsoluteAxis::MultitouchPositionY, TOUCH_AXIS_MAX),
+        axis(AbsoluteAxis::MultitouchTrackingId, i32::from(u16::MAX))
+    ];
+    for setup in &axes {
+        uhandle.set_absbit(setup.axis)?;
+    }
+
+    let input_id = InputId {
+        bustype: input_linux::sys::BUS_USB,
+        vendor: VIRTUAL_VENDOR_ID,
+        product: VIRTUAL_TOUCH_PRODUCT_ID,
+        version: 0,
+    };
+    uhandle.create(&input_id, VIRTUAL_TOUCH_DEVICE_NAME.as_bytes(), 0, &axes)?;
+    debug!("Virtual touchscreen successfully created.");
+
+    if pointer_on_screen().is_none() {
+        warn!("Can't find out where the pointer is on this desktop, so touch drags \
+            will start from the middle of the screen");
+    }
+    Ok(())
+}
+
+
+/// Whether a device with the given name and IDs is a virtual touchscreen
+/// created by this program.
+pub fn is_virtual_touchscreen(name: &str, vendor: u32, product: u32) -> bool {
+    name == VIRTUAL_TOUCH_DEVICE_NAME
+    && vendor == VIRTUAL_VENDOR_ID as u32
+    && product == VIRTUAL_TOUCH_PRODUCT_ID as u32
+}
+
+
+impl TouchContact {
+
+    /// A new touch where the pointer is (or the middle of the screen).
+    pub fn at_pointer(tracking_id: i32) -> TouchContact {
+        let (at, screen) = pointer_on_screen().unwrap_or(((0.5, 0.5), FALLBACK_SCREEN_SIZE));
+        debug!("Touching down at {:.3}, {:.3} of a {}x{} screen", at.0, at.1, screen.0, screen.1);
+        TouchContact { at, screen, tracking_id }
+    }
+
+
+    /// Moves the touch by `dx`, `dy` pixels, stopping at the screen's edges.
+    pub fn move_by(&mut self, dx: f64, dy: f64) {
+        self.at = (
+            (self.at.0 + dx / self.screen.0).clamp(0.0, 1.0),
+            (self.at.1 + dy / self.screen.1).clamp(0.0, 1.0)
+        );
+    }
+
+
+    fn position(&self) -> (i32, i32) {
+        (
+            (self.at.0 * TOUCH_AXIS_MAX as f64).round() as i32,
+            (self.at.1 * TOUCH_AXIS_MAX as f64).round() as i32
+        )
+    }
+
+
+    /// The events that put the finger down.
+    pub fn down_events(&self) -> Vec<input_event> {
+        let mut events = vec![
+            InputEvent::from(AbsoluteEvent::new(ZERO, AbsoluteAxis::MultitouchSlot, 0)).into_raw(),
+            InputEvent::from(AbsoluteEvent::new(ZERO, AbsoluteAxis::MultitouchTrackingId, self.tracking_id)).into_raw(),
+            InputEvent::from(KeyEvent::new(ZERO, Key::ButtonTouch, KeyState::pressed(true))).into_raw()
+        ];
+        events.extend(self.move_events());
+        events
+    }
+
+
+    /// The events that move the finger to where the contact is now, in
+    /// both the multitouch and the single-touch axes.
+    pub fn move_events(&self) -> Vec<input_event> {
+        let (x, y) = self.position();
+        vec![
+            InputEvent::from(AbsoluteEvent::new(ZERO, AbsoluteAxis::MultitouchPositionX, x)).into_raw(),
+            InputEvent::from(AbsoluteEvent::new(ZERO, AbsoluteAxis::MultitouchPositionY, y)).into_raw(),
+            InputEvent::from(AbsoluteEvent::new(ZERO, AbsoluteAxis::X, x)).into_raw(),
+            InputEvent::from(AbsoluteEvent::new(ZERO, AbsoluteAxis::Y, y)).into_raw(),
+            InputEvent::from(SynchronizeEvent::new(ZERO, SynchronizeKind::Report, 0)).into_raw()
+        ]
+    }
+}
+
+
+/// The events that lift the finger. These don't depend on where it was,
+/// so a clone that's lost track of the contact (like the timer thread's)
+/// can still lift it.
+pub fn lift_events() -> [input_event; 3] {
+    [
+        InputEvent::from(AbsoluteEvent::new(ZERO, AbsoluteAxis::MultitouchTrackingId, -1)).into_raw(),
+        InputEvent::from(KeyEvent::new(ZERO, Key::ButtonTouch, KeyState::pressed(false))).into_raw(),
+        InputEvent::from(SynchronizeEvent::new(ZERO, SynchronizeKind::Report, 0)).into_raw()
+    ]
+}
+
+
+/// Where the pointer is, as a fraction of the way across and down the
+/// screen it's on, along with that screen's size in pixels. Only some
+/// desktops can be asked; Sway, GNOME, and KDE don't say where the
+/// pointer is.
+fn pointer_on_screen() -> Option<((f64, f64), (f64, f64))> {
+    match detect_compositor() {
+        Some(Compositor::Hyprland) => hyprland_pointer(),
+        Some(Compositor::Sway) => None,
+        None if std::env::var_os("WAYLAND_DISPLAY").is_none() => x11_pointer(),
+        None => None
+    }
+}
+
+
+fn hyprland_pointer() -> Option<((f64, f64), (f64, f64))> {
+
+    let cursor = Command::new("hyprctl").args(["cursorpos", "-j"]).output().ok()?;
+    let cursor: serde_json::Value = serde_json::from_slice(&cursor.stdout).ok()?;
+    let (x, y) = (cursor["x"].as_f64()?, cursor["y"].as_f64()?);
+
+    // the cursor's position is in logical pixels across all the monitors,
+    // but their sizes are before scaling
+    let monitors = Command::new("hyprctl").args(["monitors", "-j"]).output().ok()?;
+    let monitors: serde_json::Value = serde_json::from_slice(&monitors.stdout).ok()?;
+
+    monitors.as_array()?.iter().find_map(|monitor| {
+        let scale = monitor["scale"].as_f64().unwrap_or(1.0);
+        let (left, top) = (monitor["x"].as_f64()?, monitor["y"].as_f64()?);
+        let (width, height) = (monitor["width"].as_f64()? / scale, monitor["height"].as_f64()? / scale);
+
+        let on_it = (left..left + width).contains(&x) && (top..top + height).contains(&y);
+        on_it.then(|| (((x - left) / width, (y - top) / height), (width, height)))
+    })
+}
+
+
+fn x11_pointer() -> Option<((f64, f64), (f64, f64))> {
+
+    // X11 maps a touchscreen across the whole display, not one monitor,
+    // so that's what the position is relative to
+    let location = Command::new("xdotool").args(["getmouselocation", "--shell"]).output().ok()?;
+    let location = String::from_utf8_lossy(&location.stdout).into_owned();
+    let coordinate = |name: &str| location.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix('=')?.parse::<f64>().ok());
+    let (x, y) = (coordinate("X")?, coordinate("Y")?);
+
+    let geometry = Command::new("xdotool").arg("getdisplaygeometry").output().ok()?;
+    let geometry = String::from_utf8_lossy(&geometry.stdout).into_owned();
+    let mut sizes = geometry.split_whitespace().filter_map(|size| size.parse::<f64>().ok());
+    let (width, height) = (sizes.next()?, sizes.next()?);
+
+    (width > 0.0 && height > 0.0).then(|| ((x / width, y / height), (width, height)))
+}
@@ -0,0 +1,246 @@
+// Gesture traces, for `record` and `replay`: the trackpad's events as
+// libinput gave them, with their timestamps, written one JSON object to a
+// line, so a drag that misbehaves can be attached to a bug report and
+// played back somewhere without the same trackpad. Each event keeps
+// everything the translator reads from libinput's, and nothing more; the
+// translator takes its events in this form even when they're live, so a
+// replay goes through exactly what the original did.
+//
+// The first line is a header, with the version that recorded it and the
+// config it was running with, since that decides what the gestures turn
+// into as much as the gestures themselves do.
+
+use std::{
+    io::{BufRead, Error, ErrorKind, Write},
+    os::fd::AsRawFd,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration
+};
+
+use input::{
+    event::{
+        gesture::{
+            GestureEndEvent, GestureEvent, GestureEventCoordinates, GestureEventTrait,
+            GestureHoldEvent, GesturePinchEvent, GesturePinchEventTrait, GestureSwipeEvent
+        },
+        pointer::{PointerEvent, PointerEventTrait},
+        DeviceEvent, EventTrait
+    },
+    Event, Libinput
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::unix::AsyncFd;
+
+use crate::init::config::Configuration;
+
+
+// bumped if a trace from an older version would be read wrong
+pub const TRACE_FORMAT: u32 = 1;
+
+// how often recording checks whether it's been told to stop
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceHeader {
+    pub trace_format: u32,
+    pub version: String,                // of the program that recorded it
+    pub config: serde_json::Value       // as it was when recording
+}
+
+impl TraceHeader {
+    pub fn new(cfg: &Configuration) -> TraceHeader {
+        TraceHeader {
+            trace_format: TRACE_FORMAT,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            config: serde_json::to_value(cfg).unwrap_or_default()
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum TraceKind {
+    DeviceAdded,
+    DeviceRemoved,
+    SwipeBegin,
+    SwipeUpdate,
+    SwipeEnd,
+    PinchBegin,
+    PinchUpdate,
+    PinchEnd,
+    HoldBegin,
+    HoldEnd,
+    PointerMotion,
+    #[default]
+    Other           // anything else, like a click or a scroll
+}
+
+
+/// One event from the trackpad. Fields that don't go with its kind are
+/// left out of the trace.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TraceEvent {
+    pub time_usec: u64,     // libinput's timestamp (0 for device events, which have none)
+    pub kind: TraceKind,
+    pub device: String,     // by name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<u32>,    // for DeviceAdded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product: Option<u32>,   // likewise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_mm: Option<(f64, f64)>,    // likewise, if the trackpad reports it
+    #[serde(skip_serializing_if = "is_zero")]
+    pub fingers: i32,
+    #[serde(skip_serializing_if = "is_zero_f64")]
+    pub dx: f64,
+    #[serde(skip_serializing_if = "is_zero_f64")]
+    pub dy: f64,
+    #[serde(skip_serializing_if = "is_zero_f64")]
+    pub dx_unaccelerated: f64,
+    #[serde(skip_serializing_if = "is_zero_f64")]
+    pub dy_unaccelerated: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,     // for PinchUpdate
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool
+}
+
+fn is_zero(n: &i32) -> bool { *n == 0 }
+fn is_zero_f64(n: &f64) -> bool { *n == 0.0 }
+
+
+impl TraceEvent {
+
+    /// `event`, as it goes in a trace.
+    pub fn from_event(event: &Event) -> TraceEvent {
+
+        let device = event.device();
+        let mut traced = TraceEvent {
+            device: device.name().to_string(),
+            ..Default::default()
+        };
+
+        match event {
+            Event::Device(DeviceEvent::Added(_)) => {
+                traced.kind = TraceKind::DeviceAdded;
+                traced.vendor = Some(device.id_vendor());
+                traced.product = Some(device.id_product());
+                traced.size_mm = device.size();
+            },
+            Event::Device(DeviceEvent::Removed(_)) => traced.kind = TraceKind::DeviceRemoved,
+            Event::Pointer(PointerEvent::Motion(motion)) => {
+                traced.kind = TraceKind::PointerMotion;
+                traced.time_usec = motion.time_usec();
+                traced.dx = motion.dx();
+                traced.dy = motion.dy();
+                traced.dx_unaccelerated = motion.dx_unaccelerated();
+                traced.dy_unaccelerated = motion.dy_unaccelerated();
+            },
+            Event::Gesture(gesture) => {
+                traced.time_usec = gesture.time_usec();
+                traced.fingers = gesture.finger_count();
+                match gesture {
+                    GestureEvent::Swipe(GestureSwipeEvent::Begin(_)) => traced.kind = TraceKind::SwipeBegin,
+                    GestureEvent::Swipe(GestureSwipeEvent::Update(update)) => {
+                        traced.kind = TraceKind::SwipeUpdate;
+                        traced.dx = update.dx();
+                        traced.dy = update.dy();
+                        traced.dx_unaccelerated = update.dx_unaccelerated();
+                        traced.dy_unaccelerated = update.dy_unaccelerated();
+                    },
+                    GestureEvent::Swipe(GestureSwipeEvent::End(end)) => {
+                        traced.kind = TraceKind::SwipeEnd;
+                        traced.cancelled = end.cancelled();
+                    },
+                    GestureEvent::Pinch(GesturePinchEvent::Begin(_)) => traced.kind = TraceKind::PinchBegin,
+                    GestureEvent::Pinch(GesturePinchEvent::Update(update)) => {
+                        traced.kind = TraceKind::PinchUpdate;
+                        traced.dx = update.dx();
+                        traced.dy = update.dy();
+                        traced.scale = Some(update.scale());
+                    },
+                    GestureEvent::Pinch(GesturePinchEvent::End(end)) => {
+                        traced.kind = TraceKind::PinchEnd;
+                        traced.cancelled = end.cancelled();
+                    },
+                    GestureEvent::Hold(GestureHoldEvent::Begin(_)) => traced.kind = TraceKind::HoldBegin,
+                    GestureEvent::Hold(GestureHoldEvent::End(end)) => {
+                        traced.kind = TraceKind::HoldEnd;
+                        traced.cancelled = end.cancelled();
+                    },
+                    _ => ()
+                }
+            },
+            _ => ()
+        }
+        traced
+    }
+}
+
+
+/// Writes everything `libinput` reports to `out`, as a trace (header
+/// first), until `should_stop` is set. Nothing is injected. Returns how
+/// many events were recorded.
+pub async fn record(
+    mut libinput: Libinput,
+    cfg: &Configuration,
+    out: &mut impl Write,
+    should_stop: &AtomicBool
+) -> Result<usize, Error> {
+
+    serde_json::to_writer(&mut *out, &TraceHeader::new(cfg))?;
+    writeln!(out)?;
+
+    let fd = AsyncFd::new(libinput.as_raw_fd())?;
+    let mut recorded = 0;
+
+    while !should_stop.load(Ordering::Relaxed) {
+        let Ok(guard) = tokio::time::timeout(STOP_CHECK_INTERVAL, fd.readable()).await else { continue };
+        guard?.clear_ready();
+
+        libinput.dispatch()?;
+        for event in &mut libinput {
+            serde_json::to_writer(&mut *out, &TraceEvent::from_event(&event))?;
+            writeln!(out)?;
+            recorded += 1;
+        }
+        // a batch at a time, so a trace cut short still has what came before
+        out.flush()?;
+    }
+    Ok(recorded)
+}
+
+
+/// Reads a trace written by `record()`: its header, and its events in
+/// order.
+pub fn read_trace(input: impl BufRead) -> Result<(TraceHeader, Vec<TraceEvent>), Error> {
+
+    let mut lines = input.lines();
+    let header: TraceHeader = match lines.next() {
+        Some(line) => serde_json::from_str(&line?)?,
+        None => return Err(Error::new(ErrorKind::InvalidData, "the trace is empty"))
+    };
+    if header.trace_format > TRACE_FORMAT {
+        return Err(Error::new(ErrorKind::InvalidData, format!(
+            "the trace was recorded by a newer version ({}); update to replay it", header.version
+        )));
+    }
+
+    let mut events = Vec::new();
+    for (number, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = serde_json::from_str(&line).map_err(|e|
+            // the header is line 1
+            Error::new(ErrorKind::InvalidData, format!("line {} of the trace: {}", number + 2, e))
+        )?;
+        events.push(event);
+    }
+    Ok((header, events))
+}
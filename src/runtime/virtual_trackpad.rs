@@ -6,11 +6,12 @@
 
 use std::{
     fs::{File, OpenOptions}, 
-    os::{fd::AsFd, unix::fs::OpenOptionsExt}, 
+    os::{fd::{AsFd, AsRawFd, OwnedFd, RawFd}, unix::fs::OpenOptionsExt}, 
+    path::{Path, PathBuf},
     thread, time::{self, Duration}
 };
 
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::{broadcast, mpsc::Receiver};
 use input_linux::{
     EventKind, EventTime, 
     InputEvent, InputId, 
@@ -20,43 +21,91 @@ use input_linux::{
     UInputHandle
 };
 
-use nix::libc::O_NONBLOCK;
-use tracing::{debug, error, trace};
+use nix::libc::{O_NONBLOCK, O_RDWR};
+use tracing::{debug, error, info, trace};
+
+use crate::{
+    init::{
+        config::OutputMode,
+        mac_check::log_if_mac_denial,
+        sandbox::log_if_sandboxed,
+        open_helper::{is_permission_error, open_via_helper},
+        setup
+    },
+    runtime::{
+        bench,
+        event_handler::ControlSignal::{self, *},
+        stats,
+        touch_output::{self, TouchContact}
+    }
+};
+
 
-use crate::runtime::event_handler::ControlSignal::{self, *};
+// These are what mark the virtual trackpad as ours. Device discovery
+// (and the hot-plug udev rule) check for them explicitly, so the program
+// never binds to its own output, even if the virtual trackpad someday 
+// gains enough capabilities to pass for a real one.
+pub const VIRTUAL_DEVICE_NAME: &str = "Virtual trackpad (created by linux-3-finger-drag)";
+pub const VIRTUAL_VENDOR_ID: u16    = 0x1234;
+pub const VIRTUAL_PRODUCT_ID: u16   = 0x5678;  // iykyk
+
+// Button state changes are only small bools, and whatever listens to them
+// shouldn't fall this far behind
+const BUTTON_EVENT_CAPACITY: usize = 16;
 
 
 /// This struct is does not preserve `mouse_is_down` state between clones: 
-/// that is copied during cloning, for simplicity. 
+/// that is copied during cloning, for simplicity. Clones do share 
+/// `button_events`, though, so button state changes from any of them
+/// reach the same subscribers.
+///
+/// With `outputMode: "touch"`, the device is a touchscreen instead, and
+/// `touch` is where the finger on it is: a "button" press puts the finger
+/// down, and moving moves it (see `touch_output`).
+///
+/// With `--dry-run`, there's no device at all (`handle` is `None`), and
+/// what would have been written to it is logged instead.
 pub struct VirtualTrackpad {
-    handle: UInputHandle<File>,
-    pub mouse_is_down: bool
+    handle: Option<UInputHandle<File>>,
+    pub mouse_is_down: bool,
+    button_events: broadcast::Sender<bool>,  // true for a press, false for a release
+    touch: Option<TouchState>
+}
+
+
+/// The finger on the virtual touchscreen, if it's down, and the tracking
+/// ID the next one gets (which the kernel wants different for each touch).
+#[derive(Debug, Clone, Copy, Default)]
+struct TouchState {
+    contact: Option<TouchContact>,
+    next_tracking_id: i32
 }
 
 
 pub fn start_handler() -> Result<VirtualTrackpad, std::io::Error> {
-    let uinput_file_res = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .custom_flags(O_NONBLOCK)
-        .open("/dev/uinput");
+    start_handler_for(OutputMode::Pointer)
+}
 
-    let uinput_file = match uinput_file_res {
-        Ok(file) => file,
-        Err(e) => {
-            error!(
-                "You are not yet allowed to write to /dev/uinput.\n\
-                Some things to try:\n\
-                - Update the udev rules for uinput (see installation guide in README.md, step 3.1)\n\
-                - Log out and log in again\n\
-                - Restart your computer\n\
-                - FOR ARCH: make sure the uinput kernel module is loaded on boot\n",
-            );
-            return Err(e);
-        }
-    };
 
-    let uhandle = UInputHandle::new(uinput_file);
+/// Creates the virtual device for `mode`: a trackpad (well, a mouse) for
+/// pointer drags, or a touchscreen for touch drags.
+pub fn start_handler_for(mode: OutputMode) -> Result<VirtualTrackpad, std::io::Error> {
+
+    let uhandle = UInputHandle::new(open_uinput()?);
+
+    if mode == OutputMode::Touch {
+        touch_output::create_touchscreen(&uhandle)?;
+        thread::sleep(time::Duration::from_millis(500));
+
+        return Ok(
+            VirtualTrackpad {
+                handle: Some(uhandle),
+                mouse_is_down: false,
+                button_events: broadcast::channel(BUTTON_EVENT_CAPACITY).0,
+                touch: Some(TouchState::default())
+            }
+        );
+    }
 
     // Setting up virtual device capabilities during initialization.
     // These operations should not fail if /dev/uinput was successfully opened.
@@ -64,6 +113,10 @@ pub fn start_handler() -> Result<VirtualTrackpad, std::io::Error> {
         .expect("Failed to set Key event capability on virtual device");
     uhandle.set_keybit(input_linux::Key::ButtonLeft)
         .expect("Failed to set ButtonLeft capability on virtual device");
+    uhandle.set_keybit(input_linux::Key::ButtonRight)
+        .expect("Failed to set ButtonRight capability on virtual device");
+    uhandle.set_keybit(input_linux::Key::ButtonMiddle)
+        .expect("Failed to set ButtonMiddle capability on virtual device");
 
     uhandle.set_evbit(EventKind::Relative)
         .expect("Failed to set Relative event capability on virtual device");
@@ -74,12 +127,11 @@ pub fn start_handler() -> Result<VirtualTrackpad, std::io::Error> {
 
     let input_id = InputId {
         bustype: input_linux::sys::BUS_USB,
-        vendor: 0x1234,
-        product: 0x5678,  // iykyk
+        vendor: VIRTUAL_VENDOR_ID,
+        product: VIRTUAL_PRODUCT_ID,
         version: 0,
     };
-    let device_name = b"Virtual trackpad (created by linux-3-finger-drag)";
-    uhandle.create(&input_id, device_name, 0, &[])
+    uhandle.create(&input_id, VIRTUAL_DEVICE_NAME.as_bytes(), 0, &[])
         .expect("Failed to create virtual trackpad device");
     debug!("Virtual trackpad successfully created.");
 
@@ -88,14 +140,82 @@ pub fn start_handler() -> Result<VirtualTrackpad, std::io::Error> {
 
     Ok(
         VirtualTrackpad { 
-            handle: uhandle, 
-            mouse_is_down: false
+            handle: Some(uhandle), 
+            mouse_is_down: false,
+            button_events: broadcast::channel(BUTTON_EVENT_CAPACITY).0,
+            touch: None
         }
     )
 
 }
 
 
+/// Opens /dev/uinput for creating a device, and if that isn't allowed,
+/// logs what to do about it.
+pub fn open_uinput() -> Result<File, std::io::Error> {
+    let uinput_file_res = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(O_NONBLOCK)
+        .open("/dev/uinput")
+        // not allowed to open it ourselves, so see if the setgid helper can
+        .or_else(|err|
+            if is_permission_error(&err) {
+                open_via_helper(Path::new("/dev/uinput"), O_RDWR | O_NONBLOCK).map(File::from)
+            } else {
+                Err(err)
+            }
+        );
+
+    let uinput_file = match uinput_file_res {
+        Ok(file) => file,
+        // the usual advice won't help if it's SELinux or AppArmor saying no
+        Err(e) if is_permission_error(&e) && log_if_mac_denial(Path::new("/dev/uinput")) => {
+            return Err(e);
+        },
+        // nor if there's no /dev/uinput to be had, in a Flatpak or container
+        Err(e) if log_if_sandboxed() => {
+            return Err(e);
+        },
+        // the rule's there, so it's only a matter of it applying
+        Err(e) if setup::udev_rule_installed() => {
+            error!(
+                "You are not yet allowed to write to /dev/uinput, though the udev rule \
+                for it is installed. Log out and log in again, or restart your computer, \
+                for it to take effect (or run `linux-3-finger-drag doctor` for more)."
+            );
+            return Err(e);
+        },
+        Err(e) => {
+            error!(
+                "You are not yet allowed to write to /dev/uinput.\n\
+                Some things to try:\n\
+                - Install the udev rule for uinput, with `sudo linux-3-finger-drag install-udev-rule` \
+                (or see installation guide in README.md, step 3.1)\n\
+                - Log out and log in again\n\
+                - Restart your computer\n\
+                - FOR ARCH: make sure the uinput kernel module is loaded on boot\n",
+            );
+            return Err(e);
+        }
+    };
+
+    Ok(uinput_file)
+}
+
+
+/// Whether a device with the given name and IDs is a virtual trackpad
+/// (or touchscreen, or `bench`'s touchpad) created by this program (this
+/// instance or any other).
+pub fn is_virtual_trackpad(name: &str, vendor: u32, product: u32) -> bool {
+    (name == VIRTUAL_DEVICE_NAME
+        && vendor == VIRTUAL_VENDOR_ID as u32
+        && product == VIRTUAL_PRODUCT_ID as u32)
+    || touch_output::is_virtual_touchscreen(name, vendor, product)
+    || bench::is_bench_touchpad(name, vendor, product)
+}
+
+
 impl Clone for VirtualTrackpad {
     /// This clone() can theoretically panic since there is an expect() in 
     /// its definition. This is because `try_cloned_to_owned`, from `std::io`,
@@ -112,33 +232,112 @@ impl Clone for VirtualTrackpad {
     /// Note that the boolean `mouse_is_down` is *copied*, **not** passed by 
     /// reference, for simplicity. 
     fn clone(&self) -> Self {
-        let uinput_fd = self.handle
-            .as_fd()
-            .try_clone_to_owned()
-            .expect(
-                "uinput file descriptor could not be duplicated, \
-                likely do to hitting the maximum open file descriptors \
-                for this OS."
-        );
+        let handle = self.handle.as_ref().map(|handle| {
+            let uinput_fd = handle
+                .as_fd()
+                .try_clone_to_owned()
+                .expect(
+                    "uinput file descriptor could not be duplicated, \
+                    likely do to hitting the maximum open file descriptors \
+                    for this OS."
+            );
+            UInputHandle::new(File::from(uinput_fd))
+        });
 
         VirtualTrackpad {
-            handle: UInputHandle::new(File::from(uinput_fd)),
-            mouse_is_down: self.mouse_is_down
+            handle,
+            mouse_is_down: self.mouse_is_down,
+            button_events: self.button_events.clone(),
+            touch: self.touch
         }
     }
 }
 
 
+impl AsRawFd for VirtualTrackpad {
+    /// -1 (which is never a valid fd) for a dry run.
+    fn as_raw_fd(&self) -> RawFd {
+        self.handle.as_ref().map_or(-1, AsRawFd::as_raw_fd)
+    }
+}
+
+
 impl VirtualTrackpad
 {
     const ZERO: EventTime = EventTime::new(0, 0);
 
+    /// Wraps a uinput fd for an already-created virtual trackpad (or
+    /// touchscreen, if `is_touchscreen`), handed over by a previous run of
+    /// the program (see `reexec`). A finger left down on a touchscreen
+    /// isn't picked up, since where it was is lost; it's lifted on the
+    /// next release as usual.
+    pub fn from_handoff(uinput_fd: OwnedFd, mouse_is_down: bool, is_touchscreen: bool) -> VirtualTrackpad {
+        VirtualTrackpad {
+            handle: Some(UInputHandle::new(File::from(uinput_fd))),
+            mouse_is_down,
+            button_events: broadcast::channel(BUTTON_EVENT_CAPACITY).0,
+            touch: is_touchscreen.then(TouchState::default)
+        }
+    }
+
+
+    /// A stand-in for the virtual trackpad (or touchscreen, for `mode`),
+    /// for `--dry-run`: everything goes through it as usual, but nothing
+    /// is created, and what would have been written is logged instead.
+    pub fn dry_run(mode: OutputMode) -> VirtualTrackpad {
+        info!("Dry run: no virtual trackpad is created, so drags are only logged");
+        VirtualTrackpad {
+            handle: None,
+            mouse_is_down: false,
+            button_events: broadcast::channel(BUTTON_EVENT_CAPACITY).0,
+            touch: (mode == OutputMode::Touch).then(TouchState::default)
+        }
+    }
+
+
+    pub fn is_dry_run(&self) -> bool {
+        self.handle.is_none()
+    }
+
+
+    /// Whether this is a touchscreen (for `outputMode: "touch"`) rather
+    /// than a trackpad.
+    pub fn is_touchscreen(&self) -> bool {
+        self.touch.is_some()
+    }
+
+
+    /// Subscribes to button presses (`true`) and releases (`false`) from
+    /// this virtual trackpad and all its clones. Releases are sent even if
+    /// no button was down (since a clone can't tell), so subscribers that
+    /// care about changes should skip repeats.
+    pub fn subscribe_button_events(&self) -> broadcast::Receiver<bool> {
+        self.button_events.subscribe()
+    }
+
     pub fn mouse_down(&mut self) -> Result<(), std::io::Error> {
+        self.button_down(Key::ButtonLeft)
+    }
+
+    /// Presses `button` (left, middle, or right) and holds it, until `mouse_up`.
+    /// On a touchscreen, this puts a finger down where the pointer is
+    /// instead, whichever button it is.
+    pub fn button_down(&mut self, button: Key) -> Result<(), std::io::Error> {
+        if let Some(touch) = &mut self.touch {
+            let contact = TouchContact::at_pointer(touch.next_tracking_id);
+            touch.next_tracking_id = touch.next_tracking_id.wrapping_add(1) & i32::from(u16::MAX);
+            write(&self.handle, &contact.down_events())?;
+            touch.contact = Some(contact);
+            self.mouse_is_down = true;
+            let _ = self.button_events.send(true);
+            return Ok(());
+        }
+
         let events = [
             InputEvent::from(
                 KeyEvent::new(
                     VirtualTrackpad::ZERO, 
-                    Key::ButtonLeft, 
+                    button, 
                     KeyState::pressed(true))
                 ).into_raw(),
             InputEvent::from(
@@ -148,29 +347,43 @@ impl VirtualTrackpad
                     0)
                 ).into_raw(),
         ];
-        self.handle.write(&events)?;
+        write(&self.handle, &events)?;
         self.mouse_is_down = true;
+        let _ = self.button_events.send(true);     // only fails if nobody's subscribed
         Ok(())
     }
 
-    pub fn mouse_up(&mut self) -> Result<(), std::io::Error> {   
-
-        let events = [
-            InputEvent::from(
-                KeyEvent::new(
-                    VirtualTrackpad::ZERO, 
-                    Key::ButtonLeft, 
-                    KeyState::pressed(false))
-                ).into_raw(),
+    /// A release for each button a drag can hold, and the sync after them.
+    fn release_events() -> [input_linux::sys::input_event; 4] {
+        let release = |button| InputEvent::from(
+            KeyEvent::new(VirtualTrackpad::ZERO, button, KeyState::pressed(false))
+        ).into_raw();
+        [
+            release(Key::ButtonLeft),
+            release(Key::ButtonMiddle),
+            release(Key::ButtonRight),
             InputEvent::from(
                 SynchronizeEvent::new(
                     VirtualTrackpad::ZERO, 
                     SynchronizeKind::Report, 
                     0)
                 ).into_raw(),
-        ];
-        self.handle.write(&events)?;
+        ]
+    }
+
+    /// Releases whichever button is held. Every button gets a release 
+    /// written, since the timer thread works on a clone that doesn't know
+    /// which one was pressed; the kernel drops the release for a button 
+    /// that isn't down, so it never reaches anything else.
+    pub fn mouse_up(&mut self) -> Result<(), std::io::Error> {   
+
+        if self.touch.is_some() {
+            return self.lift_touch();
+        }
+
+        write(&self.handle, &VirtualTrackpad::release_events())?;
         self.mouse_is_down = false;
+        let _ = self.button_events.send(false);
 
         debug!("mouse_up written from simple mouse_up fn");
 
@@ -183,7 +396,7 @@ impl VirtualTrackpad
     /// thread will not panic, and will not stop unless either it's 
     /// sent a `ControlSignal::TerminateThread`, or an error was 
     /// raised. So if it ends prematurely, it's because of an error.
-    pub async fn handle_mouse_up_timeout(&mut self, delay: Duration, mut rx: Receiver<ControlSignal>) -> Result<(), std::io::Error> {
+    pub async fn handle_mouse_up_timeout(&mut self, mut delay: Duration, mut rx: Receiver<ControlSignal>) -> Result<(), std::io::Error> {
         
         loop {
             trace!("awaiting signal in handle_mouse_up_timeout...");
@@ -202,16 +415,20 @@ impl VirtualTrackpad
                     continue;
                 },
                 CancelMouseUp => continue,  // don't do anything this iteration
+                UpdateDelay(new_delay) => {
+                    delay = new_delay;
+                    continue;
+                },
                 TerminateThread => break
             }
 
             // handle signals received during timer loop
             // that can't be handled within that scope
-            if let Some(signal) = self.run_timer(delay, &mut rx).await {
+            if let Some(signal) = self.run_timer(&mut delay, &mut rx).await {
                 match signal {
                     CancelMouseUp => continue,
                     TerminateThread => break,
-                    _ => {}                     // cancel/restart timer have already been handled
+                    _ => {}                     // cancel/restart timer and delay updates have already been handled
                 }
             }
 
@@ -229,30 +446,47 @@ impl VirtualTrackpad
         
         std::thread::sleep(delay);
 
-        let events = [
-            InputEvent::from(
-                KeyEvent::new(
-                    VirtualTrackpad::ZERO,
-                    Key::ButtonLeft, 
-                    KeyState::pressed(false))
-                ).into_raw(),
-            InputEvent::from(
-                SynchronizeEvent::new(
-                    VirtualTrackpad::ZERO,
-                    SynchronizeKind::Report, 
-                    0)
-                ).into_raw(),
-        ];
-        self.handle.write(&events)?;
+        if self.touch.is_some() {
+            return self.lift_touch();
+        }
+
+        // same as in mouse_up(), every button is released
+        write(&self.handle, &VirtualTrackpad::release_events())?;
 
         debug!("mouse_up written from mouse_up_delay_blocking");
 
         self.mouse_is_down = false;
+        let _ = self.button_events.send(false);
+        Ok(())
+    }
+
+
+    /// Lifts the finger off the touchscreen. Like `mouse_up()`, this is
+    /// written whether or not this clone thinks a finger is down.
+    fn lift_touch(&mut self) -> Result<(), std::io::Error> {
+        write(&self.handle, &touch_output::lift_events())?;
+        if let Some(touch) = &mut self.touch {
+            touch.contact = None;
+        }
+        self.mouse_is_down = false;
+        let _ = self.button_events.send(false);
+
+        debug!("Touch lifted");
         Ok(())
     }
 
 
-    pub fn mouse_move_relative(&self, x_rel: f64, y_rel:f64) -> Result<(), std::io::Error> {
+    pub fn mouse_move_relative(&mut self, x_rel: f64, y_rel:f64) -> Result<(), std::io::Error> {
+
+        // a touchscreen has no relative motion, so the finger on it is
+        // moved instead (and with no finger down, there's nothing to move)
+        if let Some(touch) = &mut self.touch {
+            if let Some(contact) = &mut touch.contact {
+                contact.move_by(x_rel, y_rel);
+                write(&self.handle, &contact.move_events())?;
+            }
+            return Ok(());
+        }
         
         // RelativeEvent::new() can only take integers, 
         // so some precision must be lost. But this needs to be done 
@@ -297,19 +531,20 @@ impl VirtualTrackpad
                     0)
                 ).into_raw(),
         ];
-        self.handle.write(&events)?;
+        write(&self.handle, &events)?;
         Ok(())
     }
 
 
     /// A timer that can be cancelled or reset via a signal in the channel. The return value
     /// is what signal was received, if any, except for `RestartTimer`, since it can be handled 
-    /// within the function.
-    async fn run_timer(&self, delay: Duration, rx: &mut Receiver<ControlSignal>) -> Option<ControlSignal> {
+    /// within the function. It sleeps on tokio's clock, which is monotonic, like the
+    /// translator's (see `clock`).
+    async fn run_timer(&self, delay: &mut Duration, rx: &mut Receiver<ControlSignal>) -> Option<ControlSignal> {
         loop {
             // Use tokio::select! to race between timeout and signal
             let signal = tokio::select! {
-                _ = tokio::time::sleep(delay) => {
+                _ = tokio::time::sleep(*delay) => {
                     trace!("Delay completed fully");
                     None
                 }
@@ -318,6 +553,12 @@ impl VirtualTrackpad
             
             match signal {
                 RestartTimer => continue,  
+                // the timer restarts with the new delay, rather than 
+                // trying to work out how much of it is left
+                UpdateDelay(new_delay) => {
+                    *delay = new_delay;
+                    continue;
+                },
                 // function exits, lets the outer loop handle the other signals
                 // covers `CancelTimer` arm, since the behavior would be identical
                 _ => return Some(signal), 
@@ -325,7 +566,125 @@ impl VirtualTrackpad
         }
     }
 
+    /// The evdev node (`/dev/input/eventN`) the kernel made for this
+    /// virtual trackpad.
+    pub fn evdev_path(&self) -> Result<PathBuf, std::io::Error> {
+        match &self.handle {
+            Some(handle) => handle.evdev_path(),
+            None => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "a dry run has no virtual trackpad"))
+        }
+    }
+
+    /// Destroys the device. The kernel releases any button it still holds
+    /// as it goes, which is also what keeps a button from staying stuck
+    /// when the process dies without getting here (a crash, or the OOM
+    /// killer): the uinput fd closing destroys the device just the same.
     pub fn destruct(self) -> Result<(), std::io::Error> {
-        self.handle.dev_destroy()
+        self.handle.map_or(Ok(()), |handle| handle.dev_destroy())
     }
-}
\ No newline at end of file
+
+
+    /// Swaps the uinput device for a freshly created one, for when writes
+    /// to the old one keep failing. Anyone subscribed to button events
+    /// stays subscribed. The old device is destroyed if it still can be;
+    /// any clones of it (like the timer thread's) still point at it, so 
+    /// those need replacing too.
+    pub fn recreate(&mut self) -> Result<(), std::io::Error> {
+
+        if self.is_dry_run() {
+            return Ok(());
+        }
+        let mode = if self.is_touchscreen() { OutputMode::Touch } else { OutputMode::Pointer };
+        let fresh = start_handler_for(mode)?;
+        let old_handle = std::mem::replace(&mut self.handle, fresh.handle);
+        if let Some(Err(e)) = old_handle.map(|handle| handle.dev_destroy()) {
+            debug!("Could not destroy the old virtual trackpad: {}", e);
+        }
+
+        self.mouse_is_down = false;
+        if let Some(touch) = &mut self.touch {
+            touch.contact = None;
+        }
+        let _ = self.button_events.send(false);
+        Ok(())
+    }
+}
+
+
+// motion a dry run would have written, for tests to count
+#[cfg(test)]
+pub static DRY_RUN_MOVES: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+
+/// Writes `events` to the device behind `handle`, or for a dry run (with
+/// no device), logs them.
+fn write(handle: &Option<UInputHandle<File>>, events: &[input_linux::sys::input_event]) -> Result<(), std::io::Error> {
+    match handle {
+        Some(handle) => handle.write(events).map(|_| ()).inspect_err(|_| stats::count_write_error()),
+        None => {
+            let buttons_changed = events.iter().any(|event| event.type_ == EventKind::Key as u16);
+            if buttons_changed {
+                info!("Dry run: {}", describe_events(events));
+            } else {
+                trace!("Dry run: {}", describe_events(events));
+                #[cfg(test)]
+                DRY_RUN_MOVES.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+            Ok(())
+        }
+    }
+}
+
+
+/// `events` as something readable, for a dry run's log: like
+/// `press left`, `release right`, or `move x 3, y -1`. Syncs are left out,
+/// since every write ends with one.
+fn describe_events(events: &[input_linux::sys::input_event]) -> String {
+    let button_name = |code: u16| match code {
+        c if c == Key::ButtonLeft as u16 => "left".to_string(),
+        c if c == Key::ButtonMiddle as u16 => "middle".to_string(),
+        c if c == Key::ButtonRight as u16 => "right".to_string(),
+        c => format!("button {c:#x}")
+    };
+    let relative_name = |code: u16| match code {
+        c if c == RelativeAxis::X as u16 => "x".to_string(),
+        c if c == RelativeAxis::Y as u16 => "y".to_string(),
+        c => format!("axis {c}")
+    };
+
+    events.iter()
+        .filter_map(|event| match event.type_ {
+            t if t == EventKind::Key as u16 => Some(format!(
+                "{} {}", if event.value != 0 { "press" } else { "release" }, button_name(event.code)
+            )),
+            t if t == EventKind::Relative as u16 => Some(format!("move {} {}", relative_name(event.code), event.value)),
+            // the touchscreen's finger, by slot, tracking ID and position
+            t if t == EventKind::Absolute as u16 => Some(format!("absolute axis {} to {}", event.code, event.value)),
+            _ => None
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn own_device_is_excluded() {
+        assert!(is_virtual_trackpad(VIRTUAL_DEVICE_NAME, VIRTUAL_VENDOR_ID as u32, VIRTUAL_PRODUCT_ID as u32));
+    }
+
+    #[test]
+    fn similar_real_trackpads_are_not() {
+        let (vendor, product) = (VIRTUAL_VENDOR_ID as u32, VIRTUAL_PRODUCT_ID as u32);
+
+        // a real one that happens to share the name, or the IDs, but not both
+        assert!(!is_virtual_trackpad(VIRTUAL_DEVICE_NAME, 0x06cb, 0xcd7d));
+        assert!(!is_virtual_trackpad("Synaptics Virtual trackpad", vendor, product));
+        assert!(!is_virtual_trackpad("Virtual trackpad", vendor, product));
+        assert!(!is_virtual_trackpad(&format!("{VIRTUAL_DEVICE_NAME} "), vendor, product));
+        assert!(!is_virtual_trackpad("SynPS/2 Synaptics TouchPad", 0x0002, 0x0007));
+    }
+}